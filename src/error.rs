@@ -1,4 +1,6 @@
-use crate::location::Location;
+use crate::lexer::Lexeme;
+use crate::location::{HasLocation, Location};
+use crate::parser::KEYWORDS;
 use crate::token::Token;
 use std::fmt;
 
@@ -10,11 +12,37 @@ pub enum ErrorLevel {
     Error,
 }
 
+/// Stable identifiers for each kind of diagnostic, shown as `error[E0001]: ...`. Codes for
+/// warnings start with a W.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ErrorCode {
+    Internal,
+    UnexpectedToken,
+    UnterminatedString,
+    UnbalancedBracket,
+    UnexpectedEof,
+    InvalidPattern,
+    InvalidExpression,
+    NotImplemented,
+    UnboundSymbol,
+    NestingTooDeep,
+    ArityMismatch,
+    DuplicateFixity,
+    UnusedBinding,
+    FloatPattern,
+    Hole,
+    UnknownAttribute,
+}
+
 #[derive(Debug)]
 pub struct ParseError<'a> {
     location: Location<'a>,
     level: ErrorLevel,
+    code: ErrorCode,
     message: String,
+    /// Other locations which help explain the error, each with a short message.
+    notes: Vec<(Location<'a>, String)>,
+    source: Option<&'a str>,
 }
 
 pub type ParseResult<'a, T> = Result<T, ParseError<'a>>;
@@ -33,25 +61,91 @@ impl std::fmt::Display for ErrorLevel {
     }
 }
 
+impl ErrorLevel {
+    fn ansi_color(&self) -> &'static str {
+        match self {
+            ErrorLevel::Info => "\x1b[1;36m",
+            ErrorLevel::Warning => "\x1b[1;33m",
+            ErrorLevel::Error => "\x1b[1;31m",
+        }
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorCode::Internal => "E0000",
+                ErrorCode::UnexpectedToken => "E0001",
+                ErrorCode::UnterminatedString => "E0002",
+                ErrorCode::UnbalancedBracket => "E0003",
+                ErrorCode::UnexpectedEof => "E0004",
+                ErrorCode::InvalidPattern => "E0005",
+                ErrorCode::InvalidExpression => "E0006",
+                ErrorCode::NotImplemented => "E0007",
+                ErrorCode::UnboundSymbol => "E0008",
+                ErrorCode::NestingTooDeep => "E0009",
+                ErrorCode::ArityMismatch => "E0010",
+                ErrorCode::DuplicateFixity => "E0011",
+                ErrorCode::UnusedBinding => "W0001",
+                ErrorCode::FloatPattern => "W0002",
+                ErrorCode::Hole => "W0003",
+                ErrorCode::UnknownAttribute => "W0004",
+            }
+        )
+    }
+}
+
 impl<'a> std::error::Error for ParseError<'a> {}
 
+impl<'a> HasLocation<'a> for ParseError<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        &self.location
+    }
+}
+
 impl<'a> ParseError<'a> {
-    pub fn error<T>(location: Location<'a>, message: T) -> ParseError<'a>
+    pub fn error<T>(code: ErrorCode, location: Location<'a>, message: T) -> ParseError<'a>
     where
         T: fmt::Display,
     {
         ParseError {
-            location: location,
+            location,
             level: ErrorLevel::Error,
+            code,
+            message: format!("{}", message),
+            notes: Vec::new(),
+            source: None,
+        }
+    }
+
+    /// A non-fatal diagnostic, which is reported without failing compilation.
+    pub fn warning<T>(code: ErrorCode, location: Location<'a>, message: T) -> ParseError<'a>
+    where
+        T: fmt::Display,
+    {
+        ParseError {
+            location,
+            level: ErrorLevel::Warning,
+            code,
             message: format!("{}", message),
+            notes: Vec::new(),
+            source: None,
         }
     }
 
     pub fn not_impl(location: Location<'a>) -> ParseError<'a> {
         ParseError {
-            location: location,
+            location,
             level: ErrorLevel::Error,
+            code: ErrorCode::NotImplemented,
             message: "parsing this is not implemented".to_string(),
+            notes: Vec::new(),
+            source: None,
         }
     }
 
@@ -59,16 +153,330 @@ impl<'a> ParseError<'a> {
     where
         T: fmt::Display,
     {
+        if token.lexeme == Lexeme::Eof {
+            return ParseError::error(
+                ErrorCode::UnexpectedEof,
+                token.location,
+                format!("hit EOF but expected {}", expected),
+            );
+        }
+        let mut message = format!("unexpected token ({token}) found. expected {}", expected);
+        if let Lexeme::Identifier(name) = token.lexeme {
+            if let Some(keyword) = suggest_keyword(name) {
+                message.push_str(&format!("; did you mean `{}`?", keyword));
+            }
+        }
         ParseError {
-            location: token.location.clone(),
+            location: token.location,
             level: ErrorLevel::Error,
-            message: format!("unexpected token ({token}) found. expected {}", expected),
+            code: ErrorCode::UnexpectedToken,
+            message,
+            notes: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn level(&self) -> &ErrorLevel {
+        &self.level
+    }
+
+    pub fn is_warning(&self) -> bool {
+        matches!(self.level, ErrorLevel::Warning)
+    }
+
+    /// Attach the source text this error refers to, so that it renders with a snippet of the
+    /// offending line.
+    pub fn with_source(mut self, source: &'a str) -> ParseError<'a> {
+        self.source = Some(source);
+        self
+    }
+
+    /// Point out `location` as well, such as where something involved in the error was declared.
+    pub fn with_note<T>(mut self, location: Location<'a>, message: T) -> ParseError<'a>
+    where
+        T: fmt::Display,
+    {
+        self.notes.push((location, message.to_string()));
+        self
+    }
+
+    pub fn notes(&self) -> &[(Location<'a>, String)] {
+        &self.notes
+    }
+
+    /// Render this error as a single-line JSON object, for consumption by editors and other tools.
+    /// Notes, if there are any, are a list of objects under "notes".
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"file\":{},\"line\":{},\"col\":{},\"level\":{},\"code\":{},\"message\":{}",
+            json_string(self.location.filename),
+            self.location.line,
+            self.location.col,
+            json_string(&self.level.to_string()),
+            json_string(&self.code.to_string()),
+            json_string(&self.message),
+        );
+        if !self.notes.is_empty() {
+            let notes: Vec<String> = self
+                .notes
+                .iter()
+                .map(|(location, message)| {
+                    format!(
+                        "{{\"file\":{},\"line\":{},\"col\":{},\"message\":{}}}",
+                        json_string(location.filename),
+                        location.line,
+                        location.col,
+                        json_string(message),
+                    )
+                })
+                .collect();
+            json.push_str(&format!(",\"notes\":[{}]", notes.join(",")));
+        }
+        json.push('}');
+        json
+    }
+
+    /// Render this error for a terminal, highlighting the level and caret with ANSI colors when
+    /// `use_color` is set. Each note follows, with its own caret. `render(false)` is the same as
+    /// the `Display` output.
+    pub fn render(&self, use_color: bool) -> String {
+        let (color, note_color, reset) = if use_color {
+            (
+                self.level.ansi_color(),
+                ErrorLevel::Info.ansi_color(),
+                ANSI_RESET,
+            )
+        } else {
+            ("", "", "")
+        };
+        let mut rendered = format!(
+            "{}: {}{}[{}]{}: {}",
+            self.location, color, self.level, self.code, reset, self.message
+        );
+        rendered.push_str(&self.caret(&self.location, color, reset));
+        for (location, message) in &self.notes {
+            rendered.push_str(&format!(
+                "\n{}: {}note{}: {}",
+                location, note_color, reset, message
+            ));
+            rendered.push_str(&self.caret(location, note_color, reset));
         }
+        rendered
+    }
+
+    /// The source line of `location` with a caret under its column, or nothing without source.
+    fn caret(&self, location: &Location<'a>, color: &str, reset: &str) -> String {
+        let line = match self.source_line(location) {
+            Some(line) => line,
+            None => return String::new(),
+        };
+        // Keep tabs so that the caret lines up with the source line.
+        let col = usize::try_from(location.col).unwrap_or(0).max(1);
+        let padding: String = line
+            .chars()
+            .take(col - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!("\n{}\n{}{}^{}", line, padding, color, reset)
+    }
+
+    fn source_line(&self, location: &Location<'a>) -> Option<&'a str> {
+        let line = usize::try_from(location.line).ok()?.checked_sub(1)?;
+        self.source?.lines().nth(line)
     }
 }
 
 impl<'a> fmt::Display for ParseError<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {}: {}", self.location, self.level, self.message)
+        write!(f, "{}", self.render(false))
+    }
+}
+
+/// The keyword that `name` is most likely a typo of, if any. Names shorter than three letters are
+/// never taken for typos, and short keywords only tolerate a single edit, so that ordinary short
+/// names such as `t` or `xs` are not mistaken for `let` or `as`.
+pub fn suggest_keyword(name: &str) -> Option<&'static str> {
+    if name.chars().count() < 3 {
+        return None;
+    }
+    KEYWORDS
+        .iter()
+        .filter(|keyword| **keyword != name)
+        .map(|keyword| (edit_distance(name, keyword), *keyword))
+        .filter(|(distance, keyword)| *distance <= 2.min(keyword.len() - 1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, keyword)| keyword)
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Collects diagnostics while parsing so that more than one can be reported per run.
+#[derive(Debug, Default)]
+pub struct Diagnostics<'a> {
+    source: Option<&'a str>,
+    errors: Vec<ParseError<'a>>,
+    warnings: Vec<ParseError<'a>>,
+}
+
+impl<'a> Diagnostics<'a> {
+    /// Diagnostics for errors within `source`, which is attached to each recorded error.
+    pub fn new(source: &'a str) -> Diagnostics<'a> {
+        Diagnostics {
+            source: Some(source),
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn error(&mut self, err: ParseError<'a>) {
+        let err = self.attach_source(err);
+        self.errors.push(err);
+    }
+
+    /// Record a diagnostic which does not, by itself, fail compilation.
+    pub fn warning(&mut self, warning: ParseError<'a>) {
+        let warning = self.attach_source(warning);
+        self.warnings.push(warning);
+    }
+
+    pub fn errors(&self) -> &[ParseError<'a>] {
+        &self.errors
+    }
+
+    pub fn warnings(&self) -> &[ParseError<'a>] {
+        &self.warnings
+    }
+
+    fn attach_source(&self, err: ParseError<'a>) -> ParseError<'a> {
+        match self.source {
+            Some(source) => err.with_source(source),
+            None => err,
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(line: i32, col: i32) -> Location<'static> {
+        Location {
+            filename: "test.mv",
+            line,
+            col,
+        }
+    }
+
+    #[test]
+    fn renders_source_line_and_caret() {
+        let source = "id x = x\nf = a + * b\n";
+        let err = ParseError::error(ErrorCode::InvalidExpression, location(2, 9), "bad operand")
+            .with_source(source);
+        assert_eq!(
+            err.to_string(),
+            "test.mv:2:9: error[E0006]: bad operand\nf = a + * b\n        ^"
+        );
+    }
+
+    #[test]
+    fn renders_with_color() {
+        let err = ParseError::error(ErrorCode::UnexpectedToken, location(1, 4), "bad")
+            .with_source("id x = )");
+        let plain = err.render(false);
+        assert!(!plain.contains('\x1b'));
+        assert_eq!(plain, err.to_string());
+        let colored = err.render(true);
+        assert!(colored.contains("\x1b[1;31merror[E0001]\x1b[0m"));
+        assert!(colored.ends_with("\x1b[1;31m^\x1b[0m"));
+    }
+
+    #[test]
+    fn suggests_close_keywords() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(suggest_keyword("els"), Some("else"));
+        assert_eq!(suggest_keyword("ese"), Some("else"));
+        assert_eq!(suggest_keyword("thn"), Some("then"));
+        assert_eq!(suggest_keyword("inn"), Some("in"));
+        assert_eq!(suggest_keyword("x"), None);
+        assert_eq!(suggest_keyword("else"), None);
+        assert_eq!(suggest_keyword("elephant"), None);
+    }
+
+    #[test]
+    fn renders_json() {
+        let err = ParseError::error(
+            ErrorCode::UnexpectedToken,
+            location(3, 4),
+            "found \"x\"\tthen\nmore",
+        );
+        assert_eq!(
+            err.to_json(),
+            r#"{"file":"test.mv","line":3,"col":4,"level":"error","code":"E0001","message":"found \"x\"\tthen\nmore"}"#
+        );
+    }
+
+    #[test]
+    fn renders_notes() {
+        let source = "f x =\ng = 1\n";
+        let err = ParseError::error(ErrorCode::InvalidExpression, location(1, 5), "no body")
+            .with_note(location(1, 1), "declared here");
+        assert_eq!(
+            err.to_json(),
+            r#"{"file":"test.mv","line":1,"col":5,"level":"error","code":"E0006","message":"no body","notes":[{"file":"test.mv","line":1,"col":1,"message":"declared here"}]}"#
+        );
+        let err = err.with_source(source);
+        assert_eq!(
+            err.to_string(),
+            "test.mv:1:5: error[E0006]: no body\nf x =\n    ^\n\
+             test.mv:1:1: note: declared here\nf x =\n^"
+        );
+        assert!(err
+            .render(true)
+            .contains("test.mv:1:1: \x1b[1;36mnote\x1b[0m: declared here"));
+    }
+
+    #[test]
+    fn renders_without_source() {
+        let err = ParseError::error(ErrorCode::Internal, location(1, 1), "oops");
+        assert_eq!(err.to_string(), "test.mv:1:1: error[E0000]: oops");
     }
 }
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::{ErrorCode, ParseError, ParseResult};
+use crate::identifier::Identifier;
+use crate::lexer::{Lexeme, Lexer};
+use crate::location::{HasLocation, Location};
+use crate::token::Token;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Associativity {
+    Left,
+    Right,
+    /// `a == b == c` is an error, rather than grouping either way.
+    None,
+}
+
+/// How tightly a binary operator binds, from 0 (loosest) to 9, and how it groups with itself.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Fixity {
+    pub associativity: Associativity,
+    pub precedence: u8,
+}
+
+impl Fixity {
+    pub fn new(associativity: Associativity, precedence: u8) -> Self {
+        Fixity {
+            associativity,
+            precedence,
+        }
+    }
+}
+
+impl fmt::Display for Fixity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self.associativity {
+            Associativity::Left => "infixl",
+            Associativity::Right => "infixr",
+            Associativity::None => "infix",
+        };
+        write!(f, "{} {}", keyword, self.precedence)
+    }
+}
+
+/// The fixity of a function applied infix with backticks, as in ``x `div` y``: the same as `*`.
+pub const BACKTICK_FIXITY: Fixity = Fixity {
+    associativity: Associativity::Left,
+    precedence: 7,
+};
+
+/// The fixity of every operator which may be used infix. Application binds tighter than any of
+/// these, and the prefix operators `-` and `!` sit between application and the tightest binary
+/// operator.
+#[derive(Debug, Clone)]
+pub struct FixityTable<'a> {
+    operators: HashMap<&'a str, Fixity>,
+}
+
+impl<'a> FixityTable<'a> {
+    /// The fixities of the operators every program starts with.
+    pub fn builtin() -> Self {
+        use Associativity::*;
+        let builtins: &[(&[&'static str], Fixity)] = &[
+            (&["$"], Fixity::new(Right, 0)),
+            (&["||"], Fixity::new(Right, 2)),
+            (&["&&"], Fixity::new(Right, 3)),
+            (&["==", "!=", "<", "<=", ">", ">="], Fixity::new(None, 4)),
+            (&[":", "++"], Fixity::new(Right, 5)),
+            (&["+", "-"], Fixity::new(Left, 6)),
+            (&["*", "/", "%"], Fixity::new(Left, 7)),
+            (&["^"], Fixity::new(Right, 8)),
+            (&["."], Fixity::new(Left, 9)),
+        ];
+        let mut operators = HashMap::new();
+        for (names, fixity) in builtins {
+            for name in names.iter() {
+                operators.insert(*name, *fixity);
+            }
+        }
+        FixityTable { operators }
+    }
+
+    pub fn get(&self, op: &str) -> Option<Fixity> {
+        self.operators.get(op).copied()
+    }
+
+    /// Give `op` a fixity, replacing any it already had.
+    pub fn declare(&mut self, op: &'a str, fixity: Fixity) {
+        self.operators.insert(op, fixity);
+    }
+}
+
+impl<'a> Default for FixityTable<'a> {
+    fn default() -> Self {
+        FixityTable::builtin()
+    }
+}
+
+/// A fixity declaration, such as `infixl 6 +`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FixityDecl<'a> {
+    pub location: Location<'a>,
+    pub fixity: Fixity,
+    pub operator: Identifier<'a>,
+}
+
+impl<'a> HasLocation<'a> for FixityDecl<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        &self.location
+    }
+}
+
+/// The associativity introduced by `keyword`, if it begins a fixity declaration.
+pub fn fixity_keyword(keyword: &str) -> Option<Associativity> {
+    match keyword {
+        "infixl" => Some(Associativity::Left),
+        "infixr" => Some(Associativity::Right),
+        "infix" => Some(Associativity::None),
+        _ => None,
+    }
+}
+
+/// Parse the rest of a fixity declaration, after its keyword at `location`.
+pub fn parse_fixity_decl<'a>(
+    location: Location<'a>,
+    associativity: Associativity,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, FixityDecl<'a>> {
+    let precedence = match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Signed(value),
+            ..
+        } if (0..=9).contains(&value) => value as u8,
+        Token {
+            location,
+            lexeme: Lexeme::Signed(value),
+            ..
+        } => {
+            return Err(ParseError::error(
+                ErrorCode::UnexpectedToken,
+                location,
+                format!("precedence {value} is out of range; it must be from 0 to 9"),
+            ))
+        }
+        token => return Err(ParseError::unexpected(token, "a precedence from 0 to 9")),
+    };
+    lexer.advance()?;
+    let operator = match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Operator(op),
+            ..
+        } if !is_reserved_operator(op) => Identifier::new(op, location),
+        Token {
+            location,
+            lexeme: Lexeme::Operator(op),
+            ..
+        } => return Err(reserved_operator(location, op)),
+        Token {
+            location,
+            lexeme: Lexeme::PathSep,
+            ..
+        } => return Err(reserved_operator(location, "::")),
+        token => return Err(ParseError::unexpected(token, "an operator")),
+    };
+    lexer.advance()?;
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Semicolon | Lexeme::Eof,
+            ..
+        } => Ok(FixityDecl {
+            location,
+            fixity: Fixity::new(associativity, precedence),
+            operator,
+        }),
+        token => Err(ParseError::unexpected(
+            token,
+            "the end of the line after the fixity declaration",
+        )),
+    }
+}
+
+/// Operators which belong to the grammar itself, so they can never be declared or used infix.
+pub fn is_reserved_operator(op: &str) -> bool {
+    matches!(
+        op,
+        "=" | "=>" | "->" | "<-" | "::" | "@" | "|" | ".." | "..="
+    )
+}
+
+fn reserved_operator<'a>(location: Location<'a>, op: &str) -> ParseError<'a> {
+    ParseError::error(
+        ErrorCode::UnexpectedToken,
+        location,
+        format!("`{op}` is reserved by the grammar, so it cannot be given a fixity"),
+    )
+}
+
+/// Read ahead through the whole input for fixity declarations, so that an operator's fixity
+/// applies even to uses which come before its declaration. Malformed declarations are skipped
+/// here and reported when the parser reaches them.
+pub fn scan_fixities<'a>(lexer: &mut Lexer<'a>) -> FixityTable<'a> {
+    let mut table = lexer.fixities.as_ref().clone();
+    lexer.lookahead(|lexer| {
+        let mut at_line_start = true;
+        while !lexer.at_eof() {
+            let token = lexer.peek();
+            if lexer.advance().is_err() {
+                break;
+            }
+            match token.lexeme {
+                Lexeme::Identifier(keyword) if at_line_start => {
+                    if let Some(associativity) = fixity_keyword(keyword) {
+                        let decl = lexer.lookahead(|lexer| {
+                            parse_fixity_decl(token.location, associativity, lexer)
+                        });
+                        if let Ok(decl) = decl {
+                            table.declare(decl.operator.name, decl.fixity);
+                        }
+                    }
+                    at_line_start = false;
+                }
+                Lexeme::Semicolon => at_line_start = true,
+                _ => at_line_start = false,
+            }
+        }
+    });
+    table
+}
+
+/// Each operator may be given a fixity only once per file. Returns an error for every
+/// declaration which repeats an earlier one.
+pub fn duplicate_fixities<'a, 'b, I>(decls: I) -> Vec<ParseError<'a>>
+where
+    'a: 'b,
+    I: IntoIterator<Item = &'b FixityDecl<'a>>,
+{
+    let mut seen: Vec<&FixityDecl<'a>> = Vec::new();
+    let mut errors = Vec::new();
+    for decl in decls {
+        match seen
+            .iter()
+            .find(|earlier| earlier.operator.name == decl.operator.name)
+        {
+            Some(earlier) => errors.push(ParseError::error(
+                ErrorCode::DuplicateFixity,
+                decl.location,
+                format!(
+                    "the fixity of {} was already declared at {}",
+                    decl.operator.name, earlier.location
+                ),
+            )),
+            None => seen.push(decl),
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(input: &'static str) -> FixityTable<'static> {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance().unwrap();
+        scan_fixities(&mut lexer)
+    }
+
+    #[test]
+    fn scanning_finds_declarations_anywhere_in_the_file() {
+        let table = scan("f = a <+> b\ninfixr 3 <+>\ng = 1\ninfix 4 ==");
+        assert_eq!(table.get("<+>"), Some(Fixity::new(Associativity::Right, 3)));
+        assert_eq!(table.get("=="), Some(Fixity::new(Associativity::None, 4)));
+        assert_eq!(table.get("+"), Some(Fixity::new(Associativity::Left, 6)));
+        assert_eq!(table.get("<$>"), None);
+    }
+
+    #[test]
+    fn scanning_skips_malformed_declarations() {
+        let table = scan("infixl 12 <+>\ninfixl 1 =>\nf = infixl 1 <->");
+        assert_eq!(table.get("<+>"), None);
+        assert_eq!(table.get("=>"), None);
+        assert_eq!(table.get("<->"), None);
+    }
+
+    fn parse_err(input: &'static str) -> String {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance().unwrap();
+        let location = lexer.location;
+        lexer.advance().unwrap();
+        parse_fixity_decl(location, Associativity::Left, &mut lexer)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn reserved_operators_cannot_be_declared() {
+        assert!(is_reserved_operator("=>"));
+        assert!(is_reserved_operator("::"));
+        assert!(!is_reserved_operator("<+>"));
+        let err = parse_err("infixl 5 =>");
+        assert!(err.starts_with("test.mv:1:10: error[E0001]:"), "{}", err);
+        assert!(err.contains("`=>` is reserved by the grammar"), "{}", err);
+        let err = parse_err("infixl 5 ::");
+        assert!(err.starts_with("test.mv:1:10: error[E0001]:"), "{}", err);
+        assert!(err.contains("`::` is reserved"), "{}", err);
+    }
+}
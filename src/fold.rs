@@ -0,0 +1,277 @@
+use crate::parser::{DoStatement, Expr, Generator, InterpPart, PatternExpr};
+
+/// The value of `lhs op rhs` for integer literals, or None when `op` is not arithmetic or the
+/// result is undefined, as for division by zero or on overflow.
+fn fold_integers(op: &str, lhs: i64, rhs: i64) -> Option<i64> {
+    match op {
+        "+" => lhs.checked_add(rhs),
+        "-" => lhs.checked_sub(rhs),
+        "*" => lhs.checked_mul(rhs),
+        "/" => lhs.checked_div(rhs),
+        _ => None,
+    }
+}
+
+fn fold_boxed(mut expr: Box<Expr>) -> Box<Expr> {
+    *expr = fold_constants(*expr);
+    expr
+}
+
+fn fold_statements(statements: Vec<DoStatement>) -> Vec<DoStatement> {
+    statements
+        .into_iter()
+        .map(|statement| match statement {
+            DoStatement::Expr(expr) => DoStatement::Expr(fold_constants(expr)),
+            DoStatement::Let { binding, value } => DoStatement::Let {
+                binding,
+                value: fold_constants(value),
+            },
+            DoStatement::Bind { binding, value } => DoStatement::Bind {
+                binding,
+                value: fold_constants(value),
+            },
+        })
+        .collect()
+}
+
+/// Replace each arithmetic operation on two integer literals with its result, working bottom-up
+/// so that `1 + 2 * 3` becomes `7`. Anything which is not constant is left as it was.
+pub fn fold_constants(expr: Expr) -> Expr {
+    match expr {
+        Expr::BinaryOp { op, lhs, rhs } => {
+            let lhs = fold_boxed(lhs);
+            let rhs = fold_boxed(rhs);
+            if let (
+                Expr::LiteralInteger {
+                    location,
+                    value: lhs_value,
+                },
+                Expr::LiteralInteger {
+                    value: rhs_value, ..
+                },
+            ) = (lhs.as_ref(), rhs.as_ref())
+            {
+                if let Some(value) = fold_integers(op.name, *lhs_value, *rhs_value) {
+                    return Expr::LiteralInteger {
+                        location: *location,
+                        value,
+                    };
+                }
+            }
+            Expr::BinaryOp { op, lhs, rhs }
+        }
+        Expr::UnaryOp { op, operand } => Expr::UnaryOp {
+            op,
+            operand: fold_boxed(operand),
+        },
+        Expr::Lambda {
+            location,
+            params,
+            body,
+        } => Expr::Lambda {
+            location,
+            params,
+            body: fold_boxed(body),
+        },
+        Expr::Let {
+            location,
+            binding,
+            value,
+            body,
+        } => Expr::Let {
+            location,
+            binding,
+            value: fold_boxed(value),
+            body: fold_boxed(body),
+        },
+        Expr::LetRec {
+            location,
+            bindings,
+            body,
+        } => Expr::LetRec {
+            location,
+            bindings: bindings
+                .into_iter()
+                .map(|(binding, value)| (binding, fold_constants(value)))
+                .collect(),
+            body: fold_boxed(body),
+        },
+        Expr::LetPattern {
+            location,
+            pattern,
+            value,
+            body,
+        } => Expr::LetPattern {
+            location,
+            pattern,
+            value: fold_boxed(value),
+            body: fold_boxed(body),
+        },
+        Expr::If {
+            location,
+            condition,
+            then_branch,
+            else_branch,
+        } => Expr::If {
+            location,
+            condition: fold_boxed(condition),
+            then_branch: fold_boxed(then_branch),
+            else_branch: fold_boxed(else_branch),
+        },
+        Expr::Match {
+            location,
+            subject,
+            pattern_exprs,
+        } => Expr::Match {
+            location,
+            subject: fold_boxed(subject),
+            pattern_exprs: pattern_exprs
+                .into_iter()
+                .map(|arm| PatternExpr {
+                    predicate: arm.predicate,
+                    guard: arm.guard.map(fold_constants),
+                    expr: fold_constants(arm.expr),
+                })
+                .collect(),
+        },
+        Expr::Callsite {
+            span,
+            function,
+            arguments,
+        } => Expr::Callsite {
+            span,
+            function: fold_boxed(function),
+            arguments: arguments.into_iter().map(fold_boxed).collect(),
+        },
+        Expr::TupleCtor { span, dims } => Expr::TupleCtor {
+            span,
+            dims: dims.into_iter().map(fold_boxed).collect(),
+        },
+        Expr::List { span, items } => Expr::List {
+            span,
+            items: items.into_iter().map(fold_boxed).collect(),
+        },
+        Expr::Comprehension {
+            span,
+            output,
+            generators,
+            guards,
+        } => Expr::Comprehension {
+            span,
+            output: fold_boxed(output),
+            generators: generators
+                .into_iter()
+                .map(|generator| Generator {
+                    pattern: generator.pattern,
+                    source: fold_constants(generator.source),
+                })
+                .collect(),
+            guards: guards
+                .into_iter()
+                .map(|(after, guard)| (after, fold_constants(guard)))
+                .collect(),
+        },
+        Expr::Record { location, fields } => Expr::Record {
+            location,
+            fields: fields
+                .into_iter()
+                .map(|(field, value)| (field, fold_constants(value)))
+                .collect(),
+        },
+        Expr::Range {
+            location,
+            start,
+            step,
+            end,
+            inclusive,
+        } => Expr::Range {
+            location,
+            start: start.map(fold_boxed),
+            step: step.map(fold_boxed),
+            end: end.map(fold_boxed),
+            inclusive,
+        },
+        Expr::FieldAccess { subject, field } => Expr::FieldAccess {
+            subject: fold_boxed(subject),
+            field,
+        },
+        Expr::Do {
+            location,
+            statements,
+            result,
+        } => Expr::Do {
+            location,
+            statements: fold_statements(statements),
+            result: fold_boxed(result),
+        },
+        Expr::Block {
+            location,
+            statements,
+            result,
+        } => Expr::Block {
+            location,
+            statements: fold_statements(statements),
+            result: fold_boxed(result),
+        },
+        Expr::Interp { location, parts } => Expr::Interp {
+            location,
+            parts: parts
+                .into_iter()
+                .map(|part| match part {
+                    InterpPart::Expr(expr) => InterpPart::Expr(fold_constants(expr)),
+                    literal => literal,
+                })
+                .collect(),
+        },
+        Expr::LiteralInteger { .. }
+        | Expr::LiteralFloat { .. }
+        | Expr::LiteralString { .. }
+        | Expr::LiteralChar { .. }
+        | Expr::LiteralBool { .. }
+        | Expr::Symbol { .. }
+        | Expr::Path { .. }
+        | Expr::Hole { .. } => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use crate::parser::{to_sexpr, Item};
+
+    fn folded(input: &'static str) -> String {
+        let mut module = parse_str("test.mv", input).unwrap();
+        match module.items.remove(0) {
+            Item::Decl(mut decl) => to_sexpr(&fold_constants(decl.clauses.remove(0).body)),
+            item => panic!("expected a decl, got {:?}", item),
+        }
+    }
+
+    #[test]
+    fn folds_integer_arithmetic() {
+        assert_eq!(folded("f = 1 + 2 * 3"), "(lit-int 7)");
+        assert_eq!(folded("f = (10 - 4) / 3"), "(lit-int 2)");
+        assert_eq!(
+            folded("f x = g (2 * 3) x"),
+            "(callsite (symbol g) (lit-int 6) (symbol x))"
+        );
+    }
+
+    #[test]
+    fn leaves_non_constant_expressions_alone() {
+        assert_eq!(
+            folded("f x = x + 1"),
+            "(binary-op + (symbol x) (lit-int 1))"
+        );
+        assert_eq!(
+            folded("f x = x + 1 * 2"),
+            "(binary-op + (symbol x) (lit-int 2))"
+        );
+        assert_eq!(folded("f = 1 / 0"), "(binary-op / (lit-int 1) (lit-int 0))");
+        assert_eq!(
+            folded("f = 9223372036854775807 + 1"),
+            "(binary-op + (lit-int 9223372036854775807) (lit-int 1))"
+        );
+    }
+}
@@ -1,18 +1,45 @@
+use crate::interner::Symbol;
 use crate::location::{HasLocation, Location};
 
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Identifier<'a> {
     pub name: &'a str,
     location: Location<'a>,
+    /// The interned name, when the lexer which read it had an interner.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    symbol: Option<Symbol>,
 }
 
 impl<'a> Identifier<'a> {
     #[inline]
     pub fn new(name: &'a str, location: Location<'a>) -> Self {
         Identifier {
-            name: name,
-            location: location,
+            name,
+            location,
+            symbol: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_symbol(self, symbol: Symbol) -> Self {
+        Identifier {
+            symbol: Some(symbol),
+            ..self
+        }
+    }
+
+    pub fn symbol(&self) -> Option<Symbol> {
+        self.symbol
+    }
+
+    /// Whether both identifiers have the same name. When both were interned this compares their
+    /// symbols rather than their text.
+    pub fn same_name(&self, other: &Identifier) -> bool {
+        match (self.symbol, other.symbol) {
+            (Some(symbol), Some(other)) => symbol == other,
+            _ => self.name == other.name,
         }
     }
 }
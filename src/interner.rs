@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::parser::KEYWORDS;
+
+/// An interned identifier. Two symbols from the same `Interner` are equal exactly when their
+/// names are, so comparing them is an integer comparison.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// Maps identifier text to `Symbol`s and back. The keywords are interned first, so that checking
+/// whether a symbol is a keyword needs no string comparison at all.
+#[derive(Debug)]
+pub struct Interner<'a> {
+    symbols: HashMap<&'a str, Symbol>,
+    names: Vec<&'a str>,
+}
+
+impl<'a> Default for Interner<'a> {
+    fn default() -> Self {
+        Interner::new()
+    }
+}
+
+impl<'a> Interner<'a> {
+    pub fn new() -> Self {
+        let mut interner = Interner {
+            symbols: HashMap::new(),
+            names: Vec::new(),
+        };
+        for keyword in KEYWORDS {
+            interner.intern(keyword);
+        }
+        interner
+    }
+
+    /// The symbol for `name`, allocating a new one the first time `name` is seen.
+    pub fn intern(&mut self, name: &'a str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name);
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The symbol for `name`, if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// The name that `symbol` was interned from.
+    pub fn resolve(&self, symbol: Symbol) -> &'a str {
+        self.names[symbol.0 as usize]
+    }
+
+    pub fn is_keyword(&self, symbol: Symbol) -> bool {
+        (symbol.0 as usize) < KEYWORDS.len()
+    }
+
+    /// The number of distinct names interned, including the keywords.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning_is_stable() {
+        let mut interner = Interner::new();
+        let source = String::from("foo bar foo");
+        let names: Vec<&str> = source.split(' ').collect();
+        let foo = interner.intern(names[0]);
+        let bar = interner.intern(names[1]);
+        assert_eq!(interner.intern(names[2]), foo);
+        assert_ne!(foo, bar);
+        assert_eq!(interner.resolve(bar), "bar");
+        assert_eq!(interner.len(), KEYWORDS.len() + 2);
+    }
+
+    #[test]
+    fn keywords_are_interned_up_front() {
+        let mut interner = Interner::new();
+        let let_symbol = interner.get("let").unwrap();
+        assert!(interner.is_keyword(let_symbol));
+        assert_eq!(interner.intern("let"), let_symbol);
+        let symbol = interner.intern("lets");
+        assert!(!interner.is_keyword(symbol));
+    }
+}
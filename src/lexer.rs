@@ -1,6 +1,11 @@
-use crate::error::{ParseError, ParseResult};
+use crate::error::{ErrorCode, ParseError, ParseResult};
+use crate::fixity::FixityTable;
+use crate::identifier::Identifier;
+use crate::interner::Interner;
 use crate::location::Location;
-use crate::token::Token;
+use crate::parser::is_keyword;
+use crate::token::{Token, Trivia, TriviaKind};
+use std::cell::RefCell;
 use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -10,15 +15,35 @@ pub enum Lexeme<'a> {
     Float(f64),
     Identifier(&'a str),
     QuotedString(&'a str),
+    /// A string with expressions embedded in braces, as in `"hello {name}"`, where `{{` and `}}`
+    /// stand for literal braces. Includes the quotes, and is split up by the parser.
+    InterpString(&'a str),
+    /// A character literal such as `'a'` or `'\n'`, with its escape already decoded.
+    Char(char),
     Operator(&'a str),
+    /// The `=` of a definition. Unlike `==` or `=>`, this is not an operator.
+    Equals,
+    /// The `::` between the segments of a qualified name, as in `List::map`.
+    PathSep,
+    /// A placeholder for an expression not yet written, `?name` or just `?`. Includes the `?`.
+    Hole(&'a str),
+    /// A name in backticks, used as an infix operator as in ``x `div` y``. Includes the
+    /// backticks.
+    Backtick(&'a str),
     Semicolon,
     LParen,
     RParen,
     LSquare,
+    /// The `#[` which opens an attribute, as in `#[inline]`. It is closed by an ordinary `]`.
+    HashSquare,
     RSquare,
     LCurly,
     RCurly,
     Comma,
+    Backslash,
+    /// The end of the input. Once the lexer reaches it, `peek` keeps returning this token, whose
+    /// location is just past the last character.
+    Eof,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -35,6 +60,21 @@ pub struct Nesting<'a> {
     next: Option<Rc<Nesting<'a>>>,
 }
 
+impl<'a> Drop for Nesting<'a> {
+    /// Unlink the brackets beneath this one iteratively, since dropping a long chain of them
+    /// recursively, as when a file leaves thousands of brackets open, could overflow the stack.
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(nesting) = next {
+            next = match Rc::try_unwrap(nesting) {
+                Ok(mut nesting) => nesting.next.take(),
+                // Another lexer still holds the rest of the chain.
+                Err(_) => None,
+            };
+        }
+    }
+}
+
 fn push_nested_bracket<'a>(
     location: Location<'a>,
     bt: BracketType,
@@ -49,28 +89,27 @@ fn pop_nested_bracket<'a>(
     bt: BracketType,
 ) -> ParseResult<'a, Option<Rc<Nesting<'a>>>> {
     if let Some(nesting) = nesting {
-        match *nesting {
-            Nesting {
-                location: top_location,
-                bt: top_bt,
-                ref next,
-            } => {
-                if bt == top_bt {
-                    // Peel off this head node, and return whatever's beneath.
-                    Ok(next.clone())
-                } else {
-                    Err(ParseError::error(
-                        location,
-                        format!(
-                            "encountered a {:?} but expected to close a {:?} from {}",
-                            bt, top_bt, top_location
-                        ),
-                    ))
-                }
-            }
+        let Nesting {
+            location: top_location,
+            bt: top_bt,
+            ref next,
+        } = *nesting;
+        if bt == top_bt {
+            // Peel off this head node, and return whatever's beneath.
+            Ok(next.clone())
+        } else {
+            Err(ParseError::error(
+                ErrorCode::UnbalancedBracket,
+                location,
+                format!(
+                    "encountered a {:?} but expected to close a {:?} from {}",
+                    bt, top_bt, top_location
+                ),
+            ))
         }
     } else {
         Err(ParseError::error(
+            ErrorCode::UnbalancedBracket,
             location,
             format!(
                 "encountered a {:?} but we're not inside of any nested syntax",
@@ -84,20 +123,200 @@ fn pop_nested_bracket<'a>(
 pub enum LexState<'a> {
     Started,
     Read(Token<'a>),
-    EOF,
+    EOF(Token<'a>),
 }
 
+/// A saved lexer position, along with the parser's state on the lexer. See `Lexer::checkpoint`.
+#[derive(Debug)]
+pub struct LexerCheckpoint<'a> {
+    contents: &'a str,
+    location: Location<'a>,
+    nesting: Option<Rc<Nesting<'a>>>,
+    state: LexState<'a>,
+    previous_token_end: Location<'a>,
+    in_match_guard: bool,
+    in_ternary: bool,
+    /// How much trivia had been read, and how much of it given to tokens. See `Lexer::trivia`.
+    trivia: (usize, usize),
+    fixities: Rc<FixityTable<'a>>,
+    depth: usize,
+    /// How many layout columns there were. A let's column is only pushed and popped around
+    /// parsing it, so the columns themselves are unchanged when the checkpoint is restored.
+    layout: usize,
+}
+
+/// The column at which the bindings of a let line up, and the brackets the let is within. See
+/// `Lexer::with_layout`.
 #[derive(Debug, Clone)]
+struct LayoutColumn<'a> {
+    col: i32,
+    nesting: Option<Rc<Nesting<'a>>>,
+}
+
+#[derive(Debug)]
 #[allow(dead_code)]
 pub struct Lexer<'a> {
     contents: &'a str,
     pub location: Location<'a>,
     nesting: Option<Rc<Nesting<'a>>>,
     state: LexState<'a>,
+    /// The location of the last character of the previously read token.
+    previous_token_end: Location<'a>,
+    /// Set by the parser while it reads a match arm guard, which runs up to the arm's `=>`, so
+    /// that a `predicate =>` inside the guard is not mistaken for the start of the next arm.
+    pub(crate) in_match_guard: bool,
+    /// Set by the parser while it reads the middle of a conditional `c ? a : b`, so that the `:`
+    /// ending it is not taken for a cons.
+    pub(crate) in_ternary: bool,
+    /// The bracket depth of the last `?` the parser scanned from without finding a `:` to make it
+    /// a conditional, and where that scan stopped. See `parser::ternary_operator`.
+    pub(crate) no_ternary_before: Option<(usize, Location<'a>)>,
+    /// When set, every identifier lexed is interned here. Clones of the lexer share it.
+    interner: Option<Rc<RefCell<Interner<'a>>>>,
+    /// When lexing losslessly, the whitespace and comments skipped so far. Those from
+    /// `trivia_start` on have not been given to a token yet. Nothing is removed while lexing, so
+    /// that a checkpoint only needs to remember the lengths.
+    trivia: Option<Vec<Trivia<'a>>>,
+    trivia_start: usize,
+    /// The fixities the parser uses for binary operators. See `fixity::scan_fixities`.
+    pub(crate) fixities: Rc<FixityTable<'a>>,
+    /// How deeply the parser may nest expressions, patterns and types before giving up with an
+    /// error, rather than overflowing the stack. Defaults to DEFAULT_MAX_DEPTH; see
+    /// `set_max_depth`.
+    max_depth: usize,
+    /// How deeply the parser is nested right now. See `nested`.
+    depth: usize,
+    /// The layout columns of the lets being parsed, innermost last. See `with_layout`.
+    layout: Vec<LayoutColumn<'a>>,
+}
+
+impl<'a> Clone for Lexer<'a> {
+    fn clone(&self) -> Self {
+        #[cfg(test)]
+        CLONES.with(|clones| clones.set(clones.get() + 1));
+        Lexer {
+            contents: self.contents,
+            location: self.location,
+            nesting: self.nesting.clone(),
+            state: self.state.clone(),
+            previous_token_end: self.previous_token_end,
+            in_match_guard: self.in_match_guard,
+            in_ternary: self.in_ternary,
+            no_ternary_before: self.no_ternary_before,
+            interner: self.interner.clone(),
+            trivia: self.trivia.clone(),
+            trivia_start: self.trivia_start,
+            fixities: self.fixities.clone(),
+            max_depth: self.max_depth,
+            depth: self.depth,
+            layout: self.layout.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// How many lexers have been cloned on this thread, so that tests can check the parser
+    /// backtracks with checkpoints instead.
+    static CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// How many lexers have been cloned on this thread so far.
+#[cfg(test)]
+pub(crate) fn clone_count() -> usize {
+    CLONES.with(|clones| clones.get())
+}
+
+/// The default for `Lexer::max_depth`, well beyond the nesting of any reasonable program.
+pub const DEFAULT_MAX_DEPTH: usize = 200;
+
+/// The rest of `input` after any leading whitespace and `#` comments.
+fn skip_whitespace_and_comments(mut input: &str) -> &str {
+    loop {
+        input = input.trim_start();
+        if !input.starts_with('#') || input.starts_with("#[") {
+            return input;
+        }
+        input = input.find('\n').map_or("", |newline| &input[newline..]);
+    }
 }
 
-fn is_operator_char(ch: char) -> bool {
-    return ch == '.'
+/// Reject a decimal literal such as `0123` (or `-0123`, or the whole part of `0123.5`) at
+/// `location`, since in C a leading zero would make it octal. A lone `0` is fine.
+fn check_leading_zero<'a>(digits: &str, location: Location<'a>) -> ParseResult<'a, ()> {
+    let unsigned = digits.trim_start_matches('-');
+    if unsigned.len() > 1 && unsigned.starts_with('0') {
+        return Err(ParseError::error(
+            ErrorCode::UnexpectedToken,
+            location,
+            format!(
+                "`{}` has a leading zero, which is not allowed in a decimal literal",
+                digits
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Reject a number which runs straight into a name, as `0x10` or `1.5e3` do, at `location`.
+/// `rest` is the input just after the number.
+fn check_number_end<'a>(number: &str, rest: &str, location: Location<'a>) -> ParseResult<'a, ()> {
+    let name_len = rest
+        .find(|ch: char| !(ch == '_' || ch.is_alphanumeric()))
+        .unwrap_or(rest.len());
+    if name_len > 0 {
+        return Err(ParseError::error(
+            ErrorCode::UnexpectedToken,
+            location,
+            format!(
+                "`{}{}` is not a number; put a space between a number and a name",
+                number,
+                &rest[..name_len]
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Decode the character literal at the start of `rest`, just after its opening `'`. Returns the
+/// character and the length of the rest of the literal, including the closing `'`.
+fn lex_char_literal(rest: &str) -> Option<(char, usize)> {
+    let mut chars = rest.char_indices();
+    let value = match chars.next()? {
+        (_, '\\') => match chars.next()?.1 {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            '"' => '"',
+            'u' => {
+                // A code point in hex, as in `'\u{1f600}'`.
+                let (brace, '{') = chars.next()? else {
+                    return None;
+                };
+                let (hex, _) = rest[brace + 1..].split_once('}')?;
+                if !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+                    return None;
+                }
+                let value = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+                chars.nth(hex.len());
+                value
+            }
+            _ => return None,
+        },
+        (_, '\'' | '\n') => return None,
+        (_, ch) => ch,
+    };
+    match chars.next()? {
+        (index, '\'') => Some((value, index + 1)),
+        _ => None,
+    }
+}
+
+pub(crate) fn is_operator_char(ch: char) -> bool {
+    ch == '.'
         || ch == '='
         || ch == '>'
         || ch == '<'
@@ -113,42 +332,59 @@ fn is_operator_char(ch: char) -> bool {
         || ch == '*'
         || ch == '/'
         || ch == '?'
-        || ch == '~';
+        || ch == '|'
+        || ch == '~'
 }
 impl<'a> Lexer<'a> {
     pub fn skip_semicolon(&mut self) -> ParseResult<'a, ()> {
-        while let Some(Token {
-            lexeme: Lexeme::Semicolon,
-            ..
-        }) = self.peek()
-        {
+        while self.peek_matches(Lexeme::Semicolon) {
             self.advance_mut()?;
         }
         Ok(())
     }
 
-    pub fn peek(&self) -> Option<Token<'a>> {
-        match self.state {
-            LexState::Started => None,
-            LexState::Read(ref token) => {
-                println!("{}: lexing  {:?}", token.location, token);
-                Some(token.clone())
+    /// Skip tokens up to and including the next semicolon, or to EOF. This is how the parser
+    /// resumes after an error; a semicolon is usually the newline before the next declaration.
+    pub fn recover_to_semicolon(&mut self) -> ParseResult<'a, ()> {
+        while !self.at_eof() {
+            let token = self.peek();
+            self.advance_mut()?;
+            if token.lexeme == Lexeme::Semicolon {
+                break;
             }
-            LexState::EOF => None,
         }
+        Ok(())
+    }
+
+    /// The current token, which is an `Eof` token once the lexer has reached the end of the
+    /// input. Before the first `advance` there is no current token yet, so it is an `Eof` token
+    /// at the start of the input, which nothing can be parsed from.
+    pub fn peek(&self) -> Token<'a> {
+        match self.state {
+            LexState::Started => Token::new(self.next_char_location(), Lexeme::Eof),
+            LexState::Read(ref token) | LexState::EOF(ref token) => token.clone(),
+        }
+    }
+
+    /// Whether the lexer has reached the end of the input.
+    pub fn at_eof(&self) -> bool {
+        matches!(self.state, LexState::EOF(_))
     }
 
     pub fn peek_matches(&self, expect_lexeme: Lexeme<'a>) -> bool {
         match self.state {
-            LexState::Started => false,
-            LexState::Read(ref token) => token.lexeme == expect_lexeme,
-            LexState::EOF => false,
+            LexState::Started => expect_lexeme == Lexeme::Eof,
+            LexState::Read(ref token) | LexState::EOF(ref token) => token.lexeme == expect_lexeme,
         }
     }
 
     pub fn chomp(&mut self, expect_lexeme: Lexeme<'a>) -> ParseResult<'a, ()> {
         match self.state {
-            LexState::Started => Err(ParseError::error(self.location, "lexer was not started!")),
+            LexState::Started => Err(ParseError::error(
+                ErrorCode::Internal,
+                self.location,
+                "lexer was not started!",
+            )),
             LexState::Read(ref token) => {
                 if token.lexeme == expect_lexeme {
                     self.advance()
@@ -159,8 +395,9 @@ impl<'a> Lexer<'a> {
                     ))
                 }
             }
-            LexState::EOF => Err(ParseError::error(
-                self.location,
+            LexState::EOF(ref token) => Err(ParseError::error(
+                ErrorCode::UnexpectedEof,
+                token.location,
                 format!("hit EOF but expected {:?}", expect_lexeme),
             )),
         }
@@ -172,12 +409,41 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn advance_mut(&mut self) -> ParseResult<'a, Location<'a>> {
-        let mut start_location = self.location.clone();
+        let before = self.contents;
+        let carried = self.trivia.as_ref().map_or(0, Vec::len);
+        let location = self.lex_next()?;
+        if let (Some(trivia), LexState::Read(token)) = (&mut self.trivia, &mut self.state) {
+            // A semicolon is read along with the indentation after its newline, which leads the
+            // token after it instead.
+            let position = (token.location.line, token.location.col);
+            let pending = self.trivia_start;
+            let after = trivia[pending..]
+                .iter()
+                .position(|trivia| (trivia.location.line, trivia.location.col) > position)
+                .map_or(trivia.len(), |index| pending + index);
+            // Everything read besides the trivia is the token's own text.
+            let length = |trivia: &[Trivia]| trivia.iter().map(|trivia| trivia.text.len()).sum();
+            let read = &before[..before.len() - self.contents.len()];
+            let start: usize = length(&trivia[carried.min(after)..after]);
+            let end = read.len() - length(&trivia[after.max(carried)..]);
+            token.text = &read[start..end];
+            token.leading_trivia = trivia[pending..after].to_vec();
+            self.trivia_start = after;
+        }
+        Ok(location)
+    }
+
+    fn lex_next(&mut self) -> ParseResult<'a, Location<'a>> {
+        let mut start_location = self.location;
+        // Until the next token is lexed, the location is still at the end of the current one.
+        if let LexState::Read(_) = self.state {
+            self.previous_token_end = self.location;
+        }
 
-        if self.state == LexState::EOF {
+        if self.at_eof() {
             return Ok(start_location);
-        } else if self.contents.len() == 0 {
-            self.state = LexState::EOF;
+        } else if self.contents.is_empty() {
+            self.reach_eof(self.next_char_location());
             return Ok(start_location);
         }
 
@@ -186,80 +452,177 @@ impl<'a> Lexer<'a> {
             Start,
             Identifier,
             Digits,
+            Fraction,
             Operator,
             Minus,
             QuotedString,
+            Backtick,
         }
         let mut ls = LS::Start;
         let mut count = 0;
         let mut lexeme_start = self.contents;
         let mut lexeme_start_index = 0;
         let mut ch_iter = self.contents.chars();
+        // The offset and location of the whitespace skipped since the last token or comment.
+        let mut whitespace: Option<(usize, Location<'a>)> = None;
+        // Within a string, the `{` of the expression being embedded and the depth of the braces
+        // opened since, and whether there has been any such expression.
+        let mut embed: Option<(Location<'a>, usize)> = None;
+        let mut interpolated = false;
         loop {
             let ch: char = ch_iter.next().unwrap_or('\0');
 
             match ls {
                 LS::Start => {
-                    if ch == '\n' && self.nesting.is_some() {
-                        start_location = self.location.clone();
-                        let mut count = 1;
-                        loop {
-                            // Gobble up all whitespace.
-                            match ch_iter.next() {
-                                Some(ch) => {
-                                    if ch.is_whitespace() {
-                                        count += 1;
-                                    } else {
-                                        break;
-                                    }
-
-                                    // This is a lexing discontinuity but it achieves the whitespace
-                                    // flexibility we want. If a newline occurs outside of a nested structure,
-                                    // then it lexes as a semicolon token.
+                    if !ch.is_whitespace() {
+                        self.record_whitespace(whitespace.take(), count);
+                    }
+                    if ch == '\n' && self.newlines_separate(count) {
+                        // This is a lexing discontinuity but it achieves the whitespace
+                        // flexibility we want. If a newline occurs outside of a nested structure,
+                        // then it lexes as a semicolon token, unless the next line is indented, in
+                        // which case it continues the current line. Inside of curly braces every
+                        // newline is a separator, and inside of parens and squares newlines are
+                        // just whitespace. Comments do not count as the next line.
+                        let rest = &self.contents[count + ch.len_utf8()..];
+                        let next_token = skip_whitespace_and_comments(rest);
+                        let gap = &rest[..rest.len() - next_token.len()];
+                        let indentation = gap.rsplit('\n').next().unwrap_or("");
+                        let continues = self.nesting.is_none()
+                            && !indentation.is_empty()
+                            && !self.at_layout_column(indentation);
+                        if !next_token.is_empty() && !continues {
+                            self.record_whitespace(whitespace.take(), count);
+                            let mut newline_location = self.location;
+                            newline_location.col += 1;
+                            self.update_loc(ch);
+                            let mut gap_offset = count + ch.len_utf8();
+                            for line in gap.split_inclusive('\n') {
+                                let (space, comment) =
+                                    line.split_at(line.find('#').unwrap_or(line.len()));
+                                let space_location = self.next_char_location();
+                                for ch in space.chars() {
                                     self.update_loc(ch);
                                 }
-                                None => break,
+                                self.record_whitespace(
+                                    Some((gap_offset, space_location)),
+                                    gap_offset + space.len(),
+                                );
+                                gap_offset += space.len();
+                                let comment = comment.trim_end_matches('\n');
+                                if !comment.is_empty() {
+                                    let comment_location = self.next_char_location();
+                                    for ch in comment.chars() {
+                                        self.update_loc(ch);
+                                    }
+                                    self.record_trivia(
+                                        TriviaKind::Comment,
+                                        comment,
+                                        comment_location,
+                                    );
+                                    gap_offset += comment.len();
+                                }
+                                if line.len() > space.len() + comment.len() {
+                                    let newline_location = self.next_char_location();
+                                    self.update_loc('\n');
+                                    self.record_trivia(
+                                        TriviaKind::Whitespace,
+                                        &line[line.len() - 1..],
+                                        newline_location,
+                                    );
+                                    gap_offset += 1;
+                                }
                             }
+                            self.contents = next_token;
+                            self.state =
+                                LexState::Read(Token::new(newline_location, Lexeme::Semicolon));
+                            return Ok(newline_location);
                         }
-
-                        self.contents = &self.contents[count..];
-                        self.state = LexState::Read(Token {
-                            location: start_location,
-                            lexeme: Lexeme::Semicolon,
-                        });
-                        return Ok(start_location);
+                    }
+                    if ch.is_whitespace() && whitespace.is_none() {
+                        whitespace = Some((count, self.next_char_location()));
                     }
                     self.update_loc(ch);
                     let location = self.location;
                     if ch == '\0' {
-                        self.state = LexState::EOF;
+                        self.reach_eof(location);
                         return Ok(start_location);
                     } else if ch.is_whitespace() {
-                    } else if ch.is_digit(10) {
+                    } else if ch.is_ascii_digit() {
                         ls = LS::Digits;
                         lexeme_start_index = count;
                         lexeme_start = &self.contents[count..];
-                        start_location = self.location.clone();
+                        start_location = self.location;
                     } else if ch == '_' || ch.is_alphabetic() {
                         ls = LS::Identifier;
                         lexeme_start_index = count;
                         lexeme_start = &self.contents[count..];
-                        start_location = self.location.clone();
+                        start_location = self.location;
+                    } else if ch == '?' && !ch_iter.clone().next().is_some_and(is_operator_char) {
+                        let rest = &self.contents[count + 1..];
+                        let len = if rest.starts_with(|ch: char| ch.is_alphabetic() || ch == '_') {
+                            rest.find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                                .unwrap_or(rest.len())
+                        } else {
+                            0
+                        };
+                        for ch in rest[..len].chars() {
+                            ch_iter.next();
+                            self.update_loc(ch);
+                        }
+                        let text = &self.contents[count..count + 1 + len];
+                        self.contents = &rest[len..];
+                        self.state = LexState::Read(Token::new(location, Lexeme::Hole(text)));
+                        return Ok(location);
                     } else if ch == '-' {
                         ls = LS::Minus;
                         lexeme_start_index = count;
                         lexeme_start = &self.contents[count..];
-                        start_location = self.location.clone();
+                        start_location = self.location;
                     } else if is_operator_char(ch) {
                         ls = LS::Operator;
                         lexeme_start_index = count;
                         lexeme_start = &self.contents[count..];
-                        start_location = self.location.clone();
+                        start_location = self.location;
                     } else if ch == '"' {
                         ls = LS::QuotedString;
                         lexeme_start_index = count;
                         lexeme_start = &self.contents[count..];
-                        start_location = self.location.clone();
+                        start_location = self.location;
+                    } else if ch == '#' && ch_iter.clone().next() == Some('[') {
+                        ch_iter.next();
+                        self.update_loc('[');
+                        return self._advance('[', count + 1, location, Lexeme::HashSquare);
+                    } else if ch == '#' {
+                        // A comment, which runs to the end of the line.
+                        let text = self.contents[count..].split('\n').next().unwrap_or("");
+                        for ch in text[1..].chars() {
+                            ch_iter.next();
+                            self.update_loc(ch);
+                        }
+                        self.record_trivia(TriviaKind::Comment, text, location);
+                        count += text.len() - 1;
+                    } else if ch == '\'' {
+                        let rest = &self.contents[count + 1..];
+                        let Some((value, len)) = lex_char_literal(rest) else {
+                            self.contents = rest;
+                            return Err(ParseError::error(
+                                ErrorCode::UnexpectedToken,
+                                location,
+                                "expected a single character or escape, then a closing '",
+                            ));
+                        };
+                        for ch in rest[..len].chars() {
+                            self.update_loc(ch);
+                        }
+                        self.contents = &rest[len..];
+                        self.state = LexState::Read(Token::new(location, Lexeme::Char(value)));
+                        return Ok(location);
+                    } else if ch == '`' {
+                        ls = LS::Backtick;
+                        lexeme_start_index = count;
+                        lexeme_start = &self.contents[count..];
+                        start_location = self.location;
                     } else if ch == '(' {
                         return self._advance(ch, count, location, Lexeme::LParen);
                     } else if ch == ')' {
@@ -276,11 +639,10 @@ impl<'a> Lexer<'a> {
                         return self._advance(ch, count, location, Lexeme::Semicolon);
                     } else if ch == ',' {
                         return self._advance(ch, count, location, Lexeme::Comma);
+                    } else if ch == '\\' {
+                        return self._advance(ch, count, location, Lexeme::Backslash);
                     } else {
-                        assert!(
-                            false,
-                            "could not figure out what do do with character ({ch})"
-                        );
+                        panic!("could not figure out what do do with character ({ch})");
                     }
 
                     count += ch.len_utf8();
@@ -290,11 +652,13 @@ impl<'a> Lexer<'a> {
                         self.update_loc(ch);
                         count += ch.len_utf8();
                     } else {
+                        let name = &lexeme_start[..count - lexeme_start_index];
+                        if let Some(interner) = &self.interner {
+                            interner.borrow_mut().intern(name);
+                        }
                         self.contents = &self.contents[count..];
-                        self.state = LexState::Read(Token {
-                            location: start_location,
-                            lexeme: Lexeme::Identifier(&lexeme_start[..count - lexeme_start_index]),
-                        });
+                        self.state =
+                            LexState::Read(Token::new(start_location, Lexeme::Identifier(name)));
                         return Ok(start_location);
                     }
                 }
@@ -305,15 +669,20 @@ impl<'a> Lexer<'a> {
                     } else {
                         // println!("{}: info: found a {:?}", &start_location, &lexeme_start[..count - lexeme_start_index]);
                         self.contents = &self.contents[count..];
-                        self.state = LexState::Read(Token {
-                            location: start_location,
-                            lexeme: Lexeme::Operator(&lexeme_start[..count - lexeme_start_index]),
-                        });
+                        let op = &lexeme_start[..count - lexeme_start_index];
+                        self.state = LexState::Read(Token::new(
+                            start_location,
+                            match op {
+                                "=" => Lexeme::Equals,
+                                "::" => Lexeme::PathSep,
+                                _ => Lexeme::Operator(op),
+                            },
+                        ));
                         return Ok(start_location);
                     }
                 }
                 LS::Minus => {
-                    if ch.is_digit(10) {
+                    if ch.is_ascii_digit() && !self.ends_operand_before(start_location) {
                         self.update_loc(ch);
                         count += ch.len_utf8();
                         ls = LS::Digits;
@@ -323,44 +692,156 @@ impl<'a> Lexer<'a> {
                         ls = LS::Operator;
                     } else {
                         self.contents = &self.contents[count..];
-                        self.state = LexState::Read(Token {
-                            location: start_location,
-                            lexeme: Lexeme::Operator(&lexeme_start[..count - lexeme_start_index]),
-                        });
+                        self.state = LexState::Read(Token::new(
+                            start_location,
+                            Lexeme::Operator(&lexeme_start[..count - lexeme_start_index]),
+                        ));
                         return Ok(start_location);
                     }
                 }
                 LS::Digits => {
-                    if ch.is_digit(10) {
+                    if ch.is_ascii_digit() {
+                        self.update_loc(ch);
+                        count += ch.len_utf8();
+                        continue;
+                    }
+                    let digits = &lexeme_start[..count - lexeme_start_index];
+                    if let Err(err) = check_leading_zero(digits, start_location).and_then(|()| {
+                        check_number_end(digits, &self.contents[count..], start_location)
+                    }) {
+                        self.contents = &self.contents[count..];
+                        return Err(err);
+                    }
+                    if ch == '.' && ch_iter.clone().next().is_some_and(|ch| ch.is_ascii_digit()) {
+                        // Only a `.` followed by a digit continues a number, leaving `1..5` alone.
                         self.update_loc(ch);
                         count += ch.len_utf8();
+                        ls = LS::Fraction;
                     } else {
                         self.contents = &self.contents[count..];
-                        self.state = LexState::Read(Token {
-                            location: start_location,
-                            lexeme: Lexeme::Signed(
-                                lexeme_start[..count - lexeme_start_index]
-                                    .parse::<i64>()
-                                    .unwrap(),
-                            ),
-                        });
+                        let Ok(value) = digits.parse::<i64>() else {
+                            return Err(ParseError::error(
+                                ErrorCode::UnexpectedToken,
+                                start_location,
+                                format!("`{}` does not fit in a 64-bit integer", digits),
+                            ));
+                        };
+                        self.state =
+                            LexState::Read(Token::new(start_location, Lexeme::Signed(value)));
                         return Ok(start_location);
                     }
                 }
-                LS::QuotedString => {
-                    count += ch.len_utf8();
-                    if ch != '"' {
+                LS::Fraction => {
+                    if ch.is_ascii_digit() {
                         self.update_loc(ch);
+                        count += ch.len_utf8();
                     } else {
+                        let text = &lexeme_start[..count - lexeme_start_index];
+                        let end = check_number_end(text, &self.contents[count..], start_location);
+                        self.contents = &self.contents[count..];
+                        end?;
+                        // Every run of digits parses, but one too large for an f64 becomes
+                        // infinity rather than failing.
+                        let value = text.parse::<f64>().unwrap();
+                        if value.is_infinite() {
+                            return Err(ParseError::error(
+                                ErrorCode::UnexpectedToken,
+                                start_location,
+                                format!("`{}` is too large to represent as a float", text),
+                            ));
+                        }
+                        self.state =
+                            LexState::Read(Token::new(start_location, Lexeme::Float(value)));
+                        return Ok(start_location);
+                    }
+                }
+                LS::Backtick => {
+                    let name = &lexeme_start[1..count - lexeme_start_index];
+                    let continues_name = ch == '_'
+                        || ch.is_alphabetic()
+                        || (ch.is_alphanumeric() && !name.is_empty());
+                    if continues_name {
+                        self.update_loc(ch);
+                        count += ch.len_utf8();
+                    } else if ch == '`' && !name.is_empty() {
+                        self.update_loc(ch);
+                        count += ch.len_utf8();
                         self.contents = &self.contents[count..];
-                        self.state = LexState::Read(Token {
-                            location: start_location,
-                            lexeme: Lexeme::QuotedString(
-                                &lexeme_start[..count - lexeme_start_index + 1],
-                            ),
-                        });
-                        println!("lexed {}", &lexeme_start[..count - lexeme_start_index]);
+                        self.state = LexState::Read(Token::new(
+                            start_location,
+                            Lexeme::Backtick(&lexeme_start[..count - lexeme_start_index]),
+                        ));
                         return Ok(start_location);
+                    } else {
+                        self.contents = &self.contents[count..];
+                        return Err(ParseError::error(
+                            ErrorCode::UnexpectedToken,
+                            start_location,
+                            "expected a name and then a closing backtick after this `",
+                        ));
+                    }
+                }
+                LS::QuotedString => {
+                    if ch == '\0' {
+                        self.contents = "";
+                        self.reach_eof(self.next_char_location());
+                        return Err(ParseError::error(
+                            ErrorCode::UnterminatedString,
+                            start_location,
+                            "unterminated string literal",
+                        ));
+                    }
+                    count += ch.len_utf8();
+                    self.update_loc(ch);
+                    let doubled = ch_iter.clone().next() == Some(ch);
+                    match (ch, embed) {
+                        ('"', Some((open_location, _))) => {
+                            // Embedded expressions cannot contain strings of their own.
+                            self.contents = &self.contents[count..];
+                            return Err(ParseError::error(
+                                ErrorCode::UnbalancedBracket,
+                                open_location,
+                                "this `{` in a string is never closed",
+                            ));
+                        }
+                        ('"', None) => {
+                            let text = &lexeme_start[..count - lexeme_start_index];
+                            self.contents = &self.contents[count..];
+                            self.state = LexState::Read(Token::new(
+                                start_location,
+                                if interpolated {
+                                    Lexeme::InterpString(text)
+                                } else {
+                                    Lexeme::QuotedString(text)
+                                },
+                            ));
+                            return Ok(start_location);
+                        }
+                        ('{' | '}', None) if doubled => {
+                            ch_iter.next();
+                            count += ch.len_utf8();
+                            self.update_loc(ch);
+                        }
+                        ('{', None) => {
+                            embed = Some((self.location, 0));
+                            interpolated = true;
+                        }
+                        ('}', None) => {
+                            self.contents = &self.contents[count..];
+                            return Err(ParseError::error(
+                                ErrorCode::UnbalancedBracket,
+                                self.location,
+                                "a literal `}` in a string must be doubled, as in `}}`",
+                            ));
+                        }
+                        ('{', Some((open_location, depth))) => {
+                            embed = Some((open_location, depth + 1));
+                        }
+                        ('}', Some((_, 0))) => embed = None,
+                        ('}', Some((open_location, depth))) => {
+                            embed = Some((open_location, depth - 1));
+                        }
+                        _ => (),
                     }
                 }
             }
@@ -374,6 +855,11 @@ impl<'a> Lexer<'a> {
         location: Location<'a>,
         lexeme: Lexeme<'a>,
     ) -> ParseResult<'a, Location<'a>> {
+        // Consume the character before checking the nesting so that a mismatched bracket does not
+        // wedge the lexer; callers can still recover past it.
+        count += ch.len_utf8();
+        self.contents = &self.contents[count..];
+
         // TODO: make this a stack.
         match lexeme {
             Lexeme::LParen => {
@@ -384,7 +870,7 @@ impl<'a> Lexer<'a> {
                 self.nesting =
                     pop_nested_bracket(self.nesting.clone(), location, BracketType::Paren)?;
             }
-            Lexeme::LSquare => {
+            Lexeme::LSquare | Lexeme::HashSquare => {
                 self.nesting = Some(Rc::new(Nesting {
                     location,
                     bt: BracketType::Square,
@@ -409,12 +895,7 @@ impl<'a> Lexer<'a> {
             _ => (),
         }
 
-        count += ch.len_utf8();
-        self.contents = &self.contents[count..];
-        self.state = LexState::Read(Token {
-            location: self.location,
-            lexeme: lexeme,
-        });
+        self.state = LexState::Read(Token::new(location, lexeme));
         Ok(location)
     }
 
@@ -423,26 +904,649 @@ impl<'a> Lexer<'a> {
         T: 'a + Into<&'a str>,
         U: 'a + Into<&'a str>,
     {
+        let location = Location {
+            filename: filename.into(),
+            line: 1,
+            col: 0,
+        };
         Lexer {
             contents: input.into(),
-            location: Location {
-                filename: filename.into(),
-                line: 1,
-                col: 0,
-            },
+            location,
             state: LexState::Started,
             nesting: None,
+            in_match_guard: false,
+            in_ternary: false,
+            no_ternary_before: None,
+            previous_token_end: location,
+            interner: None,
+            trivia: None,
+            trivia_start: 0,
+            fixities: Rc::new(FixityTable::builtin()),
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+            layout: Vec::new(),
+        }
+    }
+
+    /// A lexer which interns every identifier it reads into `interner`.
+    pub fn with_interner<T, U>(filename: T, input: U, interner: Rc<RefCell<Interner<'a>>>) -> Self
+    where
+        T: 'a + Into<&'a str>,
+        U: 'a + Into<&'a str>,
+    {
+        let mut lexer = Lexer::new(filename, input);
+        lexer.interner = Some(interner);
+        lexer
+    }
+
+    /// An identifier for `name`, which this lexer just read at `location`. It carries its symbol
+    /// when this lexer interns identifiers.
+    pub(crate) fn identifier(&self, name: &'a str, location: Location<'a>) -> Identifier<'a> {
+        let id = Identifier::new(name, location);
+        match &self.interner {
+            Some(interner) => id.with_symbol(interner.borrow_mut().intern(name)),
+            None => id,
+        }
+    }
+
+    /// A lexer which keeps each token's source `text`, and the whitespace and comments before it
+    /// as its `leading_trivia`.
+    pub fn lossless<T, U>(filename: T, input: U) -> Self
+    where
+        T: 'a + Into<&'a str>,
+        U: 'a + Into<&'a str>,
+    {
+        let mut lexer = Lexer::new(filename, input);
+        lexer.trivia = Some(Vec::new());
+        lexer
+    }
+
+    /// A lexer for `input`, which this lexer's input embeds just after `location`, as an
+    /// interpolated string embeds each of its expressions. It shares this lexer's fixities and
+    /// interner, so locations and operators mean the same inside as out. Its nesting continues
+    /// from this lexer's.
+    pub(crate) fn embedded(&self, location: Location<'a>, input: &'a str) -> Lexer<'a> {
+        Lexer {
+            contents: input,
+            location,
+            state: LexState::Started,
+            nesting: None,
+            in_match_guard: false,
+            in_ternary: false,
+            no_ternary_before: None,
+            previous_token_end: location,
+            interner: self.interner.clone(),
+            trivia: None,
+            trivia_start: 0,
+            fixities: self.fixities.clone(),
+            max_depth: self.max_depth,
+            depth: self.depth,
+            layout: Vec::new(),
+        }
+    }
+
+    /// Limit how deeply the parser may nest expressions, patterns and types in this lexer's input.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Run `parse` one level deeper in the parser's nesting, or fail at the next token if that
+    /// would be deeper than `max_depth`.
+    pub(crate) fn nested<T, F>(&mut self, parse: F) -> ParseResult<'a, T>
+    where
+        F: FnOnce(&mut Self) -> ParseResult<'a, T>,
+    {
+        self.descend()?;
+        let result = parse(self);
+        self.ascend(1);
+        result
+    }
+
+    /// Go one level deeper in the parser's nesting, or fail at the next token if that would be
+    /// deeper than `max_depth`. This is for a loop that builds a deeper tree on each pass, such
+    /// as a chain of left-associative operators; it gives the levels back with `ascend`.
+    pub(crate) fn descend(&mut self) -> ParseResult<'a, ()> {
+        if self.depth >= self.max_depth {
+            return Err(ParseError::error(
+                ErrorCode::NestingTooDeep,
+                self.peek().location,
+                format!(
+                    "expression nesting too deep; the limit is {} levels",
+                    self.max_depth
+                ),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Give back `levels` levels of nesting taken with `descend`.
+    pub(crate) fn ascend(&mut self, levels: usize) {
+        self.depth -= levels;
+    }
+
+    /// Capture the lexer's position, so that the parser can backtrack to it with `restore`.
+    pub fn checkpoint(&self) -> LexerCheckpoint<'a> {
+        LexerCheckpoint {
+            contents: self.contents,
+            location: self.location,
+            nesting: self.nesting.clone(),
+            state: self.state.clone(),
+            previous_token_end: self.previous_token_end,
+            in_match_guard: self.in_match_guard,
+            in_ternary: self.in_ternary,
+            trivia: (self.trivia.as_ref().map_or(0, Vec::len), self.trivia_start),
+            fixities: self.fixities.clone(),
+            depth: self.depth,
+            layout: self.layout.len(),
+        }
+    }
+
+    /// Whether any input has been consumed since `checkpoint` was taken.
+    pub fn has_advanced_since(&self, checkpoint: &LexerCheckpoint<'a>) -> bool {
+        self.contents.len() != checkpoint.contents.len() || self.state != checkpoint.state
+    }
+
+    /// Rewind the lexer to a checkpoint taken earlier.
+    pub fn restore(&mut self, checkpoint: LexerCheckpoint<'a>) {
+        self.contents = checkpoint.contents;
+        self.location = checkpoint.location;
+        self.nesting = checkpoint.nesting;
+        self.state = checkpoint.state;
+        self.previous_token_end = checkpoint.previous_token_end;
+        self.in_match_guard = checkpoint.in_match_guard;
+        self.in_ternary = checkpoint.in_ternary;
+        if let Some(trivia) = &mut self.trivia {
+            trivia.truncate(checkpoint.trivia.0);
+        }
+        self.trivia_start = checkpoint.trivia.1;
+        self.fixities = checkpoint.fixities;
+        self.depth = checkpoint.depth;
+        self.layout.truncate(checkpoint.layout);
+    }
+
+    /// Run `parse` over the bindings of a let, the first of which is the next token. While it
+    /// runs, a newline followed by a token in that binding's column separates bindings, just as a
+    /// semicolon would, as long as it is within the same brackets as the let.
+    pub(crate) fn with_layout<T, F>(&mut self, parse: F) -> ParseResult<'a, T>
+    where
+        F: FnOnce(&mut Self) -> ParseResult<'a, T>,
+    {
+        let col = self.peek().location.col;
+        self.layout.push(LayoutColumn {
+            col,
+            nesting: self.nesting.clone(),
+        });
+        let result = parse(self);
+        self.layout.pop();
+        result
+    }
+
+    /// Run `scan` to read ahead in the input, then rewind the lexer to where it was.
+    pub(crate) fn lookahead<T, F>(&mut self, scan: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let checkpoint = self.checkpoint();
+        let result = scan(self);
+        self.restore(checkpoint);
+        result
+    }
+
+    /// The location of the last character of the most recently consumed token, which is where an
+    /// expression ending with that token ends.
+    pub fn previous_token_end(&self) -> Location<'a> {
+        self.previous_token_end
+    }
+
+    /// Whether the token just read is an operand which ends right before `location`, as `n` does
+    /// before the `-` of `n-1`. A `-` there subtracts rather than signing a literal.
+    fn ends_operand_before(&self, location: Location<'a>) -> bool {
+        let LexState::Read(ref token) = self.state else {
+            return false;
+        };
+        let end = self.previous_token_end;
+        end.line == location.line
+            && end.col + 1 == location.col
+            && match token.lexeme {
+                Lexeme::Identifier(name) => !is_keyword(name),
+                Lexeme::Signed(_)
+                | Lexeme::Float(_)
+                | Lexeme::QuotedString(_)
+                | Lexeme::InterpString(_)
+                | Lexeme::Char(_)
+                | Lexeme::Hole(_)
+                | Lexeme::RParen
+                | Lexeme::RSquare
+                | Lexeme::RCurly => true,
+                _ => false,
+            }
+    }
+
+    /// Stop at the end of the input, leaving an Eof token at `location` for `peek`.
+    fn reach_eof(&mut self, location: Location<'a>) {
+        self.state = LexState::EOF(Token::new(location, Lexeme::Eof));
+    }
+
+    /// The location of the next character, as `update_loc` would leave it.
+    fn next_char_location(&self) -> Location<'a> {
+        let mut location = self.location;
+        location.col += 1;
+        location
+    }
+
+    /// When lexing losslessly, keep `text`, which begins at `location`, as trivia for the next
+    /// token.
+    fn record_trivia(&mut self, kind: TriviaKind, text: &'a str, location: Location<'a>) {
+        if let Some(trivia) = &mut self.trivia {
+            trivia.push(Trivia {
+                kind,
+                location,
+                text,
+            });
+        }
+    }
+
+    /// Keep the whitespace from `start` up to `end` within the current contents as trivia. It is
+    /// split before each newline, so that `tokenize_lossless` can tell where a line ends.
+    fn record_whitespace(&mut self, start: Option<(usize, Location<'a>)>, end: usize) {
+        let (Some((start, mut location)), Some(_)) = (start, &self.trivia) else {
+            return;
+        };
+        let text = &self.contents[start..end];
+        let mut piece_start = 0;
+        let mut piece_location = location;
+        for (index, ch) in text.char_indices() {
+            if ch == '\n' && index > piece_start {
+                self.record_trivia(
+                    TriviaKind::Whitespace,
+                    &text[piece_start..index],
+                    piece_location,
+                );
+                piece_start = index;
+                piece_location = location;
+            }
+            if ch == '\n' {
+                location.line += 1;
+                location.col = 1;
+            } else {
+                location.col += 1;
+            }
+        }
+        if piece_start < text.len() {
+            self.record_trivia(TriviaKind::Whitespace, &text[piece_start..], piece_location);
+        }
+    }
+
+    /// The trivia after the last token, once the lexer has reached EOF.
+    pub fn take_trivia(&mut self) -> Vec<Trivia<'a>> {
+        let Some(trivia) = &self.trivia else {
+            return Vec::new();
+        };
+        let pending = trivia[self.trivia_start..].to_vec();
+        self.trivia_start = trivia.len();
+        pending
+    }
+
+    /// How many brackets are open, counting the current token if it opens one. Editors use this
+    /// to auto-indent.
+    pub fn nesting_depth(&self) -> usize {
+        std::iter::successors(self.nesting.as_deref(), |nesting| nesting.next.as_deref()).count()
+    }
+
+    /// The innermost open bracket, if any.
+    pub fn current_bracket(&self) -> Option<BracketType> {
+        self.nesting.as_ref().map(|nesting| nesting.bt)
+    }
+
+    /// Lex the rest of the input, returning an error for each mismatched closing bracket and then
+    /// one for each bracket still open at EOF, outermost first. A mismatched bracket leaves the
+    /// nesting as it was, so lexing carries on past it. Any other error stops the check, and is
+    /// returned last.
+    pub fn check_brackets(&mut self) -> Vec<ParseError<'a>> {
+        let mut errors = Vec::new();
+        loop {
+            match self.advance_mut() {
+                Ok(_) if self.at_eof() => break,
+                Ok(_) => (),
+                Err(err) if err.code() == ErrorCode::UnbalancedBracket => errors.push(err),
+                Err(err) => {
+                    errors.push(err);
+                    return errors;
+                }
+            }
+        }
+        let mut unclosed: Vec<ParseError<'a>> =
+            std::iter::successors(self.nesting.as_deref(), |nesting| nesting.next.as_deref())
+                .map(|nesting| {
+                    ParseError::error(
+                        ErrorCode::UnbalancedBracket,
+                        nesting.location,
+                        format!("this {:?} is never closed", nesting.bt),
+                    )
+                })
+                .collect();
+        unclosed.reverse();
+        errors.extend(unclosed);
+        errors
+    }
+
+    /// Whether the newline at `offset` in the contents separates what comes before it from what
+    /// comes after it.
+    fn newlines_separate(&self, offset: usize) -> bool {
+        if self.newlines_are_separators() {
+            return true;
+        }
+        let rest = skip_whitespace_and_comments(&self.contents[offset + 1..]);
+        let gap = &self.contents[offset + 1..self.contents.len() - rest.len()];
+        self.at_layout_column(gap.rsplit('\n').next().unwrap_or(""))
+    }
+
+    /// Whether a line with this `indentation` starts at the column of the innermost let's
+    /// bindings, within the same brackets as that let.
+    fn at_layout_column(&self, indentation: &str) -> bool {
+        self.layout.last().is_some_and(|layout| {
+            layout.col == indentation.chars().count() as i32 + 1
+                && match (&layout.nesting, &self.nesting) {
+                    (None, None) => true,
+                    (Some(outer), Some(nesting)) => Rc::ptr_eq(outer, nesting),
+                    _ => false,
+                }
+        })
+    }
+
+    fn newlines_are_separators(&self) -> bool {
+        match self.nesting {
+            None => true,
+            Some(ref nesting) => nesting.bt == BracketType::Curly,
         }
     }
 
     #[inline]
     fn update_loc(&mut self, ch: char) {
-        if ch == '\n' {
-            self.location.line += 1;
-            self.location.col = 0;
-        } else {
-            // println!("found {}, bumping col", ch);
-            self.location.col += 1;
+        self.location.advance(ch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::HasLocation;
+
+    #[test]
+    fn recover_to_semicolon_skips_the_rest_of_the_line() {
+        let mut lexer = Lexer::new("test.mv", "a b c\nd e");
+        lexer.advance().unwrap();
+        lexer.advance().unwrap();
+        lexer.recover_to_semicolon().unwrap();
+        assert_eq!(lexer.peek().lexeme, Lexeme::Identifier("d"));
+    }
+
+    #[test]
+    fn recover_past_a_mismatched_bracket() {
+        let mut lexer = Lexer::new("test.mv", "a ) b\nc");
+        lexer.advance().unwrap();
+        assert!(lexer.advance().is_err());
+        lexer.recover_to_semicolon().unwrap();
+        assert_eq!(lexer.peek().lexeme, Lexeme::Identifier("c"));
+    }
+
+    fn drain(lexer: &mut Lexer<'static>) -> Vec<Token<'static>> {
+        let mut tokens = Vec::new();
+        while !lexer.at_eof() {
+            tokens.push(lexer.peek());
+            lexer.advance().unwrap();
         }
+        tokens
+    }
+
+    #[test]
+    fn restoring_a_checkpoint_replays_the_same_tokens() {
+        let mut lexer = Lexer::new("test.mv", "f (a, [b]) = {\n  x = 1\n}\ng = 2");
+        lexer.advance().unwrap();
+        lexer.advance().unwrap();
+        lexer.advance().unwrap();
+        // Checkpoint inside the parentheses, so that the nesting is restored too.
+        let checkpoint = lexer.checkpoint();
+        let tokens = drain(&mut lexer);
+        assert_eq!(tokens[0].lexeme, Lexeme::Identifier("a"));
+        lexer.restore(checkpoint);
+        assert_eq!(drain(&mut lexer), tokens);
+        // A lossless lexer replays the trivia too, from wherever the checkpoint was taken.
+        let input = "f x = # note\n  let a = 1\n      b = 2\n  in a\n";
+        let mut lexer = Lexer::lossless("test.mv", input);
+        lexer.advance().unwrap();
+        let count = drain(&mut lexer).len();
+        for skip in 1..=count {
+            let mut lexer = Lexer::lossless("test.mv", input);
+            for _ in 0..skip {
+                lexer.advance().unwrap();
+            }
+            let checkpoint = lexer.checkpoint();
+            let tokens = drain(&mut lexer);
+            lexer.restore(checkpoint);
+            assert_eq!(drain(&mut lexer), tokens);
+            let rest: Vec<_> = lexer
+                .take_trivia()
+                .iter()
+                .map(|trivia| trivia.text)
+                .collect();
+            assert_eq!(rest, ["\n"]);
+        }
+    }
+
+    #[test]
+    fn interning_identifiers() {
+        let interner = Rc::new(RefCell::new(Interner::new()));
+        let mut lexer = Lexer::with_interner("test.mv", "foo = bar foo", interner.clone());
+        lexer.advance().unwrap();
+        let names: Vec<&str> = drain(&mut lexer)
+            .iter()
+            .filter_map(|token| match token.lexeme {
+                Lexeme::Identifier(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        let interner = interner.borrow();
+        let symbols: Vec<_> = names
+            .iter()
+            .map(|name| interner.get(name).unwrap())
+            .collect();
+        assert_eq!(symbols[0], symbols[2]);
+        assert_ne!(symbols[0], symbols[1]);
+        assert_eq!(interner.resolve(symbols[2]), "foo");
+    }
+
+    #[test]
+    fn quoted_strings() {
+        let mut lexer = Lexer::new("test.mv", "\"ab\" \"c\"");
+        lexer.advance().unwrap();
+        assert_eq!(lexer.peek().lexeme, Lexeme::QuotedString("\"ab\""));
+        lexer.advance().unwrap();
+        assert_eq!(lexer.peek().lexeme, Lexeme::QuotedString("\"c\""));
+        assert_eq!(lexer.peek().location.col, 6);
+        assert_eq!(lexer.previous_token_end().col, 4);
+    }
+
+    #[test]
+    fn path_separators() {
+        let mut lexer = Lexer::new("test.mv", "List::map x:xs a ::: b");
+        lexer.advance().unwrap();
+        let lexemes: Vec<Lexeme> = drain(&mut lexer).into_iter().map(|t| t.lexeme).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                Lexeme::Identifier("List"),
+                Lexeme::PathSep,
+                Lexeme::Identifier("map"),
+                Lexeme::Identifier("x"),
+                Lexeme::Operator(":"),
+                Lexeme::Identifier("xs"),
+                Lexeme::Identifier("a"),
+                Lexeme::Operator(":::"),
+                Lexeme::Identifier("b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let mut lexer = Lexer::new("test.mv", "([{ }] x)");
+        assert_eq!(lexer.nesting_depth(), 0);
+        assert_eq!(lexer.current_bracket(), None);
+        for _ in 0..3 {
+            lexer.advance().unwrap();
+        }
+        assert_eq!(lexer.peek().lexeme, Lexeme::LCurly);
+        assert_eq!(lexer.nesting_depth(), 3);
+        assert_eq!(lexer.current_bracket(), Some(BracketType::Curly));
+        for _ in 0..3 {
+            lexer.advance().unwrap();
+        }
+        assert_eq!(lexer.peek().lexeme, Lexeme::Identifier("x"));
+        assert_eq!(lexer.nesting_depth(), 1);
+        assert_eq!(lexer.current_bracket(), Some(BracketType::Paren));
+    }
+
+    #[test]
+    fn check_brackets() {
+        let errors: Vec<String> = Lexer::new("test.mv", "f = ([a)] (b\ng = {[c]}")
+            .check_brackets()
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(
+            errors[0].starts_with("test.mv:1:8: error[E0003]:"),
+            "{}",
+            errors[0]
+        );
+        assert!(errors[1].starts_with("test.mv:1:5: error[E0003]: this Paren is never closed"));
+        assert!(errors[2].starts_with("test.mv:1:11: error[E0003]: this Paren is never closed"));
+        assert!(Lexer::new("test.mv", "f = ([a]) {b}\ng = (c)")
+            .check_brackets()
+            .is_empty());
+    }
+
+    #[test]
+    fn holes() {
+        let mut lexer = Lexer::new("test.mv", "f ?todo ? ?_x ?? a <?> b");
+        lexer.advance().unwrap();
+        let lexemes: Vec<Lexeme> = drain(&mut lexer).into_iter().map(|t| t.lexeme).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                Lexeme::Identifier("f"),
+                Lexeme::Hole("?todo"),
+                Lexeme::Hole("?"),
+                Lexeme::Hole("?_x"),
+                Lexeme::Operator("??"),
+                Lexeme::Identifier("a"),
+                Lexeme::Operator("<?>"),
+                Lexeme::Identifier("b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn backticked_names() {
+        let mut lexer = Lexer::new("test.mv", "x `div` y`mod2`z");
+        lexer.advance().unwrap();
+        let lexemes: Vec<Lexeme> = drain(&mut lexer).into_iter().map(|t| t.lexeme).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                Lexeme::Identifier("x"),
+                Lexeme::Backtick("`div`"),
+                Lexeme::Identifier("y"),
+                Lexeme::Backtick("`mod2`"),
+                Lexeme::Identifier("z"),
+            ]
+        );
+        for input in ["x `div y", "x `1` y", "x `` y", "x `a b` y"] {
+            let mut lexer = Lexer::new("test.mv", input);
+            lexer.advance().unwrap();
+            let err = lexer.advance().unwrap_err();
+            assert!(err.to_string().starts_with("test.mv:1:3:"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn char_literals() {
+        let mut lexer = Lexer::new("test.mv", r"'a' '\n' '\'' '\u{e9}' 'é' x");
+        lexer.advance().unwrap();
+        let tokens = drain(&mut lexer);
+        let lexemes: Vec<Lexeme> = tokens.iter().map(|t| t.lexeme.clone()).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                Lexeme::Char('a'),
+                Lexeme::Char('\n'),
+                Lexeme::Char('\''),
+                Lexeme::Char('é'),
+                Lexeme::Char('é'),
+                Lexeme::Identifier("x"),
+            ]
+        );
+        assert_eq!(tokens[5].location.col, 28);
+        for input in ["x 'ab'", "x ''", "x 'a", r"x '\q'", r"x '\u{110000}'"] {
+            let mut lexer = Lexer::new("test.mv", input);
+            lexer.advance().unwrap();
+            let err = lexer.advance().unwrap_err();
+            assert!(err.to_string().starts_with("test.mv:1:3:"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn unterminated_string_has_a_code() {
+        let mut lexer = Lexer::new("test.mv", "x \"abc");
+        lexer.advance().unwrap();
+        let err = lexer.advance().unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UnterminatedString);
+        assert!(
+            err.to_string().starts_with("test.mv:1:3: error[E0002]:"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn peek_at_eof() {
+        // Peeking before the first advance does not panic, and finds nothing to parse.
+        let lexer = Lexer::new("test.mv", "a b");
+        let eof = lexer.peek();
+        assert_eq!(eof.lexeme, Lexeme::Eof);
+        assert_eq!((eof.location.line, eof.location.col), (1, 1));
+        assert!(lexer.peek_matches(Lexeme::Eof));
+        assert!(!lexer.at_eof());
+
+        let mut lexer = Lexer::new("test.mv", "a b");
+        lexer.advance().unwrap();
+        assert!(!lexer.at_eof());
+        drain(&mut lexer);
+        let eof = lexer.peek();
+        assert_eq!(eof.lexeme, Lexeme::Eof);
+        assert_eq!((eof.location.line, eof.location.col), (1, 4));
+        // Advancing past the end stays there.
+        lexer.advance().unwrap();
+        assert_eq!(lexer.peek(), eof);
+        assert!(lexer.peek_matches(Lexeme::Eof));
+
+        let mut lexer = Lexer::new("test.mv", "a\n  b  \n");
+        lexer.advance().unwrap();
+        drain(&mut lexer);
+        let eof = lexer.peek();
+        assert_eq!((eof.location.line, eof.location.col), (3, 1));
+        let err = lexer.chomp(Lexeme::RParen).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UnexpectedEof);
+        assert_eq!(err.get_location(), &eof.location);
+    }
+
+    #[test]
+    fn recover_to_semicolon_stops_at_eof() {
+        let mut lexer = Lexer::new("test.mv", "a b c");
+        lexer.advance().unwrap();
+        lexer.recover_to_semicolon().unwrap();
+        assert!(lexer.at_eof());
     }
 }
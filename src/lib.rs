@@ -0,0 +1,110 @@
+pub mod error;
+pub mod fixity;
+pub mod fold;
+pub mod identifier;
+pub mod interner;
+pub mod lexer;
+pub mod location;
+pub mod module;
+pub mod parser;
+pub mod resolve;
+pub mod token;
+pub mod types;
+
+pub use crate::error::{Diagnostics, ErrorCode, ErrorLevel, ParseError, ParseResult};
+pub use crate::fixity::{is_reserved_operator, Associativity, Fixity, FixityDecl, FixityTable};
+pub use crate::interner::{Interner, Symbol};
+pub use crate::lexer::{BracketType, Lexeme, Lexer, LexerCheckpoint};
+pub use crate::module::{parse_module, Export, Import, Module, QualifiedName};
+pub use crate::parser::{
+    desugar_do, keywords, pretty_print, to_sexpr, walk_decl, walk_expr, walk_predicate, Attribute,
+    Clause, DataDecl, Decl, DoStatement, Expr, InterpPart, Item, Predicate, Visitor,
+};
+pub use crate::token::{Token, Trivia, TriviaKind};
+
+use crate::module::parse_module_with_diagnostics;
+
+/// Lex and parse `input` as a module. `filename` is only used for locations.
+pub fn parse_str<'a>(filename: &'a str, input: &'a str) -> ParseResult<'a, Module<'a>> {
+    let mut lexer = Lexer::new(filename, input);
+    lexer
+        .advance()
+        .and_then(|_| parse_module(&mut lexer))
+        .map_err(|err| err.with_source(input))
+}
+
+/// Like parse_str, but keeps going after an error so that every broken item is reported.
+pub fn parse_str_with_diagnostics<'a>(
+    filename: &'a str,
+    input: &'a str,
+) -> (Module<'a>, Diagnostics<'a>) {
+    let mut diagnostics = Diagnostics::new(input);
+    let mut lexer = Lexer::new(filename, input);
+    let module = match lexer.advance() {
+        Ok(()) => parse_module_with_diagnostics(&mut lexer, &mut diagnostics),
+        Err(err) => {
+            diagnostics.error(err);
+            Module::empty(filename)
+        }
+    };
+    (module, diagnostics)
+}
+
+/// Serialize `decls` to JSON for external tooling. Locations are included on every node, and a
+/// float literal too large to represent is written as `null`.
+#[cfg(feature = "serde")]
+pub fn decls_to_json(decls: &[Decl]) -> String {
+    serde_json::to_string(decls).expect("the AST only contains serializable data")
+}
+
+/// Lex all of `input` into a vector of tokens, stopping at EOF.
+pub fn tokenize<'a>(filename: &'a str, input: &'a str) -> ParseResult<'a, Vec<Token<'a>>> {
+    let mut lexer = Lexer::new(filename, input);
+    let mut tokens = Vec::new();
+    lexer.advance().map_err(|err| err.with_source(input))?;
+    while !lexer.at_eof() {
+        tokens.push(lexer.peek());
+        lexer.advance().map_err(|err| err.with_source(input))?;
+    }
+    Ok(tokens)
+}
+
+/// Lex all of `input` and report each mismatched bracket, then each bracket left unclosed at
+/// EOF. This is much cheaper than parsing, so it makes a quick first check. An empty result means
+/// the brackets balance.
+pub fn check_brackets<'a>(filename: &'a str, input: &'a str) -> Vec<ParseError<'a>> {
+    Lexer::new(filename, input)
+        .check_brackets()
+        .into_iter()
+        .map(|err| err.with_source(input))
+        .collect()
+}
+
+/// Like tokenize, but keeps each token's source `text`, and every comment and run of whitespace as
+/// trivia on the tokens around it, so that the tokens' text and trivia together reproduce `input`.
+/// Trivia on the same line as a token, before the newline, trails that token; the rest leads the
+/// next token.
+pub fn tokenize_lossless<'a>(filename: &'a str, input: &'a str) -> ParseResult<'a, Vec<Token<'a>>> {
+    let mut lexer = Lexer::lossless(filename, input);
+    let mut tokens: Vec<Token<'a>> = Vec::new();
+    lexer.advance().map_err(|err| err.with_source(input))?;
+    while !lexer.at_eof() {
+        let mut token = lexer.peek();
+        if let Some(previous) = tokens.last_mut() {
+            let same_line = token
+                .leading_trivia
+                .iter()
+                .position(|trivia| trivia.text.starts_with('\n'))
+                .unwrap_or(token.leading_trivia.len());
+            previous
+                .trailing_trivia
+                .extend(token.leading_trivia.drain(..same_line));
+        }
+        tokens.push(token);
+        lexer.advance().map_err(|err| err.with_source(input))?;
+    }
+    if let Some(last) = tokens.last_mut() {
+        last.trailing_trivia.extend(lexer.take_trivia());
+    }
+    Ok(tokens)
+}
@@ -2,8 +2,11 @@ use std::borrow::Borrow;
 use std::fmt;
 use std::fmt::Formatter;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// A position within a source file. Locations order by filename, then line, then column, so that
+/// sorting diagnostics puts them in source order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[allow(dead_code)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Location<'a> {
     pub filename: &'a str,
     pub line: i32,
@@ -16,6 +19,51 @@ impl<'a> std::fmt::Display for Location<'a> {
     }
 }
 
+impl<'a> Location<'a> {
+    /// The span from this location through `end`.
+    pub fn to(&self, end: &Location<'a>) -> Span<'a> {
+        Span {
+            start: *self,
+            end: *end,
+        }
+    }
+
+    /// Step over `ch`, which the lexer does for every character it reads. A location's column is
+    /// that of the last character read, so a newline leaves it at column 0 of the next line.
+    pub fn advance(&mut self, ch: char) {
+        if ch == '\n' {
+            self.line += 1;
+            self.col = 0;
+        } else {
+            self.col += 1;
+        }
+    }
+}
+
+/// A range of source text, from `start` through `end`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Span<'a> {
+    pub start: Location<'a>,
+    pub end: Location<'a>,
+}
+
+impl<'a> Span<'a> {
+    /// The smallest span covering both `self` and `other`.
+    pub fn merge(self, other: Span<'a>) -> Span<'a> {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+impl<'a> std::fmt::Display for Span<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}:{}", self.start, self.end.line, self.end.col)
+    }
+}
+
 pub trait HasLocation<'a> {
     fn get_location(&self) -> &Location<'a>;
 }
@@ -26,3 +74,51 @@ impl<'a, T: HasLocation<'a>> HasLocation<'a> for Box<T> {
         borrowed.get_location()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(filename: &'static str, line: i32, col: i32) -> Location<'static> {
+        Location {
+            filename,
+            line,
+            col,
+        }
+    }
+
+    #[test]
+    fn merging_spans() {
+        let a = location("a.mv", 1, 5).to(&location("a.mv", 1, 9));
+        let b = location("a.mv", 2, 1).to(&location("a.mv", 3, 4));
+        let merged = a.merge(b);
+        assert_eq!(merged, location("a.mv", 1, 5).to(&location("a.mv", 3, 4)));
+        assert_eq!(b.merge(a), merged);
+        assert_eq!(merged.to_string(), "a.mv:1:5-3:4");
+
+        // A span nested within another merges to the outer span.
+        let inner = location("a.mv", 1, 6).to(&location("a.mv", 1, 7));
+        assert_eq!(a.merge(inner), a);
+    }
+
+    #[test]
+    fn locations_order_by_filename_line_and_col() {
+        assert!(location("a.mv", 1, 9) < location("a.mv", 2, 1));
+        assert!(location("a.mv", 2, 1) < location("a.mv", 2, 3));
+        assert!(location("b.mv", 1, 1) > location("a.mv", 9, 9));
+        let mut locations = vec![
+            location("b.mv", 1, 1),
+            location("a.mv", 2, 1),
+            location("a.mv", 1, 5),
+        ];
+        locations.sort();
+        assert_eq!(
+            locations,
+            vec![
+                location("a.mv", 1, 5),
+                location("a.mv", 2, 1),
+                location("b.mv", 1, 1),
+            ]
+        );
+    }
+}
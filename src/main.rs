@@ -1,35 +1,99 @@
 use std::env;
 use std::fs;
+use std::io::{self, IsTerminal, Read};
 
-use crate::lexer::Lexer;
-
-mod error;
-mod identifier;
-mod lexer;
-mod location;
-mod parser;
-mod token;
-use crate::parser::{parse_decl, parse_many};
+use mueve::resolve::resolve_names;
+use mueve::{
+    check_brackets, parse_str_with_diagnostics, tokenize, Diagnostics, Module, ParseError,
+};
 
 fn main() {
     let exit_code = if run_real_compiler() { 0 } else { 1 };
     std::process::exit(exit_code);
 }
 
+const STDIN_FILENAME: &str = "<stdin>";
+
+#[derive(Debug, Default, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    dump_tokens: bool,
+    dump_ast: bool,
+    check_brackets: bool,
+    error_format: ErrorFormat,
+    path: Option<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    for arg in args {
+        match arg.as_str() {
+            "--dump-tokens" => options.dump_tokens = true,
+            "--dump-ast" => options.dump_ast = true,
+            "--check-brackets" => options.check_brackets = true,
+            "--error-format=human" => options.error_format = ErrorFormat::Human,
+            "--error-format=json" => options.error_format = ErrorFormat::Json,
+            // With no filename, or a filename of "-", the program is read from stdin.
+            "-" => options.path = None,
+            flag if flag.starts_with("--") => return Err(format!("unknown flag {flag}")),
+            path => options.path = Some(path.to_string()),
+        }
+    }
+    Ok(options)
+}
+
 fn run_real_compiler() -> bool {
     let args: Vec<String> = env::args().collect();
-    if args.len() <= 1 {
-        eprintln!("mueve [filename.mv]");
-        return false;
-    }
+    let options = match parse_args(&args[1..]) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\nmueve [--dump-tokens] [--dump-ast] [--check-brackets] [--error-format=human|json] [filename.mv]");
+            return false;
+        }
+    };
 
-    let filename: String = args[1].clone();
+    let filename: String = options
+        .path
+        .clone()
+        .unwrap_or_else(|| STDIN_FILENAME.to_string());
     let filename_slice = &filename[..];
-    match fs::read_to_string(filename.clone()) {
+    match read_input(options.path.as_deref()) {
         Ok(input) => {
             let input_slice: &str = &input[..];
-            println!("parsing '{}'...", filename);
-            compile(filename_slice, input_slice)
+            if options.dump_tokens {
+                return dump_tokens(filename_slice, input_slice, &options.error_format);
+            }
+            if options.check_brackets {
+                return report_brackets(filename_slice, input_slice, &options.error_format);
+            }
+            if !options.dump_ast {
+                println!("parsing '{}'...", filename);
+            }
+            match compile(filename_slice, input_slice) {
+                Ok((module, diagnostics)) => {
+                    for warning in diagnostics.warnings() {
+                        report_error(warning, &options.error_format);
+                    }
+                    if options.dump_ast {
+                        println!("{:#?}", module);
+                    } else {
+                        println!("Parsed {:?}", module);
+                    }
+                    true
+                }
+                Err(diagnostics) => {
+                    for err in diagnostics.warnings().iter().chain(diagnostics.errors()) {
+                        report_error(err, &options.error_format);
+                    }
+                    false
+                }
+            }
         }
         Err(err) => {
             eprintln!("Failed to read input ({filename})!\n{err}");
@@ -38,24 +102,122 @@ fn run_real_compiler() -> bool {
     }
 }
 
-fn compile<'a>(filename: &'a str, input: &'a str) -> bool {
-    let mut lexer = Lexer::new(filename, input);
-    lexer.advance();
-    match parse_many(parse_decl, &mut lexer) {
-        Ok(decls) => {
-            println!("Parsed {:?}", decls);
+/// Colorize diagnostics only when stderr is a terminal, honoring https://no-color.org.
+fn use_color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+fn report_error(err: &ParseError, error_format: &ErrorFormat) {
+    match error_format {
+        ErrorFormat::Human => eprintln!("{}", err.render(use_color())),
+        ErrorFormat::Json => eprintln!("{}", err.to_json()),
+    }
+}
+
+fn dump_tokens(filename: &str, input: &str, error_format: &ErrorFormat) -> bool {
+    match tokenize(filename, input) {
+        Ok(tokens) => {
+            for token in tokens {
+                println!("{}: {}", token.location, token);
+            }
             true
         }
         Err(err) => {
-            eprintln!("{}", err);
+            report_error(&err, error_format);
             false
         }
     }
 }
 
+/// Report every unbalanced bracket in `input`, returning whether there were none.
+fn report_brackets(filename: &str, input: &str, error_format: &ErrorFormat) -> bool {
+    let errors = check_brackets(filename, input);
+    for err in &errors {
+        report_error(err, error_format);
+    }
+    errors.is_empty()
+}
+
+fn read_input(path: Option<&str>) -> io::Result<String> {
+    match path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            Ok(input)
+        }
+    }
+}
+
+/// Parse `input`, failing only if there were errors. Warnings are returned either way.
+fn compile<'a>(
+    filename: &'a str,
+    input: &'a str,
+) -> Result<(Module<'a>, Diagnostics<'a>), Diagnostics<'a>> {
+    let (module, mut diagnostics) = parse_str_with_diagnostics(filename, input);
+    resolve_names(&module, &mut diagnostics);
+    if diagnostics.has_errors() {
+        Err(diagnostics)
+    } else {
+        Ok((module, diagnostics))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
     fn lex_some() {}
+
+    #[test]
+    fn compile_valid_input() {
+        let (module, diagnostics) = compile("test.mv", "id x = x").unwrap();
+        assert_eq!(module.items.len(), 1);
+        assert!(diagnostics.warnings().is_empty());
+    }
+
+    #[test]
+    fn compile_succeeds_with_warnings() {
+        let (module, diagnostics) = compile("test.mv", "f = let x = 1 in 2").unwrap();
+        assert_eq!(module.items.len(), 1);
+        assert_eq!(diagnostics.warnings().len(), 1);
+    }
+
+    #[test]
+    fn compile_warns_about_holes() {
+        let (_, diagnostics) = compile("test.mv", "f = ?todo").unwrap();
+        assert_eq!(diagnostics.warnings().len(), 1);
+    }
+
+    #[test]
+    fn compile_rejects_trailing_tokens() {
+        let diagnostics = compile("test.mv", "id x = x\n= 5").unwrap_err();
+        assert_eq!(diagnostics.errors().len(), 1);
+        assert!(diagnostics.errors()[0]
+            .to_string()
+            .contains("expected a declaration"));
+    }
+
+    #[test]
+    fn compile_invalid_input() {
+        let diagnostics = compile("test.mv", "id x = )").unwrap_err();
+        let err = &diagnostics.errors()[0];
+        assert!(err.to_string().contains("test.mv:1:"));
+        assert!(err.to_string().ends_with("id x = )\n       ^"), "{}", err);
+    }
+
+    #[test]
+    fn compile_reports_unbound_symbols() {
+        let diagnostics = compile("test.mv", "f x = g x\ng y = h y").unwrap_err();
+        assert_eq!(diagnostics.errors().len(), 1);
+        assert!(diagnostics.errors()[0]
+            .to_string()
+            .starts_with("test.mv:2:7: error[E0008]:"));
+    }
+
+    #[test]
+    fn compile_reports_several_errors() {
+        let diagnostics = compile("test.mv", "f = a + * b\ng = 1\nh x = match x").unwrap_err();
+        assert_eq!(diagnostics.errors().len(), 2);
+    }
 }
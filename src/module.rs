@@ -0,0 +1,761 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::{Diagnostics, ErrorCode, ParseError, ParseResult};
+use crate::fixity::{duplicate_fixities, scan_fixities, FixityTable};
+use crate::identifier::Identifier;
+use crate::lexer::{Lexeme, Lexer};
+use crate::location::{HasLocation, Location};
+use crate::parser::{
+    expect_item_or_eof, group_clauses, is_keyword, parse_item, parse_items, parse_many, Item,
+};
+use crate::token::Token;
+
+/// A dotted module path, such as `Foo.Bar`.
+#[derive(Debug, Clone)]
+pub struct QualifiedName<'a> {
+    pub parts: Vec<Identifier<'a>>,
+}
+
+impl<'a> HasLocation<'a> for QualifiedName<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        self.parts[0].get_location()
+    }
+}
+
+impl<'a> fmt::Display for QualifiedName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.parts.iter().map(|part| part.name).collect();
+        write!(f, "{}", names.join("."))
+    }
+}
+
+/// An import of another module, such as `import Foo.Bar as Bar (baz, quux)`.
+#[derive(Debug, Clone)]
+pub struct Import<'a> {
+    pub location: Location<'a>,
+    pub path: QualifiedName<'a>,
+    pub alias: Option<Identifier<'a>>,
+    /// The names listed in parentheses, or None to import everything the module exports.
+    pub names: Option<Vec<Identifier<'a>>>,
+}
+
+impl<'a> HasLocation<'a> for Import<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        &self.location
+    }
+}
+
+/// One entry in a module header's export list.
+#[derive(Debug, Clone)]
+pub enum Export<'a> {
+    /// A value, or a type without its constructors.
+    Name(Identifier<'a>),
+    /// An operator, written in parentheses such as `(+)`.
+    Operator(Identifier<'a>),
+    /// A type along with all of its constructors, written `Maybe(..)`.
+    TypeWithCtors(Identifier<'a>),
+}
+
+impl<'a> Export<'a> {
+    /// The exported name, which must be defined in the module.
+    pub fn id(&self) -> &Identifier<'a> {
+        match self {
+            Export::Name(id) | Export::Operator(id) | Export::TypeWithCtors(id) => id,
+        }
+    }
+}
+
+impl<'a> HasLocation<'a> for Export<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        self.id().get_location()
+    }
+}
+
+/// A parsed file: its optional `module Foo.Bar (exports)` header and its items.
+#[derive(Debug, Clone)]
+pub struct Module<'a> {
+    /// The location of the `module` header, or the start of the file if there is none.
+    pub location: Location<'a>,
+    pub filename: &'a str,
+    pub name: Option<QualifiedName<'a>>,
+    /// The header's export list, or None to export everything.
+    pub exports: Option<Vec<Export<'a>>>,
+    pub items: Vec<Item<'a>>,
+}
+
+impl<'a> Module<'a> {
+    /// A module with no header and no items, for files that could not be lexed at all.
+    pub fn empty(filename: &'a str) -> Self {
+        Module {
+            location: Location {
+                filename,
+                line: 1,
+                col: 1,
+            },
+            filename,
+            name: None,
+            exports: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// The builtin fixities, along with those the module declares.
+    pub fn fixities(&self) -> FixityTable<'a> {
+        let mut fixities = FixityTable::builtin();
+        for item in &self.items {
+            if let Item::Fixity(decl) = item {
+                fixities.declare(decl.operator.name, decl.fixity);
+            }
+        }
+        fixities
+    }
+}
+
+impl<'a> HasLocation<'a> for Module<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        &self.location
+    }
+}
+
+/// Parse a dotted name made of uppercase parts, such as `Foo.Bar`.
+pub fn parse_qualified_name<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, QualifiedName<'a>> {
+    let mut parts = Vec::new();
+    loop {
+        match lexer.peek() {
+            Token {
+                location,
+                lexeme: Lexeme::Identifier(name),
+                ..
+            } if name.chars().next().is_some_and(char::is_uppercase) => {
+                lexer.advance()?;
+                parts.push(lexer.identifier(name, location));
+            }
+            token => {
+                return Err(ParseError::unexpected(
+                    token,
+                    "a capitalized module name such as Foo.Bar",
+                ))
+            }
+        }
+        if !lexer.peek_matches(Lexeme::Operator(".")) {
+            return Ok(QualifiedName { parts });
+        }
+        lexer.advance()?;
+    }
+}
+
+fn expect_end_of_line<'a>(lexer: &Lexer<'a>, what: &str) -> ParseResult<'a, ()> {
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Semicolon | Lexeme::Eof,
+            ..
+        } => Ok(()),
+        token => Err(ParseError::unexpected(
+            token,
+            format!("the end of the line after the {}", what),
+        )),
+    }
+}
+
+/// Parse a comma-separated list in parentheses, if the next token opens one. `what` names the
+/// list in errors.
+fn parse_parenthesized_list<'a, T, P>(
+    lexer: &mut Lexer<'a>,
+    what: &str,
+    parse_entry: P,
+) -> ParseResult<'a, Option<Vec<T>>>
+where
+    P: Fn(&mut Lexer<'a>) -> ParseResult<'a, T>,
+{
+    let open_location = match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::LParen,
+            ..
+        } => location,
+        _ => return Ok(None),
+    };
+    lexer.advance()?;
+    let mut entries = Vec::new();
+    while !lexer.peek_matches(Lexeme::RParen) {
+        entries.push(parse_entry(lexer)?);
+        if lexer.peek_matches(Lexeme::Comma) {
+            lexer.advance()?;
+        } else {
+            break;
+        }
+    }
+    if !lexer.peek_matches(Lexeme::RParen) {
+        return Err(ParseError::error(
+            ErrorCode::UnbalancedBracket,
+            open_location,
+            match lexer.peek() {
+                Token {
+                    lexeme: Lexeme::Eof,
+                    ..
+                } => format!("hit EOF before this {what} was closed"),
+                token => format!("expected ')' to close this {what}, found {token}"),
+            },
+        ));
+    }
+    lexer.advance()?;
+    Ok(Some(entries))
+}
+
+fn parse_listed_name<'a>(lexer: &mut Lexer<'a>, expected: &str) -> ParseResult<'a, Identifier<'a>> {
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } if !is_keyword(name) => {
+            lexer.advance()?;
+            Ok(lexer.identifier(name, location))
+        }
+        token => Err(ParseError::unexpected(token, expected)),
+    }
+}
+
+fn parse_export<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Export<'a>> {
+    if lexer.peek_matches(Lexeme::LParen) {
+        lexer.advance()?;
+        let operator = match lexer.peek() {
+            Token {
+                location,
+                lexeme: Lexeme::Operator(name),
+                ..
+            } => Identifier::new(name, location),
+            token => return Err(ParseError::unexpected(token, "an operator to export")),
+        };
+        lexer.advance()?;
+        lexer.chomp(Lexeme::RParen)?;
+        return Ok(Export::Operator(operator));
+    }
+    let id = parse_listed_name(lexer, "a name to export")?;
+    let exports_ctors =
+        parse_parenthesized_list(lexer, "constructor export", |lexer| match lexer.peek() {
+            Token {
+                lexeme: Lexeme::Operator(".."),
+                ..
+            } => lexer.advance(),
+            token => Err(ParseError::unexpected(
+                token,
+                "`..` to export every constructor",
+            )),
+        })?;
+    match exports_ctors {
+        None => Ok(Export::Name(id)),
+        Some(dots) if dots.len() == 1 => Ok(Export::TypeWithCtors(id)),
+        Some(_) => Err(ParseError::error(
+            ErrorCode::UnexpectedToken,
+            *id.get_location(),
+            format!(
+                "expected `{}(..)` to export the constructors of {}",
+                id.name, id.name
+            ),
+        )),
+    }
+}
+
+/// Parse an export list, rejecting a name which is exported twice.
+fn parse_exports<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Vec<Export<'a>>>> {
+    let exports = match parse_parenthesized_list(lexer, "export list", parse_export)? {
+        Some(exports) => exports,
+        None => return Ok(None),
+    };
+    for (i, export) in exports.iter().enumerate() {
+        let name = export.id().name;
+        if exports[..i].iter().any(|earlier| earlier.id().name == name) {
+            return Err(ParseError::error(
+                ErrorCode::UnexpectedToken,
+                *export.get_location(),
+                format!("`{name}` is already exported"),
+            ));
+        }
+    }
+    Ok(Some(exports))
+}
+
+struct ModuleHeader<'a> {
+    location: Location<'a>,
+    name: QualifiedName<'a>,
+    exports: Option<Vec<Export<'a>>>,
+}
+
+/// Parse a `module Foo.Bar (exports)` header, if the file begins with one. The header may end
+/// with `where`, as in `module Foo (a, b) where`, and must end its line.
+fn parse_module_header<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<ModuleHeader<'a>>> {
+    lexer.skip_semicolon()?;
+    let location = match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Identifier("module"),
+            ..
+        } => location,
+        _ => return Ok(None),
+    };
+    lexer.advance()?;
+    let name = parse_qualified_name(lexer)?;
+    let exports = parse_exports(lexer)?;
+    if lexer.peek_matches(Lexeme::Identifier("where")) {
+        lexer.advance()?;
+    }
+    expect_end_of_line(lexer, "module header")?;
+    Ok(Some(ModuleHeader {
+        location,
+        name,
+        exports,
+    }))
+}
+
+/// Parse the rest of an import once its `import` keyword (at `location`) has been consumed.
+pub fn parse_import<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Import<'a>> {
+    let path = parse_qualified_name(lexer)?;
+    let alias = if lexer.peek_matches(Lexeme::Identifier("as")) {
+        lexer.advance()?;
+        let alias = parse_qualified_name(lexer)?;
+        if alias.parts.len() > 1 {
+            return Err(ParseError::error(
+                ErrorCode::UnexpectedToken,
+                *alias.parts[1].get_location(),
+                "an import alias must be a single name",
+            ));
+        }
+        alias.parts.into_iter().next()
+    } else {
+        None
+    };
+    let names = parse_parenthesized_list(lexer, "import list", |lexer| {
+        parse_listed_name(lexer, "a name to import")
+    })?;
+    expect_end_of_line(lexer, "import")?;
+    Ok(Import {
+        location,
+        path,
+        alias,
+        names,
+    })
+}
+
+/// Group the clauses of multi-clause decls, and check what depends on more than one item:
+/// clause arities, imports after the first value declaration, and operators given a fixity more
+/// than once.
+fn check_items<'a>(items: Vec<Item<'a>>) -> (Vec<Item<'a>>, Vec<ParseError<'a>>) {
+    let (items, mut errors) = group_clauses(items);
+    errors.extend(misplaced_imports(&items));
+    errors.extend(duplicate_fixities(items.iter().filter_map(
+        |item| match item {
+            Item::Fixity(fixity_decl) => Some(fixity_decl),
+            _ => None,
+        },
+    )));
+    errors.sort_by_key(|err| *err.get_location());
+    (items, errors)
+}
+
+/// Imports are only allowed before the first value declaration. Returns an error for each one
+/// which comes after it.
+fn misplaced_imports<'a>(items: &[Item<'a>]) -> Vec<ParseError<'a>> {
+    let first_decl = items
+        .iter()
+        .position(|item| matches!(item, Item::Decl(_)))
+        .unwrap_or(items.len());
+    items[first_decl..]
+        .iter()
+        .filter_map(|item| match item {
+            Item::Import(import) => Some(ParseError::error(
+                ErrorCode::UnexpectedToken,
+                import.location,
+                "imports must come before the first declaration",
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn new_module<'a>(
+    lexer: &Lexer<'a>,
+    header: Option<ModuleHeader<'a>>,
+    items: Vec<Item<'a>>,
+) -> Module<'a> {
+    let mut module = Module::empty(lexer.location.filename);
+    if let Some(header) = header {
+        module.location = header.location;
+        module.name = Some(header.name);
+        module.exports = header.exports;
+    }
+    module.items = items;
+    module
+}
+
+/// Parse a whole file, stopping at the first error.
+pub fn parse_module<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Module<'a>> {
+    lexer.fixities = Rc::new(scan_fixities(lexer));
+    let header = parse_module_header(lexer)?;
+    let items = parse_many(parse_item, lexer)?;
+    expect_item_or_eof(lexer)?;
+    let (items, errors) = check_items(items);
+    if let Some(err) = errors.into_iter().next() {
+        return Err(err);
+    }
+    Ok(new_module(lexer, header, items))
+}
+
+/// Parse a whole file, recording errors in `diagnostics` and recovering from them so that every
+/// broken item is reported.
+pub fn parse_module_with_diagnostics<'a>(
+    lexer: &mut Lexer<'a>,
+    diagnostics: &mut Diagnostics<'a>,
+) -> Module<'a> {
+    lexer.fixities = Rc::new(scan_fixities(lexer));
+    let header = match parse_module_header(lexer) {
+        Ok(header) => header,
+        Err(err) => {
+            diagnostics.error(err);
+            if lexer.recover_to_semicolon().is_err() {
+                return new_module(lexer, None, Vec::new());
+            }
+            None
+        }
+    };
+    let (items, errors) = check_items(parse_items(lexer, diagnostics));
+    for err in errors {
+        diagnostics.error(err);
+    }
+    new_module(lexer, header, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::pretty_print;
+
+    fn parse(input: &'static str) -> ParseResult<'static, Module<'static>> {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance()?;
+        parse_module(&mut lexer)
+    }
+
+    #[test]
+    fn module_header() {
+        let module = parse("module Foo.Bar\n\nf x = x\ng = 1").unwrap();
+        assert_eq!(module.name.as_ref().unwrap().to_string(), "Foo.Bar");
+        assert_eq!(module.filename, "test.mv");
+        assert_eq!((module.location.line, module.location.col), (1, 1));
+        assert_eq!(module.items.len(), 2);
+    }
+
+    fn parse_import(input: &'static str) -> Import<'static> {
+        match parse(input).unwrap().items.remove(0) {
+            Item::Import(import) => import,
+            item => panic!("expected an import, got {:?}", item),
+        }
+    }
+
+    fn names<'a>(import: &Import<'a>) -> Option<Vec<&'a str>> {
+        import
+            .names
+            .as_ref()
+            .map(|names| names.iter().map(|name| name.name).collect())
+    }
+
+    #[test]
+    fn bare_import() {
+        let import = parse_import("import Data.List");
+        assert_eq!(import.path.to_string(), "Data.List");
+        assert!(import.alias.is_none());
+        assert!(import.names.is_none());
+    }
+
+    #[test]
+    fn selective_import() {
+        let import = parse_import("import Data.List (map,\n    Cons, foldr)");
+        assert_eq!(names(&import), Some(vec!["map", "Cons", "foldr"]));
+        let foldr = import.names.as_ref().unwrap()[2].get_location();
+        assert_eq!((foldr.line, foldr.col), (2, 11));
+        assert_eq!(names(&parse_import("import Data.List ()")), Some(vec![]));
+        assert_eq!(
+            names(&parse_import("import Data.List (\n    map,\n    foldr,\n)")),
+            Some(vec!["map", "foldr"])
+        );
+    }
+
+    #[test]
+    fn imports_before_definitions() {
+        let module = parse("import Foo\nimport Foo (bar, baz)\nf = bar baz").unwrap();
+        assert_eq!(module.items.len(), 3);
+        let Item::Import(bare) = &module.items[0] else {
+            panic!("expected an import, got {:?}", module.items[0]);
+        };
+        assert_eq!(bare.path.to_string(), "Foo");
+        assert!(bare.names.is_none());
+        let Item::Import(selective) = &module.items[1] else {
+            panic!("expected an import, got {:?}", module.items[1]);
+        };
+        assert_eq!(selective.path.to_string(), "Foo");
+        assert_eq!(names(selective), Some(vec!["bar", "baz"]));
+        assert!(matches!(module.items[2], Item::Decl(_)));
+    }
+
+    #[test]
+    fn aliased_import() {
+        let import = parse_import("import Data.Map as M (insert)");
+        assert_eq!(import.alias.as_ref().unwrap().name, "M");
+        assert_eq!(names(&import), Some(vec!["insert"]));
+    }
+
+    #[test]
+    fn import_errors() {
+        let err = parse("module Main\nimport A\nf = 1\nimport B")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.mv:4:1: error[E0001]:"), "{}", err);
+        let err = parse("import A (b c)").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:10: error[E0003]:"), "{}", err);
+        for input in ["import A (b,,)", "import A (,b)"] {
+            let err = parse(input).unwrap_err().to_string();
+            assert!(err.starts_with("test.mv:1:"), "{}", err);
+            assert!(!err.contains("E0004"), "{}", err);
+        }
+        let err = parse("import A as B.C").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:15: error[E0001]:"), "{}", err);
+    }
+
+    fn exports(input: &'static str) -> Vec<String> {
+        parse(input)
+            .unwrap()
+            .exports
+            .unwrap()
+            .iter()
+            .map(|export| match export {
+                Export::Name(id) => id.name.to_string(),
+                Export::Operator(id) => format!("({})", id.name),
+                Export::TypeWithCtors(id) => format!("{}(..)", id.name),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn module_export_list() {
+        assert_eq!(
+            exports("module Foo (f, g, Maybe(..), (+),\n    Either)"),
+            vec!["f", "g", "Maybe(..)", "(+)", "Either"]
+        );
+        assert!(parse("module Foo").unwrap().exports.is_none());
+        assert!(exports("module Foo ()").is_empty());
+        assert_eq!(exports("module Foo (f, g,)"), vec!["f", "g"]);
+        let module = parse("module Foo (a, b) where\na = 1\nb = 2").unwrap();
+        assert_eq!(module.name.as_ref().unwrap().to_string(), "Foo");
+        assert_eq!(module.items.len(), 2);
+        assert_eq!(exports("module Foo (a, b) where"), vec!["a", "b"]);
+        assert!(parse("module Foo where\nf = 1").unwrap().exports.is_none());
+    }
+
+    #[test]
+    fn module_export_errors() {
+        let err = parse("module Foo (f, g, f)").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:19: error[E0001]:"), "{}", err);
+        assert!(err.contains("`f` is already exported"), "{}", err);
+        let err = parse("module Foo (Maybe(Just))").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:19: error[E0001]:"), "{}", err);
+        let err = parse("module Foo (f").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:12: error[E0003]:"), "{}", err);
+    }
+
+    fn printed(input: &'static str) -> String {
+        let module = parse(input).unwrap();
+        let fixities = module.fixities();
+        let decls: Vec<_> = module
+            .items
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Decl(decl) => Some(decl),
+                _ => None,
+            })
+            .collect();
+        pretty_print(&decls, &fixities)
+    }
+
+    #[test]
+    fn default_fixities() {
+        assert_eq!(
+            printed("f = a + b * c ^ d ^ e - g . h"),
+            "f = a + b * c ^ d ^ e - g . h\n"
+        );
+        assert_eq!(printed("f = (a - b) - c"), "f = a - b - c\n");
+        assert_eq!(printed("f = a - (b - c)"), "f = a - (b - c)\n");
+        let err = parse("f = a <+> b").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:7: error[E0006]:"), "{}", err);
+    }
+
+    #[test]
+    fn fixity_declarations_apply_before_and_after_them() {
+        assert_eq!(
+            printed("f = a <+> b <+> c\ninfixr 5 <+>\ng = a <+> b <+> c"),
+            "f = a <+> b <+> c\ng = a <+> b <+> c\n"
+        );
+        // Overriding a builtin.
+        assert_eq!(printed("infixr 6 -\nf = a - b - c"), "f = a - b - c\n");
+        assert_eq!(printed("infixr 6 -\nf = (a - b) - c"), "f = (a - b) - c\n");
+        assert_eq!(
+            printed("infixl 7 <+>\nf = a + b <+> c"),
+            "f = a + b <+> c\n"
+        );
+        assert_eq!(
+            printed("infixl 7 <+>\nf = (a + b) <+> c"),
+            "f = (a + b) <+> c\n"
+        );
+    }
+
+    #[test]
+    fn fixity_redefinition() {
+        let err = parse("infixl 6 <+>\nf = 1\ninfixr 6 <+>")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.mv:3:1: error[E0011]:"), "{}", err);
+        assert!(err.contains("already declared at test.mv:1:1"), "{}", err);
+        let err = parse("infixl 10 <+>").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:8: error[E0001]:"), "{}", err);
+    }
+
+    #[test]
+    fn mixing_associativities_at_one_level() {
+        let err = parse("infixl 6 <+\ninfixr 6 +>\nf = a <+ b +> c")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.mv:3:12: error[E0006]:"), "{}", err);
+        assert!(
+            err.contains("cannot mix <+ (infixl 6) and +> (infixr 6)"),
+            "{}",
+            err
+        );
+        let err = parse("infixl 6 <+\ninfixr 6 +>\nf = a +> b <+ c")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.mv:3:12: error[E0006]:"), "{}", err);
+        let err = parse("infix 4 ==\nf = a == b == c")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("test.mv:2:12: error[E0006]:"), "{}", err);
+        // The builtin comparisons don't chain either, without having to be declared.
+        let err = parse("f = 1 == 2 == 3").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:12: error[E0006]:"), "{}", err);
+        assert!(
+            err.contains("cannot mix == (infix 4) and == (infix 4)"),
+            "{}",
+            err
+        );
+        let err = parse("f = a < b <= c").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:11: error[E0006]:"), "{}", err);
+        assert_eq!(
+            printed("f = (1 == 2) == (a < b)"),
+            "f = (1 == 2) == (a < b)\n"
+        );
+        assert_eq!(
+            printed("infixl 6 <+\ninfixr 6 +>\nf = (a <+ b) +> c"),
+            "f = (a <+ b) +> c\n"
+        );
+    }
+
+    fn decl_names(module: &Module) -> Vec<String> {
+        module
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Decl(decl) => {
+                    format!("{}/{}", decl.get_location().line, decl.clauses().len())
+                }
+                item => panic!("expected only decls, found {:?}", item),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adjacent_clauses_form_one_decl() {
+        let module = parse("len [] = 0\nlen (x : xs) = add 1 (len xs)\nhead (x : _) = x").unwrap();
+        assert_eq!(decl_names(&module), vec!["1/2", "3/1"]);
+        let module = parse("f 0 = 1\ng = 2\nf n = n").unwrap();
+        assert_eq!(decl_names(&module), vec!["1/1", "2/1", "3/1"]);
+    }
+
+    #[test]
+    fn clauses_must_agree_on_arity() {
+        let err = parse("f 0 = 1\nf n m = n").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:2:1: error[E0010]:"), "{}", err);
+        assert!(
+            err.contains("has 2 parameter(s), but the clause at test.mv:1:1 has 1"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn module_without_header() {
+        let module = parse("f x = x").unwrap();
+        assert!(module.name.is_none());
+        assert_eq!(module.items.len(), 1);
+    }
+
+    #[test]
+    fn module_header_must_come_first() {
+        let err = parse("f x = x\nmodule Foo").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:2:1: error[E0001]:"), "{}", err);
+        let err = parse("module foo").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:8: error[E0001]:"), "{}", err);
+    }
+
+    #[test]
+    fn tokens_after_the_last_item() {
+        for (input, location) in [
+            ("f x = x\n= 5", "2:1"),
+            ("= 5\nf x = x", "1:1"),
+            ("f x = x\ng y = y\n42 43", "3:1"),
+            ("\"not a decl\"", "1:1"),
+        ] {
+            let err = parse(input).unwrap_err().to_string();
+            assert!(
+                err.starts_with(&format!("test.mv:{}: error[E0001]:", location)),
+                "{}",
+                err
+            );
+            assert!(err.contains("expected a declaration"), "{}", err);
+        }
+        assert!(parse("").unwrap().items.is_empty());
+        assert!(parse("# just a comment\n").unwrap().items.is_empty());
+    }
+
+    #[test]
+    fn tokens_after_the_last_item_with_diagnostics() {
+        let source = "= 5\nf x = x\n=> 1\ng = 1\n42";
+        let mut diagnostics = Diagnostics::new(source);
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let module = parse_module_with_diagnostics(&mut lexer, &mut diagnostics);
+        assert_eq!(module.items.len(), 2);
+        let errors: Vec<String> = diagnostics
+            .errors()
+            .iter()
+            .map(|err| err.to_string())
+            .collect();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(
+            errors[0].starts_with("test.mv:1:1: error[E0001]:"),
+            "{}",
+            errors[0]
+        );
+        assert!(
+            errors[1].starts_with("test.mv:3:1: error[E0001]:"),
+            "{}",
+            errors[1]
+        );
+        assert!(
+            errors[2].starts_with("test.mv:5:1: error[E0001]:"),
+            "{}",
+            errors[2]
+        );
+    }
+}
@@ -1,23 +1,41 @@
-use std::fmt;
-
-use crate::error::{ParseError, ParseResult};
+use crate::error::{suggest_keyword, Diagnostics, ErrorCode, ParseError, ParseResult};
+use crate::fixity::{
+    fixity_keyword, is_reserved_operator, parse_fixity_decl, Associativity, Fixity, FixityDecl,
+    FixityTable, BACKTICK_FIXITY,
+};
 use crate::identifier::Identifier;
-use crate::lexer::{Lexeme, Lexer};
-use crate::location::{HasLocation, Location};
+use crate::lexer::{is_operator_char, Lexeme, Lexer};
+use crate::location::{HasLocation, Location, Span};
+use crate::module::{parse_import, Import};
 use crate::token::Token;
+use crate::types::{parse_type_atom, TypeExpr};
 
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
-enum Predicate<'a> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Predicate<'a> {
     Irrefutable(Identifier<'a>),
     Integer {
         location: Location<'a>,
         value: i64,
     },
+    Float {
+        location: Location<'a>,
+        value: f64,
+    },
     String {
         location: Location<'a>,
         value: String,
     },
+    Char {
+        location: Location<'a>,
+        value: char,
+    },
+    /// `True` or `False`, which are builtin literals rather than constructors.
+    Bool {
+        location: Location<'a>,
+        value: bool,
+    },
     Ctor {
         ctor_id: Identifier<'a>,
         dims: Vec<Box<Predicate<'a>>>,
@@ -26,33 +44,298 @@ enum Predicate<'a> {
         location: Location<'a>,
         dims: Vec<Box<Predicate<'a>>>,
     },
+    List {
+        location: Location<'a>,
+        dims: Vec<Box<Predicate<'a>>>,
+    },
+    Cons {
+        head: Box<Predicate<'a>>,
+        tail: Box<Predicate<'a>>,
+    },
+    As {
+        binding: Identifier<'a>,
+        pattern: Box<Predicate<'a>>,
+    },
+    Or {
+        location: Location<'a>,
+        alternatives: Vec<Box<Predicate<'a>>>,
+    },
+    /// A record pattern such as `{ name = n, age }`. A field without a pattern binds a variable
+    /// of the same name. With `rest`, written `..`, any other fields are ignored.
+    Record {
+        location: Location<'a>,
+        fields: Vec<(Identifier<'a>, Option<Box<Predicate<'a>>>)>,
+        rest: bool,
+    },
+}
+
+impl<'a> Predicate<'a> {
+    /// The names bound by this predicate, in the order they appear. `_` binds nothing.
+    pub(crate) fn bound_names(&self, names: &mut Vec<&'a str>) {
+        let mut ids = Vec::new();
+        self.bound_ids(&mut ids);
+        names.extend(ids.iter().map(|id| id.name));
+    }
+
+    /// The identifiers bound by this predicate, in the order they appear.
+    fn bound_ids<'b>(&'b self, ids: &mut Vec<&'b Identifier<'a>>) {
+        match self {
+            Predicate::Irrefutable(id) => {
+                if id.name != "_" {
+                    ids.push(id)
+                }
+            }
+            Predicate::Integer { .. }
+            | Predicate::Float { .. }
+            | Predicate::String { .. }
+            | Predicate::Char { .. }
+            | Predicate::Bool { .. } => (),
+            Predicate::Ctor { dims, .. }
+            | Predicate::Tuple { dims, .. }
+            | Predicate::List { dims, .. } => {
+                for dim in dims {
+                    dim.bound_ids(ids);
+                }
+            }
+            Predicate::Cons { head, tail } => {
+                head.bound_ids(ids);
+                tail.bound_ids(ids);
+            }
+            Predicate::As { binding, pattern } => {
+                ids.push(binding);
+                pattern.bound_ids(ids);
+            }
+            // Every alternative binds the same names (see check_or_patterns).
+            Predicate::Or { alternatives, .. } => alternatives[0].bound_ids(ids),
+            Predicate::Record { fields, .. } => {
+                for (field, predicate) in fields {
+                    match predicate {
+                        Some(predicate) => predicate.bound_ids(ids),
+                        None => ids.push(field),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check that this predicate binds each name at most once, as `(x, x)` is ambiguous. Since
+    /// the alternatives of an or-pattern bind the same names, this should follow
+    /// check_or_patterns.
+    fn check_duplicate_bindings(&self) -> ParseResult<'a, ()> {
+        let mut ids = Vec::new();
+        self.bound_ids(&mut ids);
+        check_duplicate_ids(&ids, "this pattern")
+    }
+
+    /// Warn about float literals within this predicate, since exact float equality is rarely what
+    /// was intended.
+    fn lint(&self, diagnostics: &mut Diagnostics<'a>) {
+        match self {
+            Predicate::Float { location, value } => diagnostics.warning(ParseError::warning(
+                ErrorCode::FloatPattern,
+                *location,
+                format!(
+                    "matching the float {} relies on exact float equality",
+                    value
+                ),
+            )),
+            Predicate::Irrefutable(_)
+            | Predicate::Integer { .. }
+            | Predicate::String { .. }
+            | Predicate::Char { .. }
+            | Predicate::Bool { .. } => (),
+            Predicate::Ctor { dims, .. }
+            | Predicate::Tuple { dims, .. }
+            | Predicate::List { dims, .. } => {
+                for dim in dims {
+                    dim.lint(diagnostics);
+                }
+            }
+            Predicate::Cons { head, tail } => {
+                head.lint(diagnostics);
+                tail.lint(diagnostics);
+            }
+            Predicate::As { pattern, .. } => pattern.lint(diagnostics),
+            Predicate::Or { alternatives, .. } => {
+                for alternative in alternatives {
+                    alternative.lint(diagnostics);
+                }
+            }
+            Predicate::Record { fields, .. } => {
+                for predicate in fields
+                    .iter()
+                    .filter_map(|(_, predicate)| predicate.as_ref())
+                {
+                    predicate.lint(diagnostics);
+                }
+            }
+        }
+    }
+
+    /// Check that every alternative of each or-pattern within this predicate binds the same set
+    /// of names, so that the body can use them regardless of which alternative matched.
+    fn check_or_patterns(&self) -> ParseResult<'a, ()> {
+        match self {
+            Predicate::Irrefutable(_)
+            | Predicate::Integer { .. }
+            | Predicate::Float { .. }
+            | Predicate::String { .. }
+            | Predicate::Char { .. }
+            | Predicate::Bool { .. } => Ok(()),
+            Predicate::Ctor { dims, .. }
+            | Predicate::Tuple { dims, .. }
+            | Predicate::List { dims, .. } => {
+                dims.iter().try_for_each(|dim| dim.check_or_patterns())
+            }
+            Predicate::Cons { head, tail } => {
+                head.check_or_patterns()?;
+                tail.check_or_patterns()
+            }
+            Predicate::As { pattern, .. } => pattern.check_or_patterns(),
+            Predicate::Record { fields, .. } => fields
+                .iter()
+                .filter_map(|(_, predicate)| predicate.as_ref())
+                .try_for_each(|predicate| predicate.check_or_patterns()),
+            Predicate::Or { alternatives, .. } => {
+                let mut expected_names = Vec::new();
+                alternatives[0].bound_names(&mut expected_names);
+                expected_names.sort();
+                for alternative in alternatives {
+                    alternative.check_or_patterns()?;
+                    let mut names = Vec::new();
+                    alternative.bound_names(&mut names);
+                    names.sort();
+                    if names != expected_names {
+                        return Err(ParseError::error(
+                            ErrorCode::InvalidPattern,
+                            *alternative.get_location(),
+                            format!(
+                                "every alternative of an or-pattern must bind the same names \
+                                 (expected [{}], found [{}])",
+                                expected_names.join(", "),
+                                names.join(", ")
+                            ),
+                        ));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 impl<'a> HasLocation<'a> for Predicate<'a> {
     fn get_location(&self) -> &Location<'a> {
         match self {
             Predicate::Irrefutable(id) => id.get_location(),
-            Predicate::Integer { location, value: _ } => &location,
-            Predicate::String { location, value: _ } => &location,
+            Predicate::Integer { location, value: _ } => location,
+            Predicate::Float { location, value: _ } => location,
+            Predicate::String { location, value: _ } => location,
+            Predicate::Char { location, value: _ } => location,
+            Predicate::Bool { location, value: _ } => location,
             Predicate::Ctor { ctor_id, dims: _ } => ctor_id.get_location(),
-            Predicate::Tuple { location, dims: _ } => &location,
+            Predicate::Tuple { location, dims: _ } => location,
+            Predicate::List { location, dims: _ } => location,
+            Predicate::Cons { head, tail: _ } => head.get_location(),
+            Predicate::As {
+                binding,
+                pattern: _,
+            } => binding.get_location(),
+            Predicate::Or {
+                location,
+                alternatives: _,
+            } => location,
+            Predicate::Record {
+                location,
+                fields: _,
+                rest: _,
+            } => location,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PatternExpr<'a> {
-    predicate: Predicate<'a>,
-    expr: Expr<'a>,
+    pub(crate) predicate: Predicate<'a>,
+    /// The arm only fires when the predicate matches and this guard is truthy.
+    pub(crate) guard: Option<Expr<'a>>,
+    pub(crate) expr: Expr<'a>,
+}
+
+/// One `pattern <- source` of a list comprehension, which draws each element of `source` in turn
+/// and matches it against `pattern`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Generator<'a> {
+    pub(crate) pattern: Box<Predicate<'a>>,
+    pub(crate) source: Expr<'a>,
+}
+
+/// A piece of an interpolated string: either text, with `{{` and `}}` already unescaped, or an
+/// embedded expression.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum InterpPart<'a> {
+    Literal(String),
+    Expr(Expr<'a>),
+}
+
+impl<'a> InterpPart<'a> {
+    pub fn expr(&self) -> Option<&Expr<'a>> {
+        match self {
+            InterpPart::Literal(_) => None,
+            InterpPart::Expr(expr) => Some(expr),
+        }
+    }
+}
+
+/// One statement of a do-block.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum DoStatement<'a> {
+    /// An expression evaluated in sequence. The last statement of a block is always one of
+    /// these, and gives the block its value.
+    Expr(Expr<'a>),
+    /// `let name = value`, without an `in`. The binding is in scope for the rest of the block.
+    Let {
+        binding: Identifier<'a>,
+        value: Expr<'a>,
+    },
+    /// `name <- value`, which binds the result of the action `value` for the rest of the block.
+    Bind {
+        binding: Identifier<'a>,
+        value: Expr<'a>,
+    },
+}
+
+impl<'a> DoStatement<'a> {
+    pub fn value(&self) -> &Expr<'a> {
+        match self {
+            DoStatement::Expr(expr)
+            | DoStatement::Let { value: expr, .. }
+            | DoStatement::Bind { value: expr, .. } => expr,
+        }
+    }
+
+    /// The name this statement binds for the rest of its block, if any.
+    pub fn binding(&self) -> Option<&Identifier<'a>> {
+        match self {
+            DoStatement::Expr(_) => None,
+            DoStatement::Let { binding, .. } | DoStatement::Bind { binding, .. } => Some(binding),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr<'a> {
     Lambda {
         location: Location<'a>,
-        param_names: Vec<Identifier<'a>>,
+        /// One pattern for each parameter, so that a lambda may destructure its arguments.
+        params: Vec<Box<Predicate<'a>>>,
         body: Box<Expr<'a>>,
     },
     Let {
@@ -61,6 +344,43 @@ pub enum Expr<'a> {
         value: Box<Expr<'a>>,
         body: Box<Expr<'a>>,
     },
+    /// A recursive let, `let rec a = ...; b = ... in body`. Its bindings form one group, each
+    /// in scope within every binding's value as well as the body.
+    LetRec {
+        location: Location<'a>,
+        bindings: Vec<(Identifier<'a>, Expr<'a>)>,
+        body: Box<Expr<'a>>,
+    },
+    /// A let which destructures its value with a pattern, as in `let (a, b) = pair in a`. A let
+    /// of a plain name is always an `Expr::Let`. The pattern may be refutable; that is left to
+    /// exhaustiveness checking.
+    LetPattern {
+        location: Location<'a>,
+        pattern: Box<Predicate<'a>>,
+        value: Box<Expr<'a>>,
+        body: Box<Expr<'a>>,
+    },
+    /// `do { statement; ...; result }`. See desugar_do for what it means in terms of other
+    /// expressions.
+    Do {
+        location: Location<'a>,
+        statements: Vec<DoStatement<'a>>,
+        result: Box<Expr<'a>>,
+    },
+    /// `{ statement; ...; result }`, whose value is that of `result`. The statements are those
+    /// of a do-block, other than binds.
+    Block {
+        location: Location<'a>,
+        statements: Vec<DoStatement<'a>>,
+        result: Box<Expr<'a>>,
+    },
+    /// `if condition then a else b`. The else branch is required.
+    If {
+        location: Location<'a>,
+        condition: Box<Expr<'a>>,
+        then_branch: Box<Expr<'a>>,
+        else_branch: Box<Expr<'a>>,
+    },
     LiteralInteger {
         location: Location<'a>,
         value: i64,
@@ -69,26 +389,373 @@ pub enum Expr<'a> {
         location: Location<'a>,
         value: f64,
     },
+    /// A string literal without interpolation. The value is the text between the quotes, with
+    /// doubled braces undoubled, just like the text of an `InterpPart::Literal`.
     LiteralString {
         location: Location<'a>,
         value: String,
     },
+    /// An interpolated string such as `"hello {name}"`, which concatenates its parts in order.
+    /// Empty text between parts is left out.
+    Interp {
+        location: Location<'a>,
+        parts: Vec<InterpPart<'a>>,
+    },
+    LiteralChar {
+        location: Location<'a>,
+        value: char,
+    },
+    /// `True` or `False`, which are builtin literals rather than constructors.
+    LiteralBool {
+        location: Location<'a>,
+        value: bool,
+    },
     Symbol {
         id: Identifier<'a>,
     },
+    /// `start..end`, or `start..=end` when inclusive. Either end may be left open, as in `1..`.
+    /// Within brackets a range may also have a step, given by its second element as in
+    /// `[0, 2..20]`; both ends are then required.
+    Range {
+        location: Location<'a>,
+        start: Option<Box<Expr<'a>>>,
+        /// The second element of a stepped range, so that the step is its distance from `start`.
+        step: Option<Box<Expr<'a>>>,
+        end: Option<Box<Expr<'a>>>,
+        inclusive: bool,
+    },
+    /// A placeholder for an expression still to be written, `?name`, or `?` without a name.
+    Hole {
+        location: Location<'a>,
+        name: Option<&'a str>,
+    },
+    /// A qualified name such as `List::map`, with at least two segments.
+    Path {
+        location: Location<'a>,
+        segments: Vec<Identifier<'a>>,
+    },
     Match {
         location: Location<'a>,
         subject: Box<Expr<'a>>,
         pattern_exprs: Vec<PatternExpr<'a>>,
     },
     Callsite {
+        /// Covers the function through the last argument.
+        span: Span<'a>,
         function: Box<Expr<'a>>,
         arguments: Vec<Box<Expr<'a>>>,
     },
     TupleCtor {
-        location: Location<'a>,
+        /// Covers the parentheses.
+        span: Span<'a>,
         dims: Vec<Box<Expr<'a>>>,
     },
+    /// A list literal such as `[1, 2, 3]`.
+    List {
+        /// Covers the brackets.
+        span: Span<'a>,
+        items: Vec<Box<Expr<'a>>>,
+    },
+    /// A list comprehension such as `[x * 2 | x <- xs, x > 0]`. The names bound by each
+    /// generator are in scope for the output and for every qualifier after it.
+    Comprehension {
+        /// Covers the brackets.
+        span: Span<'a>,
+        output: Box<Expr<'a>>,
+        generators: Vec<Generator<'a>>,
+        /// Each guard, with the number of generators before it, whose names it may use.
+        guards: Vec<(usize, Expr<'a>)>,
+    },
+    Record {
+        location: Location<'a>,
+        fields: Vec<(Identifier<'a>, Expr<'a>)>,
+    },
+    FieldAccess {
+        subject: Box<Expr<'a>>,
+        field: Identifier<'a>,
+    },
+    BinaryOp {
+        op: Identifier<'a>,
+        lhs: Box<Expr<'a>>,
+        rhs: Box<Expr<'a>>,
+    },
+    UnaryOp {
+        op: Identifier<'a>,
+        operand: Box<Expr<'a>>,
+    },
+}
+
+impl<'a> Expr<'a> {
+    /// Whether `name` is referenced anywhere within this expression. Shadowing is ignored, so
+    /// this may report a mention of an inner binding of the same name.
+    fn mentions(&self, name: &str) -> bool {
+        match self {
+            Expr::Lambda { body, .. } => body.mentions(name),
+            Expr::Let { value, body, .. } | Expr::LetPattern { value, body, .. } => {
+                value.mentions(name) || body.mentions(name)
+            }
+            Expr::LetRec { bindings, body, .. } => {
+                bindings.iter().any(|(_, value)| value.mentions(name)) || body.mentions(name)
+            }
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                condition.mentions(name) || then_branch.mentions(name) || else_branch.mentions(name)
+            }
+            Expr::LiteralInteger { .. }
+            | Expr::LiteralFloat { .. }
+            | Expr::LiteralString { .. }
+            | Expr::LiteralChar { .. }
+            | Expr::LiteralBool { .. } => false,
+            Expr::Interp { parts, .. } => parts
+                .iter()
+                .filter_map(InterpPart::expr)
+                .any(|expr| expr.mentions(name)),
+            Expr::Do {
+                statements, result, ..
+            }
+            | Expr::Block {
+                statements, result, ..
+            } => {
+                statements
+                    .iter()
+                    .any(|statement| statement.value().mentions(name))
+                    || result.mentions(name)
+            }
+            Expr::Symbol { id } => id.name == name,
+            Expr::Path { .. } | Expr::Hole { .. } => false,
+            Expr::Range {
+                start, step, end, ..
+            } => [start, step, end]
+                .into_iter()
+                .flatten()
+                .any(|bound| bound.mentions(name)),
+            Expr::Match {
+                subject,
+                pattern_exprs,
+                ..
+            } => {
+                subject.mentions(name)
+                    || pattern_exprs.iter().any(|pattern_expr| {
+                        pattern_expr
+                            .guard
+                            .as_ref()
+                            .is_some_and(|guard| guard.mentions(name))
+                            || pattern_expr.expr.mentions(name)
+                    })
+            }
+            Expr::Callsite {
+                function,
+                arguments,
+                ..
+            } => function.mentions(name) || arguments.iter().any(|arg| arg.mentions(name)),
+            Expr::TupleCtor { dims, .. } => dims.iter().any(|dim| dim.mentions(name)),
+            Expr::List { items, .. } => items.iter().any(|item| item.mentions(name)),
+            Expr::Comprehension {
+                output,
+                generators,
+                guards,
+                ..
+            } => {
+                output.mentions(name)
+                    || generators
+                        .iter()
+                        .any(|generator| generator.source.mentions(name))
+                    || guards.iter().any(|(_, guard)| guard.mentions(name))
+            }
+            Expr::Record { fields, .. } => fields.iter().any(|(_, value)| value.mentions(name)),
+            Expr::FieldAccess { subject, .. } => subject.mentions(name),
+            Expr::BinaryOp { lhs, rhs, .. } => lhs.mentions(name) || rhs.mentions(name),
+            Expr::UnaryOp { operand, .. } => operand.mentions(name),
+        }
+    }
+
+    /// Report warnings within this expression: each `let` whose binding is never used (bindings
+    /// whose names start with `_` are deliberately unused), and suspicious match patterns.
+    fn lint(&self, diagnostics: &mut Diagnostics<'a>) {
+        match self {
+            Expr::Lambda { body, .. } => body.lint(diagnostics),
+            Expr::Let {
+                binding,
+                value,
+                body,
+                ..
+            } => {
+                if !binding.name.starts_with('_') && !body.mentions(binding.name) {
+                    diagnostics.warning(ParseError::warning(
+                        ErrorCode::UnusedBinding,
+                        *binding.get_location(),
+                        format!("unused let binding `{}`", binding.name),
+                    ));
+                }
+                value.lint(diagnostics);
+                body.lint(diagnostics);
+            }
+            Expr::LetRec { bindings, body, .. } => {
+                for (index, (binding, _)) in bindings.iter().enumerate() {
+                    // A binding used only within its own value is still unused.
+                    let used = body.mentions(binding.name)
+                        || bindings.iter().enumerate().any(|(other, (_, value))| {
+                            other != index && value.mentions(binding.name)
+                        });
+                    if !binding.name.starts_with('_') && !used {
+                        diagnostics.warning(ParseError::warning(
+                            ErrorCode::UnusedBinding,
+                            *binding.get_location(),
+                            format!("unused let binding `{}`", binding.name),
+                        ));
+                    }
+                }
+                for (_, value) in bindings {
+                    value.lint(diagnostics);
+                }
+                body.lint(diagnostics);
+            }
+            Expr::LetPattern {
+                pattern,
+                value,
+                body,
+                ..
+            } => {
+                let mut ids = Vec::new();
+                pattern.bound_ids(&mut ids);
+                for id in ids {
+                    if !id.name.starts_with('_') && !body.mentions(id.name) {
+                        diagnostics.warning(ParseError::warning(
+                            ErrorCode::UnusedBinding,
+                            *id.get_location(),
+                            format!("unused let binding `{}`", id.name),
+                        ));
+                    }
+                }
+                value.lint(diagnostics);
+                body.lint(diagnostics);
+            }
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                condition.lint(diagnostics);
+                then_branch.lint(diagnostics);
+                else_branch.lint(diagnostics);
+            }
+            Expr::LiteralInteger { .. }
+            | Expr::LiteralFloat { .. }
+            | Expr::LiteralString { .. }
+            | Expr::LiteralChar { .. }
+            | Expr::LiteralBool { .. }
+            | Expr::Symbol { .. }
+            | Expr::Path { .. } => (),
+            Expr::Interp { parts, .. } => {
+                for expr in parts.iter().filter_map(InterpPart::expr) {
+                    expr.lint(diagnostics);
+                }
+            }
+            Expr::Do {
+                statements, result, ..
+            } => desugar_do(statements, result).lint(diagnostics),
+            Expr::Block {
+                statements, result, ..
+            } => {
+                for (index, statement) in statements.iter().enumerate() {
+                    if let Some(binding) = statement.binding() {
+                        let rest = &statements[index + 1..];
+                        if !binding.name.starts_with('_')
+                            && !rest
+                                .iter()
+                                .any(|later| later.value().mentions(binding.name))
+                            && !result.mentions(binding.name)
+                        {
+                            diagnostics.warning(ParseError::warning(
+                                ErrorCode::UnusedBinding,
+                                *binding.get_location(),
+                                format!("unused let binding `{}`", binding.name),
+                            ));
+                        }
+                    }
+                    statement.value().lint(diagnostics);
+                }
+                result.lint(diagnostics);
+            }
+            Expr::Hole { location, name } => diagnostics.warning(ParseError::warning(
+                ErrorCode::Hole,
+                *location,
+                format!("found hole `?{}`", name.unwrap_or("")),
+            )),
+            Expr::Match {
+                subject,
+                pattern_exprs,
+                ..
+            } => {
+                subject.lint(diagnostics);
+                for pattern_expr in pattern_exprs {
+                    pattern_expr.predicate.lint(diagnostics);
+                    if let Some(guard) = &pattern_expr.guard {
+                        guard.lint(diagnostics);
+                    }
+                    pattern_expr.expr.lint(diagnostics);
+                }
+            }
+            Expr::Callsite {
+                function,
+                arguments,
+                ..
+            } => {
+                function.lint(diagnostics);
+                for arg in arguments {
+                    arg.lint(diagnostics);
+                }
+            }
+            Expr::Range {
+                start, step, end, ..
+            } => {
+                for bound in [start, step, end].into_iter().flatten() {
+                    bound.lint(diagnostics);
+                }
+            }
+            Expr::TupleCtor { dims, .. } => {
+                for dim in dims {
+                    dim.lint(diagnostics);
+                }
+            }
+            Expr::List { items, .. } => {
+                for item in items {
+                    item.lint(diagnostics);
+                }
+            }
+            Expr::Comprehension {
+                output,
+                generators,
+                guards,
+                ..
+            } => {
+                output.lint(diagnostics);
+                for generator in generators {
+                    generator.pattern.lint(diagnostics);
+                    generator.source.lint(diagnostics);
+                }
+                for (_, guard) in guards {
+                    guard.lint(diagnostics);
+                }
+            }
+            Expr::Record { fields, .. } => {
+                for (_, value) in fields {
+                    value.lint(diagnostics);
+                }
+            }
+            Expr::FieldAccess { subject, .. } => subject.lint(diagnostics),
+            Expr::BinaryOp { lhs, rhs, .. } => {
+                lhs.lint(diagnostics);
+                rhs.lint(diagnostics);
+            }
+            Expr::UnaryOp { operand, .. } => operand.lint(diagnostics),
+        }
+    }
 }
 
 impl<'a> HasLocation<'a> for Expr<'a> {
@@ -96,409 +763,6244 @@ impl<'a> HasLocation<'a> for Expr<'a> {
         match self {
             Expr::Lambda {
                 location,
-                param_names: _,
+                params: _,
                 body: _,
-            } => &location,
+            } => location,
             Expr::Let {
                 location,
                 binding: _,
                 value: _,
                 body: _,
             } => location,
+            Expr::LetRec { location, .. } => location,
+            Expr::LetPattern { location, .. } => location,
+            Expr::If { location, .. } => location,
             Expr::LiteralInteger { location, value: _ } => location,
             Expr::LiteralFloat { location, value: _ } => location,
             Expr::LiteralString { location, value: _ } => location,
+            Expr::Interp { location, .. } => location,
+            Expr::Do { location, .. } => location,
+            Expr::Block { location, .. } => location,
+            Expr::LiteralChar { location, value: _ } => location,
+            Expr::LiteralBool { location, value: _ } => location,
             Expr::Symbol { id } => id.get_location(),
+            Expr::Path { location, .. } => location,
+            Expr::Hole { location, .. } => location,
+            Expr::Range { location, .. } => location,
             Expr::Match {
                 location,
                 subject: _,
                 pattern_exprs: _,
             } => location,
             Expr::Callsite {
-                function,
+                span,
+                function: _,
                 arguments: _,
-            } => function.get_location(),
-            Expr::TupleCtor { location, dims: _ } => location,
+            } => &span.start,
+            Expr::TupleCtor { span, dims: _ } => &span.start,
+            Expr::List { span, .. } | Expr::Comprehension { span, .. } => &span.start,
+            Expr::Record {
+                location,
+                fields: _,
+            } => location,
+            Expr::FieldAccess { subject, field: _ } => subject.get_location(),
+            Expr::BinaryOp { op: _, lhs, rhs: _ } => lhs.get_location(),
+            Expr::UnaryOp { op, operand: _ } => op.get_location(),
         }
     }
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub struct Decl<'a> {
-    id: Identifier<'a>,
-    predicates: Vec<Box<Predicate<'a>>>,
-    body: Expr<'a>,
+fn sexpr<I>(head: &str, children: I) -> String
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut out = format!("({head}");
+    for child in children {
+        out.push(' ');
+        out.push_str(&child);
+    }
+    out.push(')');
+    out
 }
 
-impl<'a> HasLocation<'a> for Decl<'a> {
-    fn get_location(&self) -> &Location<'a> {
-        self.id.get_location()
+fn predicate_to_sexpr(predicate: &Predicate) -> String {
+    match predicate {
+        Predicate::Irrefutable(id) => sexpr("pat-bind", [id.name.to_string()]),
+        Predicate::Integer { value, .. } => sexpr("pat-int", [value.to_string()]),
+        Predicate::Float { value, .. } => sexpr("pat-float", [format!("{value:?}")]),
+        Predicate::String { value, .. } => sexpr("pat-string", [value.clone()]),
+        Predicate::Char { value, .. } => sexpr("pat-char", [char_source(*value)]),
+        Predicate::Bool { value, .. } => sexpr("pat-bool", [value.to_string()]),
+        Predicate::Ctor { ctor_id, dims } => sexpr(
+            "pat-ctor",
+            std::iter::once(ctor_id.name.to_string())
+                .chain(dims.iter().map(|dim| predicate_to_sexpr(dim))),
+        ),
+        Predicate::Tuple { dims, .. } => {
+            sexpr("pat-tuple", dims.iter().map(|dim| predicate_to_sexpr(dim)))
+        }
+        Predicate::List { dims, .. } => {
+            sexpr("pat-list", dims.iter().map(|dim| predicate_to_sexpr(dim)))
+        }
+        Predicate::Cons { head, tail } => sexpr(
+            "pat-cons",
+            [predicate_to_sexpr(head), predicate_to_sexpr(tail)],
+        ),
+        Predicate::As { binding, pattern } => sexpr(
+            "pat-as",
+            [binding.name.to_string(), predicate_to_sexpr(pattern)],
+        ),
+        Predicate::Or { alternatives, .. } => sexpr(
+            "pat-or",
+            alternatives
+                .iter()
+                .map(|alternative| predicate_to_sexpr(alternative)),
+        ),
+        Predicate::Record { fields, rest, .. } => sexpr(
+            "pat-record",
+            fields
+                .iter()
+                .map(|(field, pattern)| {
+                    sexpr(
+                        "field",
+                        std::iter::once(field.name.to_string())
+                            .chain(pattern.iter().map(|pattern| predicate_to_sexpr(pattern))),
+                    )
+                })
+                .chain(rest.then(|| "..".to_string())),
+        ),
     }
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
-pub enum ErrorLevel {
-    Info,
-    Warning,
-    Error,
+fn statement_sexpr(statement: &DoStatement) -> String {
+    match statement {
+        DoStatement::Expr(expr) => to_sexpr(expr),
+        DoStatement::Let { binding, value } => {
+            sexpr("let", [binding.name.to_string(), to_sexpr(value)])
+        }
+        DoStatement::Bind { binding, value } => {
+            sexpr("bind", [binding.name.to_string(), to_sexpr(value)])
+        }
+    }
 }
 
-impl std::fmt::Display for ErrorLevel {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                ErrorLevel::Info => "info",
-                ErrorLevel::Warning => "warning",
-                ErrorLevel::Error => "error",
-            }
-        )
+/// Render `expr` as a Lisp-style s-expression, such as `(callsite (symbol f) (lit-int 1))`.
+/// Locations are omitted, which makes this handy for golden tests.
+pub fn to_sexpr(expr: &Expr) -> String {
+    match expr {
+        Expr::Lambda { params, body, .. } => {
+            // Plain parameters are written as just their names.
+            let params: Vec<String> = params
+                .iter()
+                .map(|param| match param.as_ref() {
+                    Predicate::Irrefutable(id) => id.name.to_string(),
+                    param => predicate_to_sexpr(param),
+                })
+                .collect();
+            sexpr(
+                "lambda",
+                [format!("({})", params.join(" ")), to_sexpr(body)],
+            )
+        }
+        Expr::Let {
+            binding,
+            value,
+            body,
+            ..
+        } => sexpr(
+            "let",
+            [binding.name.to_string(), to_sexpr(value), to_sexpr(body)],
+        ),
+        Expr::LetRec { bindings, body, .. } => sexpr(
+            "let-rec",
+            bindings
+                .iter()
+                .map(|(binding, value)| {
+                    sexpr("binding", [binding.name.to_string(), to_sexpr(value)])
+                })
+                .chain([to_sexpr(body)]),
+        ),
+        Expr::LetPattern {
+            pattern,
+            value,
+            body,
+            ..
+        } => sexpr(
+            "let",
+            [predicate_to_sexpr(pattern), to_sexpr(value), to_sexpr(body)],
+        ),
+        Expr::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => sexpr(
+            "if",
+            [
+                to_sexpr(condition),
+                to_sexpr(then_branch),
+                to_sexpr(else_branch),
+            ],
+        ),
+        Expr::LiteralInteger { value, .. } => sexpr("lit-int", [value.to_string()]),
+        Expr::LiteralFloat { value, .. } => sexpr("lit-float", [format!("{value:?}")]),
+        Expr::LiteralString { value, .. } => sexpr("lit-string", [format!("\"{value}\"")]),
+        Expr::Interp { parts, .. } => sexpr(
+            "interp",
+            parts.iter().map(|part| match part {
+                InterpPart::Literal(text) => format!("\"{text}\""),
+                InterpPart::Expr(expr) => to_sexpr(expr),
+            }),
+        ),
+        Expr::Do {
+            statements, result, ..
+        } => sexpr(
+            "do",
+            statements
+                .iter()
+                .map(statement_sexpr)
+                .chain([to_sexpr(result)]),
+        ),
+        Expr::Block {
+            statements, result, ..
+        } => sexpr(
+            "block",
+            statements
+                .iter()
+                .map(statement_sexpr)
+                .chain([to_sexpr(result)]),
+        ),
+        Expr::LiteralChar { value, .. } => sexpr("lit-char", [char_source(*value)]),
+        Expr::LiteralBool { value, .. } => sexpr("lit-bool", [value.to_string()]),
+        Expr::Symbol { id } => sexpr("symbol", [id.name.to_string()]),
+        Expr::Path { segments, .. } => sexpr("path", segments.iter().map(|id| id.name.to_string())),
+        Expr::Hole { name, .. } => sexpr("hole", name.map(str::to_string)),
+        Expr::Range {
+            start,
+            step,
+            end,
+            inclusive,
+            ..
+        } => {
+            let bound = |bound: &Option<Box<Expr>>| {
+                bound
+                    .as_ref()
+                    .map_or("_".to_string(), |bound| to_sexpr(bound))
+            };
+            sexpr(
+                if *inclusive {
+                    "range-inclusive"
+                } else {
+                    "range"
+                },
+                std::iter::once(bound(start))
+                    .chain(step.as_ref().map(|step| sexpr("step", [to_sexpr(step)])))
+                    .chain(std::iter::once(bound(end))),
+            )
+        }
+        Expr::Match {
+            subject,
+            pattern_exprs,
+            ..
+        } => sexpr(
+            "match",
+            std::iter::once(to_sexpr(subject)).chain(pattern_exprs.iter().map(|arm| {
+                sexpr(
+                    "arm",
+                    std::iter::once(predicate_to_sexpr(&arm.predicate))
+                        .chain(
+                            arm.guard
+                                .iter()
+                                .map(|guard| sexpr("guard", [to_sexpr(guard)])),
+                        )
+                        .chain(std::iter::once(to_sexpr(&arm.expr))),
+                )
+            })),
+        ),
+        Expr::Callsite {
+            function,
+            arguments,
+            ..
+        } => sexpr(
+            "callsite",
+            std::iter::once(to_sexpr(function))
+                .chain(arguments.iter().map(|argument| to_sexpr(argument))),
+        ),
+        Expr::TupleCtor { dims, .. } => sexpr("tuple", dims.iter().map(|dim| to_sexpr(dim))),
+        Expr::List { items, .. } => sexpr("list", items.iter().map(|item| to_sexpr(item))),
+        Expr::Comprehension {
+            output,
+            generators,
+            guards,
+            ..
+        } => sexpr(
+            "comprehension",
+            std::iter::once(to_sexpr(output)).chain(qualifiers(
+                generators,
+                guards,
+                |generator| {
+                    sexpr(
+                        "generator",
+                        [
+                            predicate_to_sexpr(&generator.pattern),
+                            to_sexpr(&generator.source),
+                        ],
+                    )
+                },
+                |guard| sexpr("guard", [to_sexpr(guard)]),
+            )),
+        ),
+        Expr::Record { fields, .. } => sexpr(
+            "record",
+            fields
+                .iter()
+                .map(|(field, value)| sexpr("field", [field.name.to_string(), to_sexpr(value)])),
+        ),
+        Expr::FieldAccess { subject, field } => {
+            sexpr("field-access", [to_sexpr(subject), field.name.to_string()])
+        }
+        Expr::BinaryOp { op, lhs, rhs } => sexpr(
+            "binary-op",
+            [op.name.to_string(), to_sexpr(lhs), to_sexpr(rhs)],
+        ),
+        Expr::UnaryOp { op, operand } => {
+            sexpr("unary-op", [op.name.to_string(), to_sexpr(operand)])
+        }
     }
 }
 
-fn is_keyword(name: &str) -> bool {
-    name == "if"
-        || name == "then"
-        || name == "else"
-        || name == "do"
-        || name == "let"
-        || name == "in"
+// How tightly a printed expression binds, from loosest to tightest. Binary operators of
+// precedence p print at level p + 1.
+const LEVEL_OPEN: u8 = 0;
+const LEVEL_PREFIX: u8 = 11;
+const LEVEL_APPLICATION: u8 = 12;
+const LEVEL_TERM: u8 = 13;
+
+fn path_source(segments: &[Identifier]) -> String {
+    let names: Vec<&str> = segments.iter().map(|id| id.name).collect();
+    names.join("::")
 }
 
-fn maybe_id<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Identifier<'a>>> {
-    match lexer.peek() {
-        None => {
-            lexer.advance_mut()?;
-            Ok(None)
-        }
-        Some(Token {
-            location,
-            lexeme: Lexeme::Identifier(name),
-        }) => {
-            /* check for keywords */
-            if is_keyword(name) {
-                Ok(None)
-            } else {
-                lexer.advance_mut()?;
-                Ok(Some(Identifier::new(name, location)))
-            }
-        }
-        Some(_) => Ok(None),
+/// The builtin name of a boolean literal.
+fn bool_source(value: bool) -> &'static str {
+    if value {
+        "True"
+    } else {
+        "False"
     }
 }
 
-fn parse_tuple_predicate<'a>(
-    location: Location<'a>,
-    lexer: &mut Lexer<'a>,
-) -> ParseResult<'a, Option<Predicate<'a>>> {
-    let mut predicates: Vec<Box<Predicate>> = Vec::new();
-    loop {
-        match parse_predicate(lexer)? {
-            Some(predicate) => {
-                if lexer.peek_matches(Lexeme::Comma) {
-                    println!("AA {:?}", predicate);
-                    predicates.push(Box::new(predicate));
-                    lexer.advance_mut()?;
-                } else {
-                    lexer.chomp(Lexeme::RParen)?;
-                    if predicates.len() == 0 {
-                        return Ok(Some(predicate));
-                    } else if predicates.len() >= 1 {
-                        predicates.push(Box::new(predicate));
+/// The boolean literal `name` stands for, if any.
+fn bool_literal(name: &str) -> Option<bool> {
+    match name {
+        "True" => Some(true),
+        "False" => Some(false),
+        _ => None,
+    }
+}
+
+/// A char literal which lexes back to `value`, escaping quotes, backslashes and control
+/// characters.
+fn char_source(value: char) -> String {
+    match value {
+        '\n' => r"'\n'".to_string(),
+        '\t' => r"'\t'".to_string(),
+        '\r' => r"'\r'".to_string(),
+        '\0' => r"'\0'".to_string(),
+        '\\' => r"'\\'".to_string(),
+        '\'' => r"'\''".to_string(),
+        value if value.is_control() => format!("'\\u{{{:x}}}'", value as u32),
+        value => format!("'{value}'"),
+    }
+}
+
+fn float_source(value: f64) -> String {
+    let source = value.to_string();
+    if source.contains('.') {
+        source
+    } else {
+        format!("{source}.0")
+    }
+}
+
+// Predicates print at one of these levels: or-patterns, cons patterns, constructor applications
+// and atoms.
+const PATTERN_OR: u8 = 0;
+const PATTERN_CONS: u8 = 1;
+const PATTERN_CTOR: u8 = 2;
+const PATTERN_ATOM: u8 = 3;
+
+fn predicate_level(predicate: &Predicate) -> u8 {
+    match predicate {
+        Predicate::Or { .. } => PATTERN_OR,
+        Predicate::Cons { .. } => PATTERN_CONS,
+        // A constructor without arguments is an atom, like `Nothing` in `f Nothing y`.
+        Predicate::Ctor { dims, .. } if !dims.is_empty() => PATTERN_CTOR,
+        _ => PATTERN_ATOM,
+    }
+}
+
+fn predicate_source(predicate: &Predicate, min_level: u8) -> String {
+    if predicate_level(predicate) < min_level {
+        return format!("({})", predicate_source(predicate, PATTERN_OR));
+    }
+    let join = |dims: &[Box<Predicate>]| -> String {
+        let dims: Vec<String> = dims
+            .iter()
+            .map(|dim| predicate_source(dim, PATTERN_OR))
+            .collect();
+        dims.join(", ")
+    };
+    match predicate {
+        Predicate::Irrefutable(id) => id.name.to_string(),
+        Predicate::Integer { value, .. } => value.to_string(),
+        Predicate::Float { value, .. } => float_source(*value),
+        Predicate::String { value, .. } => value.clone(),
+        Predicate::Char { value, .. } => char_source(*value),
+        Predicate::Bool { value, .. } => bool_source(*value).to_string(),
+        Predicate::Ctor { ctor_id, dims } => {
+            let mut source = ctor_id.name.to_string();
+            for dim in dims {
+                source.push(' ');
+                source.push_str(&predicate_source(dim, PATTERN_ATOM));
+            }
+            source
+        }
+        Predicate::Tuple { dims, .. } if dims.len() == 1 => {
+            format!("({},)", predicate_source(&dims[0], PATTERN_OR))
+        }
+        Predicate::Tuple { dims, .. } => format!("({})", join(dims)),
+        Predicate::List { dims, .. } => format!("[{}]", join(dims)),
+        Predicate::Cons { head, tail } => format!(
+            "{} : {}",
+            predicate_source(head, PATTERN_CTOR),
+            predicate_source(tail, PATTERN_CONS)
+        ),
+        Predicate::As { binding, pattern } => {
+            format!(
+                "{}@{}",
+                binding.name,
+                predicate_source(pattern, PATTERN_ATOM)
+            )
+        }
+        Predicate::Or { alternatives, .. } => {
+            let alternatives: Vec<String> = alternatives
+                .iter()
+                .map(|alternative| predicate_source(alternative, PATTERN_CONS))
+                .collect();
+            alternatives.join(" | ")
+        }
+        Predicate::Record { fields, rest, .. } => {
+            let mut fields: Vec<String> = fields
+                .iter()
+                .map(|(field, pattern)| match pattern {
+                    Some(pattern) => {
+                        format!("{} = {}", field.name, predicate_source(pattern, PATTERN_OR))
                     }
-                    return Ok(Some(Predicate::Tuple {
-                        location,
-                        dims: predicates,
-                    }));
-                };
+                    None => field.name.to_string(),
+                })
+                .collect();
+            if *rest {
+                fields.push("..".to_string());
             }
-            None => {
-                break;
+            if fields.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", fields.join(", "))
             }
         }
     }
-    lexer.chomp(Lexeme::RParen)?;
-    Ok(Some(Predicate::Tuple {
-        location,
-        dims: predicates,
-    }))
 }
 
-fn parse_predicate<'a>(lexer: &'a mut Lexer) -> ParseResult<'a, Option<Predicate<'a>>> {
-    match lexer.peek() {
-        Some(token) => match token.lexeme {
-            Lexeme::Signed(value) => {
-                lexer.advance_mut()?;
-                Ok(Some(Predicate::Integer {
-                    location: token.location,
-                    value,
-                }))
+/// The qualifiers of a comprehension in source order, each generator given by `generator` and
+/// each guard by `guard`.
+fn qualifiers<'e, 'a, T>(
+    generators: &'e [Generator<'a>],
+    guards: &'e [(usize, Expr<'a>)],
+    generator: impl Fn(&'e Generator<'a>) -> T,
+    guard: impl Fn(&'e Expr<'a>) -> T,
+) -> Vec<T> {
+    let mut qualifiers = Vec::new();
+    let mut guards = guards.iter().peekable();
+    for index in 0..=generators.len() {
+        while let Some((_, condition)) = guards.next_if(|(after, _)| *after == index) {
+            qualifiers.push(guard(condition));
+        }
+        qualifiers.extend(generators.get(index).map(&generator));
+    }
+    qualifiers
+}
+
+fn expr_level(expr: &Expr, fixities: &FixityTable) -> u8 {
+    match expr {
+        Expr::Lambda { .. } if section_parts(expr).is_some() => LEVEL_TERM,
+        Expr::Lambda { .. }
+        | Expr::Let { .. }
+        | Expr::LetRec { .. }
+        | Expr::LetPattern { .. }
+        | Expr::If { .. }
+        | Expr::Match { .. }
+        | Expr::Range { step: None, .. } => LEVEL_OPEN,
+        Expr::BinaryOp { op, .. } => match fixities.get(op.name) {
+            Some(fixity) => fixity.precedence + 1,
+            None => LEVEL_OPEN,
+        },
+        Expr::UnaryOp { operand, .. } if expr_level(operand, fixities) < LEVEL_TERM => LEVEL_PREFIX,
+        Expr::Callsite { .. } => LEVEL_APPLICATION,
+        _ => LEVEL_TERM,
+    }
+}
+
+/// Double the braces in the text of a string literal, since a single one would begin an embedded
+/// expression.
+fn escape_braces(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+fn statement_source(statement: &DoStatement, fixities: &FixityTable) -> String {
+    match statement {
+        DoStatement::Expr(expr) => expr_source(expr, LEVEL_OPEN, fixities),
+        DoStatement::Let { binding, value } => format!(
+            "let {} = {}",
+            binding.name,
+            expr_source(value, LEVEL_OPEN, fixities)
+        ),
+        DoStatement::Bind { binding, value } => format!(
+            "{} <- {}",
+            binding.name,
+            expr_source(value, LEVEL_OPEN, fixities)
+        ),
+    }
+}
+
+/// Print `expr` as source which parses back to the same tree, parenthesizing it if it binds
+/// more loosely than `min_level`.
+fn expr_source(expr: &Expr, min_level: u8, fixities: &FixityTable) -> String {
+    if expr_level(expr, fixities) < min_level {
+        return format!("({})", expr_source(expr, LEVEL_OPEN, fixities));
+    }
+    if let Some((op, operand, operand_is_lhs)) = section_parts(expr) {
+        let operand = expr_source(operand, LEVEL_OPEN, fixities);
+        return if operand_is_lhs {
+            format!("({} {})", operand, op.name)
+        } else {
+            format!("({} {})", op.name, operand)
+        };
+    }
+    match expr {
+        Expr::Lambda { params, body, .. } => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|param| predicate_source(param, PATTERN_ATOM))
+                .collect();
+            format!(
+                "\\{} -> {}",
+                params.join(" "),
+                expr_source(body, LEVEL_OPEN, fixities)
+            )
+        }
+        Expr::Let {
+            binding,
+            value,
+            body,
+            ..
+        } => format!(
+            "let {} = {} in {}",
+            binding.name,
+            expr_source(value, LEVEL_OPEN, fixities),
+            expr_source(body, LEVEL_OPEN, fixities)
+        ),
+        Expr::LetRec { bindings, body, .. } => {
+            let bindings: Vec<String> = bindings
+                .iter()
+                .map(|(binding, value)| {
+                    format!(
+                        "{} = {}",
+                        binding.name,
+                        expr_source(value, LEVEL_OPEN, fixities)
+                    )
+                })
+                .collect();
+            format!(
+                "let rec {} in {}",
+                bindings.join("; "),
+                expr_source(body, LEVEL_OPEN, fixities)
+            )
+        }
+        Expr::LetPattern {
+            pattern,
+            value,
+            body,
+            ..
+        } => format!(
+            "let {} = {} in {}",
+            predicate_source(pattern, PATTERN_CTOR),
+            expr_source(value, LEVEL_OPEN, fixities),
+            expr_source(body, LEVEL_OPEN, fixities)
+        ),
+        Expr::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => format!(
+            "if {} then {} else {}",
+            expr_source(condition, LEVEL_OPEN, fixities),
+            expr_source(then_branch, LEVEL_OPEN, fixities),
+            expr_source(else_branch, LEVEL_OPEN, fixities)
+        ),
+        Expr::LiteralInteger { value, .. } => value.to_string(),
+        Expr::LiteralFloat { value, .. } => float_source(*value),
+        Expr::LiteralString { value, .. } => format!("\"{}\"", escape_braces(value)),
+        Expr::Do {
+            statements, result, ..
+        } => {
+            let statements: Vec<String> = statements
+                .iter()
+                .map(|statement| statement_source(statement, fixities))
+                .chain([expr_source(result, LEVEL_OPEN, fixities)])
+                .collect();
+            format!("do {{ {} }}", statements.join("; "))
+        }
+        Expr::Block {
+            statements, result, ..
+        } => {
+            let statements: Vec<String> = statements
+                .iter()
+                .map(|statement| statement_source(statement, fixities))
+                .chain([expr_source(result, LEVEL_OPEN, fixities)])
+                .collect();
+            format!("{{ {} }}", statements.join("; "))
+        }
+        Expr::Interp { parts, .. } => {
+            let mut source = String::from("\"");
+            for part in parts {
+                match part {
+                    InterpPart::Literal(text) => source.push_str(&escape_braces(text)),
+                    InterpPart::Expr(expr) => {
+                        source.push('{');
+                        source.push_str(&expr_source(expr, LEVEL_OPEN, fixities));
+                        source.push('}');
+                    }
+                }
             }
-            Lexeme::QuotedString(value) => {
-                lexer.advance_mut()?;
-                Ok(Some(Predicate::String {
-                    location: token.location,
-                    value: value.to_string(),
-                }))
+            source.push('"');
+            source
+        }
+        Expr::LiteralChar { value, .. } => char_source(*value),
+        Expr::LiteralBool { value, .. } => bool_source(*value).to_string(),
+        Expr::Symbol { id } => name_source(id.name),
+        Expr::Path { segments, .. } => path_source(segments),
+        Expr::Hole { name, .. } => format!("?{}", name.unwrap_or("")),
+        Expr::Range {
+            start,
+            step,
+            end,
+            inclusive,
+            ..
+        } => {
+            let bound = |bound: &Option<Box<Expr>>| {
+                bound.as_ref().map_or(String::new(), |bound| {
+                    expr_source(bound, LEVEL_OPEN + 1, fixities)
+                })
+            };
+            let range = format!(
+                "{}{}{}",
+                bound(step),
+                if *inclusive { "..=" } else { ".." },
+                bound(end)
+            );
+            match step {
+                Some(_) => format!("[{}, {}]", bound(start), range),
+                None => format!("{}{}", bound(start), range),
             }
-            Lexeme::Identifier(name) => {
-                // Ctor
-                if name.chars().nth(0).unwrap().is_uppercase() {
-                    let ctor_id = Identifier::new(name, token.location);
-                    lexer.advance_mut()?;
-                    let predicates = parse_predicates(lexer)?;
-                    Ok(Some(Predicate::Ctor {
-                        ctor_id,
-                        dims: predicates,
-                    }))
+        }
+        Expr::Match {
+            subject,
+            pattern_exprs,
+            ..
+        } => {
+            // An open expression would run on into the arms that follow it.
+            let mut source = format!("match {}", expr_source(subject, LEVEL_OPEN + 1, fixities));
+            for (i, arm) in pattern_exprs.iter().enumerate() {
+                source.push(' ');
+                source.push_str(&predicate_source(&arm.predicate, PATTERN_OR));
+                if let Some(guard) = &arm.guard {
+                    source.push_str(" if ");
+                    source.push_str(&expr_source(guard, LEVEL_OPEN + 1, fixities));
+                }
+                let is_last = i + 1 == pattern_exprs.len();
+                source.push_str(" => ");
+                source.push_str(&expr_source(
+                    &arm.expr,
+                    if is_last { LEVEL_OPEN } else { LEVEL_OPEN + 1 },
+                    fixities,
+                ));
+            }
+            source
+        }
+        Expr::Callsite {
+            function,
+            arguments,
+            ..
+        } => {
+            let is_string =
+                |expr: &Expr| matches!(expr, Expr::LiteralString { .. } | Expr::Interp { .. });
+            let mut source = expr_source(function, LEVEL_TERM, fixities);
+            let mut previous = function.as_ref();
+            for argument in arguments {
+                source.push(' ');
+                // Adjacent string literals would be read back as one string.
+                if is_string(previous) && is_string(argument) {
+                    source.push_str(&format!(
+                        "({})",
+                        expr_source(argument, LEVEL_OPEN, fixities)
+                    ));
                 } else {
-                    let loc = lexer.location.clone();
-                    lexer.advance_mut()?;
-                    Ok(Some(Predicate::Irrefutable(Identifier::new(name, loc))))
+                    source.push_str(&expr_source(argument, LEVEL_TERM, fixities));
                 }
+                previous = argument;
             }
-            Lexeme::LParen => {
-                lexer.advance_mut()?;
-                parse_tuple_predicate(token.location, lexer)
+            source
+        }
+        Expr::TupleCtor { dims, .. } if dims.len() == 1 => {
+            format!("({},)", expr_source(&dims[0], LEVEL_OPEN, fixities))
+        }
+        Expr::TupleCtor { dims, .. } => {
+            let dims: Vec<String> = dims
+                .iter()
+                .map(|dim| expr_source(dim, LEVEL_OPEN, fixities))
+                .collect();
+            format!("({})", dims.join(", "))
+        }
+        Expr::List { items, .. } => {
+            // A range on its own in brackets would be a list range instead.
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| match item.as_ref() {
+                    Expr::Range { step: None, .. } => expr_source(item, LEVEL_OPEN + 1, fixities),
+                    _ => expr_source(item, LEVEL_OPEN, fixities),
+                })
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        Expr::Comprehension {
+            output,
+            generators,
+            guards,
+            ..
+        } => format!(
+            "[{} | {}]",
+            expr_source(output, LEVEL_OPEN, fixities),
+            qualifiers(
+                generators,
+                guards,
+                |generator| format!(
+                    "{} <- {}",
+                    predicate_source(&generator.pattern, PATTERN_CTOR),
+                    expr_source(&generator.source, LEVEL_OPEN, fixities)
+                ),
+                |guard| expr_source(guard, LEVEL_OPEN, fixities),
+            )
+            .join(", ")
+        ),
+        Expr::Record { fields, .. } if fields.is_empty() => "{}".to_string(),
+        Expr::Record { fields, .. } => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(field, value)| {
+                    format!(
+                        "{} = {}",
+                        field.name,
+                        expr_source(value, LEVEL_OPEN, fixities)
+                    )
+                })
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        Expr::FieldAccess { subject, field } => {
+            format!(
+                "{}.{}",
+                expr_source(subject, LEVEL_TERM, fixities),
+                field.name
+            )
+        }
+        Expr::BinaryOp { op, lhs, rhs } => {
+            let fixity = fixities
+                .get(op.name)
+                .unwrap_or(Fixity::new(Associativity::None, 0));
+            let level = fixity.precedence + 1;
+            let (lhs_level, rhs_level) = match fixity.associativity {
+                Associativity::Left => (level, level + 1),
+                Associativity::Right => (level + 1, level),
+                Associativity::None => (level + 1, level + 1),
+            };
+            format!(
+                "{} {} {}",
+                expr_source(lhs, lhs_level, fixities),
+                op.name,
+                expr_source(rhs, rhs_level, fixities)
+            )
+        }
+        Expr::UnaryOp { op, operand } => {
+            if expr_level(operand, fixities) < LEVEL_TERM {
+                format!(
+                    "{} {}",
+                    op.name,
+                    expr_source(operand, LEVEL_APPLICATION, fixities)
+                )
+            } else {
+                format!("{}{}", op.name, expr_source(operand, LEVEL_TERM, fixities))
             }
-            _ => Ok(None),
-        },
-        None => {
-            return Err(ParseError::error(
-                lexer.location,
-                "missing token where a predicate was expected?",
-            ))
         }
     }
 }
 
-fn parse_predicates<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Vec<Box<Predicate<'a>>>> {
-    let mut predicates = Vec::new();
-    loop {
-        match parse_predicate(lexer)? {
-            None => return Ok(predicates),
-            Some(predicate) => {
-                println!(
-                    "{}: found predicate {:?}",
-                    predicate.get_location(),
-                    predicate
-                );
-                predicates.push(Box::new(predicate));
+/// Print `decls` as mueve source, one per line, using only the parentheses needed for the
+/// output to parse back to the same trees under `fixities`, which should be the table of the
+/// module they came from. Operators without a fixity there are always parenthesized.
+pub fn pretty_print(decls: &[Decl], fixities: &FixityTable) -> String {
+    let mut source = String::new();
+    for decl in decls {
+        for attr in &decl.attrs {
+            source.push_str("#[");
+            source.push_str(attr.name.name);
+            for arg in &attr.args {
+                source.push(' ');
+                source.push_str(&expr_source(arg, LEVEL_TERM, fixities));
             }
+            source.push_str("]\n");
+        }
+        for clause in &decl.clauses {
+            source.push_str(&clause_source(decl, clause, fixities));
+            source.push('\n');
         }
     }
+    source
 }
 
-fn parse_identifier<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Identifier<'a>> {
-    match lexer.peek() {
-        Some(Token {
-            location,
-            lexeme: Lexeme::Identifier(name),
-        }) => {
-            lexer.advance_mut()?;
-            Ok(Identifier::new(name, location))
-        }
-        _ => Err(ParseError::error(
-            lexer.location,
-            "expected an identifier here",
-        )),
+/// One clause of `decl` on a single line. A `where` block is always braced, with its decls
+/// separated by semicolons.
+fn clause_source(decl: &Decl, clause: &Clause, fixities: &FixityTable) -> String {
+    let mut source = name_source(decl.id.name);
+    for predicate in &clause.predicates {
+        source.push(' ');
+        source.push_str(&predicate_source(predicate, PATTERN_ATOM));
+    }
+    source.push_str(" = ");
+    source.push_str(&expr_source(&clause.body, LEVEL_OPEN, fixities));
+    if !clause.where_decls.is_empty() {
+        let where_clauses: Vec<String> = clause
+            .where_decls
+            .iter()
+            .flat_map(|where_decl| {
+                where_decl
+                    .clauses
+                    .iter()
+                    .map(move |where_clause| clause_source(where_decl, where_clause, fixities))
+            })
+            .collect();
+        source.push_str(&format!(" where {{ {} }}", where_clauses.join("; ")));
     }
+    source
 }
 
-fn parse_match_expr<'a>(
-    _location: Location<'a>,
-    lexer: &mut Lexer<'a>,
-) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
-    let _binding_value = parse_callsite(lexer)?;
-    loop {
-        lexer.skip_semicolon()?;
-        match parse_predicate(lexer)? {
-            Some(_predicate) => {
-                lexer.chomp(Lexeme::Operator("=>"))?;
-                break;
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Decl<'a> {
+    pub(crate) id: Identifier<'a>,
+    /// The attributes written before the decl, in source order. Only top-level decls have any.
+    pub(crate) attrs: Vec<Attribute<'a>>,
+    /// One for each equation of the definition, in source order. Every clause has the same
+    /// number of parameters.
+    pub(crate) clauses: Vec<Clause<'a>>,
+}
+
+impl<'a> Decl<'a> {
+    pub fn id(&self) -> &Identifier<'a> {
+        &self.id
+    }
+
+    pub fn attrs(&self) -> &[Attribute<'a>] {
+        &self.attrs
+    }
+
+    pub fn clauses(&self) -> &[Clause<'a>] {
+        &self.clauses
+    }
+
+    /// Report warnings for unknown attributes and for the patterns and bodies of every clause,
+    /// including those of `where` decls.
+    pub(crate) fn lint(&self, diagnostics: &mut Diagnostics<'a>) {
+        for attr in &self.attrs {
+            if !KNOWN_ATTRIBUTES.contains(&attr.name.name) {
+                diagnostics.warning(ParseError::warning(
+                    ErrorCode::UnknownAttribute,
+                    *attr.name.get_location(),
+                    format!("unknown attribute `{}`", attr.name.name),
+                ));
             }
-            None => {
-                break;
+        }
+        for clause in &self.clauses {
+            for predicate in &clause.predicates {
+                predicate.lint(diagnostics);
+            }
+            clause.body.lint(diagnostics);
+            for where_decl in &clause.where_decls {
+                where_decl.lint(diagnostics);
             }
         }
     }
+}
+
+impl<'a> HasLocation<'a> for Decl<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        self.id.get_location()
+    }
+}
+
+/// The attributes the compiler understands. Others are accepted with a warning, so that tooling
+/// can define its own.
+const KNOWN_ATTRIBUTES: &[&str] = &["inline", "deprecated"];
+
+/// An attribute on a top-level decl, such as `#[inline]` or `#[deprecated "use foo2"]`.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Attribute<'a> {
+    /// Where the attribute's `#[` is.
+    location: Location<'a>,
+    pub(crate) name: Identifier<'a>,
+    /// The arguments after the name, if any. Each is a callsite term, such as a string literal.
+    pub(crate) args: Vec<Expr<'a>>,
+}
 
-    Ok(None)
+impl<'a> HasLocation<'a> for Attribute<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        &self.location
+    }
 }
 
-fn parse_let_expr<'a>(
+/// One equation of a definition, such as `len (x : xs) = add 1 (len xs)`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Clause<'a> {
+    /// Where the clause's copy of the definition's name is.
     location: Location<'a>,
-    lexer: &mut Lexer<'a>,
-) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
-    let binding_id = parse_identifier(&mut lexer)?;
-    lexer.chomp(Lexeme::Operator("="))?;
-    let binding_value = parse_callsite(&mut lexer)?;
-    lexer.chomp(Lexeme::Identifier("in"))?;
-    let in_body = parse_callsite(&mut lexer)?;
-    Ok(Some(
-        Expr::Let {
-            location: location,
-            binding: binding_id,
-            value: binding_value.into(),
-            body: in_body.into(),
+    pub(crate) predicates: Vec<Predicate<'a>>,
+    pub(crate) body: Expr<'a>,
+    /// Local definitions from the clause's `where` block. They can see the clause's parameters
+    /// and each other, and are in scope only within this clause.
+    pub(crate) where_decls: Vec<Decl<'a>>,
+}
+
+impl<'a> Clause<'a> {
+    pub fn predicates(&self) -> &[Predicate<'a>] {
+        &self.predicates
+    }
+
+    pub fn body(&self) -> &Expr<'a> {
+        &self.body
+    }
+
+    pub fn where_decls(&self) -> &[Decl<'a>] {
+        &self.where_decls
+    }
+}
+
+impl<'a> HasLocation<'a> for Clause<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        &self.location
+    }
+}
+
+/// A data type declaration, such as `data Maybe a = Nothing | Just a`.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct DataDecl<'a> {
+    location: Location<'a>,
+    name: Identifier<'a>,
+    type_params: Vec<Identifier<'a>>,
+    /// Each constructor, with the types of its positional arguments.
+    pub(crate) ctors: Vec<(Identifier<'a>, Vec<TypeExpr<'a>>)>,
+}
+
+impl<'a> DataDecl<'a> {
+    pub fn name(&self) -> &Identifier<'a> {
+        &self.name
+    }
+
+    pub fn type_params(&self) -> &[Identifier<'a>] {
+        &self.type_params
+    }
+
+    pub fn ctors(&self) -> &[(Identifier<'a>, Vec<TypeExpr<'a>>)] {
+        &self.ctors
+    }
+}
+
+impl<'a> HasLocation<'a> for DataDecl<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        &self.location
+    }
+}
+
+/// A top-level item of a file.
+#[derive(Debug, Clone)]
+pub enum Item<'a> {
+    Decl(Decl<'a>),
+    Data(DataDecl<'a>),
+    Import(Import<'a>),
+    Fixity(FixityDecl<'a>),
+}
+
+impl<'a> HasLocation<'a> for Item<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        match self {
+            Item::Decl(decl) => decl.get_location(),
+            Item::Data(data_decl) => data_decl.get_location(),
+            Item::Import(import) => import.get_location(),
+            Item::Fixity(fixity_decl) => fixity_decl.get_location(),
         }
-        .into(),
-    ))
+    }
 }
 
-fn parse_callsite_term<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
-    match lexer.peek() {
-        None => {
-            println!("AABAB");
-            lexer.advance_mut()?;
-            Ok(None)
+/// A pass over the syntax tree. Each method's default recurses into the node's children, so an
+/// implementation overrides only the hooks it cares about, calling the matching walk function
+/// if it still wants to descend.
+pub trait Visitor<'a> {
+    fn visit_decl(&mut self, decl: &Decl<'a>) {
+        walk_decl(self, decl)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr<'a>) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_predicate(&mut self, predicate: &Predicate<'a>) {
+        walk_predicate(self, predicate)
+    }
+}
+
+/// Visit the parameters of each clause of `decl`, then that clause's body, then its `where`
+/// decls.
+pub fn walk_decl<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, decl: &Decl<'a>) {
+    for clause in &decl.clauses {
+        for predicate in &clause.predicates {
+            visitor.visit_predicate(predicate);
         }
-        Some(Token { location, lexeme }) => match lexeme {
-            // A symbol reference.
-            Lexeme::Identifier(name) => {
-                println!("KKJDKF");
-                if name == "let" {
-                    let loc = lexer.advance_mut()?;
-                    parse_let_expr(loc, lexer)
-                } else if name == "match" {
-                    let location = lexer.advance_mut()?;
-                    parse_match_expr(location, lexer)
-                } else if is_keyword(name) {
-                    println!("FIXME: not impl keyword? '{}'", name);
-                    Ok(None)
-                } else {
-                    lexer.advance()?;
-                    Ok(Some(
-                        Expr::Symbol {
-                            id: Identifier::new(name, location),
-                        }
-                        .into(),
-                    ))
+        visitor.visit_expr(&clause.body);
+        for where_decl in &clause.where_decls {
+            visitor.visit_decl(where_decl);
+        }
+    }
+}
+
+/// Visit each direct child of `expr`, in source order.
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &Expr<'a>) {
+    match expr {
+        Expr::Lambda { body, .. } => visitor.visit_expr(body),
+        Expr::Let { value, body, .. } => {
+            visitor.visit_expr(value);
+            visitor.visit_expr(body);
+        }
+        Expr::LetRec { bindings, body, .. } => {
+            for (_, value) in bindings {
+                visitor.visit_expr(value);
+            }
+            visitor.visit_expr(body);
+        }
+        Expr::LetPattern {
+            pattern,
+            value,
+            body,
+            ..
+        } => {
+            visitor.visit_predicate(pattern);
+            visitor.visit_expr(value);
+            visitor.visit_expr(body);
+        }
+        Expr::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(then_branch);
+            visitor.visit_expr(else_branch);
+        }
+        Expr::LiteralInteger { .. }
+        | Expr::LiteralFloat { .. }
+        | Expr::LiteralString { .. }
+        | Expr::LiteralChar { .. }
+        | Expr::LiteralBool { .. }
+        | Expr::Symbol { .. }
+        | Expr::Path { .. }
+        | Expr::Hole { .. } => {}
+        Expr::Interp { parts, .. } => {
+            for expr in parts.iter().filter_map(InterpPart::expr) {
+                visitor.visit_expr(expr);
+            }
+        }
+        Expr::Do {
+            statements, result, ..
+        }
+        | Expr::Block {
+            statements, result, ..
+        } => {
+            for statement in statements {
+                visitor.visit_expr(statement.value());
+            }
+            visitor.visit_expr(result);
+        }
+        Expr::Match {
+            subject,
+            pattern_exprs,
+            ..
+        } => {
+            visitor.visit_expr(subject);
+            for arm in pattern_exprs {
+                visitor.visit_predicate(&arm.predicate);
+                if let Some(guard) = &arm.guard {
+                    visitor.visit_expr(guard);
                 }
+                visitor.visit_expr(&arm.expr);
             }
-            Lexeme::Semicolon => {
-                lexer.advance_mut()?;
-                Ok(None)
+        }
+        Expr::Callsite {
+            function,
+            arguments,
+            ..
+        } => {
+            visitor.visit_expr(function);
+            for argument in arguments {
+                visitor.visit_expr(argument);
             }
-            Lexeme::Operator("=") => Ok(None),
-            Lexeme::LParen => {
-                lexer.advance()?;
-                let expr = parse_callsite(&mut lexer)?;
-                lexer.chomp(Lexeme::RParen)?;
-                Ok(Some(expr.into()))
+        }
+        Expr::Range {
+            start, step, end, ..
+        } => {
+            for bound in [start, step, end].into_iter().flatten() {
+                visitor.visit_expr(bound);
             }
-            Lexeme::RParen => Ok(None),
-            Lexeme::Operator(name) => {
-                lexer.advance()?;
-                Ok(Some(
-                    Expr::Symbol {
-                        id: Identifier::new(name, location),
-                    }
-                    .into(),
-                ))
+        }
+        Expr::TupleCtor { dims, .. } => {
+            for dim in dims {
+                visitor.visit_expr(dim);
             }
-            Lexeme::QuotedString(value) => {
-                lexer.advance()?;
-                Ok(Some(
-                    Expr::LiteralString {
-                        location,
-                        value: value.into(),
-                    }
-                    .into(),
-                ))
+        }
+        Expr::List { items, .. } => {
+            for item in items {
+                visitor.visit_expr(item);
             }
-            Lexeme::Signed(value) => {
-                lexer.advance()?;
-                Ok(Some(Expr::LiteralInteger { location, value }.into()))
+        }
+        Expr::Comprehension {
+            output,
+            generators,
+            guards,
+            ..
+        } => {
+            visitor.visit_expr(output);
+            for generator in generators {
+                visitor.visit_predicate(&generator.pattern);
+                visitor.visit_expr(&generator.source);
             }
-            Lexeme::Float(value) => {
-                lexer.advance()?;
-                Ok(Some(Expr::LiteralFloat { location, value }.into()))
+            for (_, guard) in guards {
+                visitor.visit_expr(guard);
             }
-            lexeme => {
-                eprintln!("{}: ran into {:?}", location, lexeme);
-                Err(ParseError::not_impl(location))
+        }
+        Expr::Record { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
             }
-        },
+        }
+        Expr::FieldAccess { subject, .. } => visitor.visit_expr(subject),
+        Expr::BinaryOp { lhs, rhs, .. } => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::UnaryOp { operand, .. } => visitor.visit_expr(operand),
     }
-    /*
-    parse_parentheses,
-    parse_string_literal,
-    parse_do_notation,
-    parse_if_then,
-    parse_match,
-    parse_number,
-    parse_identifier,
-    parse_ctor,
-    */
 }
 
-fn parse_callsite<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Expr<'a>> {
-    lexer.skip_semicolon()?;
-    let new_lexer = lexer.clone();
-    let maybe_function = parse_callsite_term(&mut new_lexer)?;
-    *lexer = new_lexer;
-
-    match maybe_function {
-        Some(function) => match parse_many(parse_callsite_term, lexer)? {
-            callsite_terms => {
-                if callsite_terms.len() == 0 {
-                    Ok(*function)
-                } else {
-                    Ok(Expr::Callsite {
-                        function,
-                        arguments: callsite_terms,
-                    })
-                }
+/// Visit each sub-pattern of `predicate`, in source order.
+pub fn walk_predicate<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, predicate: &Predicate<'a>) {
+    match predicate {
+        Predicate::Irrefutable(_)
+        | Predicate::Integer { .. }
+        | Predicate::Float { .. }
+        | Predicate::String { .. }
+        | Predicate::Char { .. }
+        | Predicate::Bool { .. } => {}
+        Predicate::Ctor { dims, .. }
+        | Predicate::Tuple { dims, .. }
+        | Predicate::List { dims, .. }
+        | Predicate::Or {
+            alternatives: dims, ..
+        } => {
+            for dim in dims {
+                visitor.visit_predicate(dim);
             }
-        },
-        None => Err(ParseError::error(
-            lexer.location,
-            "missing function callsite expression",
-        )),
+        }
+        Predicate::Cons { head, tail } => {
+            visitor.visit_predicate(head);
+            visitor.visit_predicate(tail);
+        }
+        Predicate::As { pattern, .. } => visitor.visit_predicate(pattern),
+        Predicate::Record { fields, .. } => {
+            for pattern in fields.iter().filter_map(|(_, pattern)| pattern.as_ref()) {
+                visitor.visit_predicate(pattern);
+            }
+        }
     }
 }
 
-pub fn parse_decl<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Decl<'a>>> {
-    let id = match maybe_id(lexer)? {
-        Some(id) => id,
-        None => return Ok(None),
-    };
-    let predicates = parse_predicates(&mut lexer)?;
-    println!("got done with predicates for {}", &id.name);
-    lexer.chomp(Lexeme::Operator("="))?;
-    let expr = parse_callsite(&mut lexer)?;
-    println!("{}: Found callsite {:?}", expr.get_location(), expr);
-    Ok(Some({
-        let decl = Decl {
-            id,
-            predicates,
-            body: expr,
-        };
-        println!("{}: found decl {:?}", decl.get_location(), decl);
-        decl
-    }))
+/// Every reserved word. None of these can be used as a name.
+pub(crate) const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "do", "let", "rec", "in", "match", "data", "where", "type", "module",
+    "import", "as", "infixl", "infixr", "infix",
+];
+
+/// The reserved words of the language, for tooling such as completion and highlighting.
+pub fn keywords() -> &'static [&'static str] {
+    KEYWORDS
 }
 
-pub fn parse_many<'a, T, P>(parser: P, lexer: &mut Lexer<'a>) -> Result<Vec<T>, ParseError<'a>>
-where
-    T: 'a + std::fmt::Debug + HasLocation<'a>,
-    P: 'a + Fn(&mut Lexer<'a>) -> Result<Option<T>, ParseError<'a>>,
-{
-    let mut objects = Vec::new();
-    loop {
-        let new_lexer = lexer.clone();
-        match parser(&mut new_lexer)? {
-            Some(object) => {
-                *lexer = new_lexer;
-                // let loc = object.get_location();
-                // println!("{}: info: found a thing! {:?}", loc, object);
-                objects.push(object);
-            }
-            None => return Ok(objects),
-        }
+pub(crate) fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+/// The error for the keyword `name` at `location`, found where a name was to be bound.
+fn keyword_as_name<'a>(name: &str, location: Location<'a>) -> ParseError<'a> {
+    ParseError::error(
+        ErrorCode::UnexpectedToken,
+        location,
+        format!("`{}` is a keyword and cannot be used as a name", name),
+    )
+}
+
+/// The keyword error when the next token is a keyword, for places where only a name to bind can
+/// come next.
+fn reject_keyword<'a>(lexer: &Lexer<'a>) -> ParseResult<'a, ()> {
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } if is_keyword(name) => Err(keyword_as_name(name, location)),
+        _ => Ok(()),
+    }
+}
+
+/// Consume the next token if it is an identifier which is not a keyword, or an operator in
+/// parentheses such as `(+)`, which names the operator. Otherwise the lexer is left exactly as it
+/// was.
+fn maybe_id<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Identifier<'a>>> {
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::LParen,
+            ..
+        } => {
+            let checkpoint = lexer.checkpoint();
+            lexer.advance()?;
+            if lexer.peek_matches(Lexeme::RParen) {
+                return Err(ParseError::error(
+                    ErrorCode::UnexpectedToken,
+                    location,
+                    "expected an operator between these parentheses, as in `(+)`",
+                ));
+            }
+            match section_operator(lexer) {
+                Some(op) => {
+                    lexer.advance()?;
+                    lexer.advance()?;
+                    Ok(Some(op))
+                }
+                None => {
+                    lexer.restore(checkpoint);
+                    Ok(None)
+                }
+            }
+        }
+        Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } => {
+            /* check for keywords */
+            if is_keyword(name) {
+                Ok(None)
+            } else {
+                lexer.advance_mut()?;
+                Ok(Some(lexer.identifier(name, location)))
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parse the remainder of a parenthesized pattern, after its opening `(` at `location`:
+///
+/// ```text
+/// '(' ')'                          the unit pattern
+/// '(' pred ')'                     just pred, grouped
+/// '(' pred ',' ')'                 a tuple with one element, as in expressions
+/// '(' pred (',' pred)+ ','? ')'    a tuple
+/// ```
+fn parse_tuple_predicate<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Predicate<'a>>> {
+    if lexer.peek_matches(Lexeme::RParen) {
+        lexer.advance_mut()?;
+        return Ok(Some(Predicate::Tuple {
+            location,
+            dims: Vec::new(),
+        }));
+    }
+    let first = parse_tuple_element(lexer)?;
+    if lexer.peek_matches(Lexeme::RParen) {
+        lexer.advance_mut()?;
+        return Ok(Some(first));
+    }
+    let mut dims = vec![Box::new(first)];
+    while lexer.peek_matches(Lexeme::Comma) {
+        lexer.advance_mut()?;
+        if lexer.peek_matches(Lexeme::RParen) {
+            break;
+        }
+        dims.push(Box::new(parse_tuple_element(lexer)?));
+    }
+    lexer.chomp(Lexeme::RParen)?;
+    Ok(Some(Predicate::Tuple { location, dims }))
+}
+
+/// Parse one element of a parenthesized pattern, which must be there.
+fn parse_tuple_element<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Predicate<'a>> {
+    match parse_or_predicate(lexer)? {
+        Some(predicate) => Ok(predicate),
+        None => Err(ParseError::unexpected(
+            lexer.peek(),
+            "a pattern in these parentheses",
+        )),
+    }
+}
+
+/// Parse the remainder of a list pattern such as `[]`, `[x]` or `[x, (a, b), Just c]`, after its
+/// opening `[` at `location`. A single trailing comma is allowed.
+fn parse_list_predicate<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Predicate<'a>>> {
+    let mut dims: Vec<Box<Predicate>> = Vec::new();
+    if !lexer.peek_matches(Lexeme::RSquare) {
+        loop {
+            match parse_or_predicate(lexer)? {
+                Some(predicate) => dims.push(Box::new(predicate)),
+                None => {
+                    return Err(ParseError::unexpected(
+                        lexer.peek(),
+                        "a pattern in this list",
+                    ))
+                }
+            }
+            if lexer.peek_matches(Lexeme::Comma) {
+                lexer.advance_mut()?;
+                if lexer.peek_matches(Lexeme::RSquare) {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    if !lexer.peek_matches(Lexeme::RSquare) {
+        return Err(ParseError::error(
+            ErrorCode::UnbalancedBracket,
+            location,
+            match lexer.peek() {
+                Token {
+                    lexeme: Lexeme::Eof,
+                    ..
+                } => "hit EOF before this list pattern was closed".to_string(),
+                token => format!("expected , or ] to close this list pattern, found {token}"),
+            },
+        ));
+    }
+    lexer.advance_mut()?;
+    Ok(Some(Predicate::List { location, dims }))
+}
+
+/// Parse the remainder of a record pattern such as `{ name = n, age, .. }`, after its opening `{`
+/// at `location`. Fields are separated by commas or newlines.
+fn parse_record_predicate<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Predicate<'a>>> {
+    let mut fields: Vec<(Identifier<'a>, Option<Box<Predicate<'a>>>)> = Vec::new();
+    let mut rest = false;
+    loop {
+        lexer.skip_semicolon()?;
+        match lexer.peek() {
+            Token {
+                lexeme: Lexeme::RCurly,
+                ..
+            } => break,
+            Token {
+                lexeme: Lexeme::Operator(".."),
+                ..
+            } if !rest => {
+                lexer.advance_mut()?;
+                rest = true;
+            }
+            Token {
+                location,
+                lexeme: Lexeme::Identifier(name),
+                ..
+            } if !rest && !is_keyword(name) => {
+                lexer.advance_mut()?;
+                let field = lexer.identifier(name, location);
+                if fields.iter().any(|(other, _)| other.same_name(&field)) {
+                    return Err(ParseError::error(
+                        ErrorCode::InvalidPattern,
+                        location,
+                        format!("field `{}` is matched more than once", name),
+                    ));
+                }
+                let predicate = if lexer.peek_matches(Lexeme::Equals) {
+                    lexer.advance_mut()?;
+                    match parse_or_predicate(lexer)? {
+                        Some(predicate) => Some(Box::new(predicate)),
+                        None => {
+                            return Err(ParseError::error(
+                                ErrorCode::InvalidPattern,
+                                location,
+                                format!("expected a pattern for field `{}`", name),
+                            ))
+                        }
+                    }
+                } else {
+                    None
+                };
+                fields.push((field, predicate));
+            }
+            Token {
+                lexeme: Lexeme::Eof,
+                ..
+            } => {
+                return Err(ParseError::error(
+                    ErrorCode::UnexpectedEof,
+                    location,
+                    "hit EOF before this record pattern was closed",
+                ))
+            }
+            token => {
+                return Err(ParseError::unexpected(
+                    token,
+                    if rest {
+                        "} after .. in this record pattern"
+                    } else {
+                        "a field name, .. or } in this record pattern"
+                    },
+                ))
+            }
+        }
+        if lexer.peek_matches(Lexeme::Comma) {
+            lexer.advance_mut()?;
+        }
+    }
+    lexer.advance_mut()?;
+    Ok(Some(Predicate::Record {
+        location,
+        fields,
+        rest,
+    }))
+}
+
+/// Parse a single pattern, in which a constructor takes the atoms after it as its arguments, as in
+/// `Just x` or `Pair (a, b) c`.
+fn parse_predicate<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Predicate<'a>>> {
+    lexer.nested(|lexer| parse_predicate_term(lexer, true))
+}
+
+/// Parse a pattern which stands alone among others, so a constructor takes no arguments unless
+/// it is in parentheses. Each parameter in a decl head or lambda is an atom, as is each argument
+/// of a constructor: `f (Just x) y` has two parameters, and so does `f Just x`.
+fn parse_predicate_atom<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Predicate<'a>>> {
+    lexer.nested(|lexer| parse_predicate_term(lexer, false))
+}
+
+fn parse_predicate_term<'a>(
+    lexer: &mut Lexer<'a>,
+    ctor_args: bool,
+) -> ParseResult<'a, Option<Predicate<'a>>> {
+    let token = lexer.peek();
+    match token.lexeme {
+        Lexeme::Signed(value) => {
+            lexer.advance_mut()?;
+            Ok(Some(Predicate::Integer {
+                location: token.location,
+                value,
+            }))
+        }
+        Lexeme::Float(value) => {
+            lexer.advance_mut()?;
+            Ok(Some(Predicate::Float {
+                location: token.location,
+                value,
+            }))
+        }
+        Lexeme::Operator("-") => {
+            // A negated numeric literal, written with a space as in `- 1`. (`-1` lexes as a
+            // single literal.)
+            let checkpoint = lexer.checkpoint();
+            lexer.advance_mut()?;
+            let predicate = match lexer.peek().lexeme {
+                Lexeme::Signed(value) => Predicate::Integer {
+                    location: token.location,
+                    value: -value,
+                },
+                Lexeme::Float(value) => Predicate::Float {
+                    location: token.location,
+                    value: -value,
+                },
+                _ => {
+                    lexer.restore(checkpoint);
+                    return Ok(None);
+                }
+            };
+            lexer.advance_mut()?;
+            Ok(Some(predicate))
+        }
+        Lexeme::QuotedString(value) => {
+            lexer.advance_mut()?;
+            Ok(Some(Predicate::String {
+                location: token.location,
+                value: value.to_string(),
+            }))
+        }
+        Lexeme::Char(value) => {
+            lexer.advance_mut()?;
+            Ok(Some(Predicate::Char {
+                location: token.location,
+                value,
+            }))
+        }
+        Lexeme::Identifier(name) if is_keyword(name) => Ok(None),
+        Lexeme::Identifier(name) => {
+            if let Some(value) = bool_literal(name) {
+                lexer.advance_mut()?;
+                Ok(Some(Predicate::Bool {
+                    location: token.location,
+                    value,
+                }))
+            } else if name.chars().next().unwrap().is_uppercase() {
+                // Ctor
+                let ctor_id = lexer.identifier(name, token.location);
+                lexer.advance_mut()?;
+                let dims = if ctor_args {
+                    parse_predicates(lexer)?
+                } else {
+                    Vec::new()
+                };
+                Ok(Some(Predicate::Ctor { ctor_id, dims }))
+            } else {
+                let binding = lexer.identifier(name, token.location);
+                lexer.advance_mut()?;
+                match lexer.peek() {
+                    Token {
+                        location,
+                        lexeme: Lexeme::Operator("@"),
+                        ..
+                    } => {
+                        // An as-pattern, binding the whole value as well as its parts.
+                        lexer.advance_mut()?;
+                        match lexer.nested(|lexer| parse_predicate_term(lexer, ctor_args))? {
+                            Some(pattern) => Ok(Some(Predicate::As {
+                                binding,
+                                pattern: pattern.into(),
+                            })),
+                            None => Err(ParseError::error(
+                                ErrorCode::InvalidPattern,
+                                location,
+                                "expected a pattern after @",
+                            )),
+                        }
+                    }
+                    _ => Ok(Some(Predicate::Irrefutable(binding))),
+                }
+            }
+        }
+        Lexeme::LParen => {
+            lexer.advance_mut()?;
+            parse_tuple_predicate(token.location, lexer)
+        }
+        Lexeme::LSquare => {
+            lexer.advance_mut()?;
+            parse_list_predicate(token.location, lexer)
+        }
+        Lexeme::LCurly => {
+            lexer.advance_mut()?;
+            parse_record_predicate(token.location, lexer)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parse a predicate which may be a cons pattern, `head : tail`. Cons is right-associative, so
+/// `x : y : rest` is `x : (y : rest)`. Cons patterns appear inside parentheses and in match arms,
+/// but not bare in a decl head, where juxtaposed predicates are separate parameters.
+fn parse_cons_predicate<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Predicate<'a>>> {
+    let head = match parse_predicate(lexer)? {
+        Some(head) => head,
+        None => return Ok(None),
+    };
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Operator(":"),
+            ..
+        } => {
+            lexer.advance_mut()?;
+            match lexer.nested(parse_cons_predicate)? {
+                Some(tail) => Ok(Some(Predicate::Cons {
+                    head: head.into(),
+                    tail: tail.into(),
+                })),
+                None => Err(ParseError::error(
+                    ErrorCode::InvalidPattern,
+                    location,
+                    "expected a pattern for the tail of this cons pattern",
+                )),
+            }
+        }
+        _ => Ok(Some(head)),
+    }
+}
+
+/// Parse a predicate which may be an or-pattern, `p1 | p2 | ...`, matching when any alternative
+/// does. Or-patterns bind more loosely than cons patterns, so `[] | _ : []` has two alternatives.
+/// Whether the alternatives bind the same names is checked separately, by check_or_patterns, so
+/// that match arm lookahead still recognizes a mismatched or-pattern as an arm.
+fn parse_or_predicate<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Predicate<'a>>> {
+    let first = match parse_cons_predicate(lexer)? {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    if !lexer.peek_matches(Lexeme::Operator("|")) {
+        return Ok(Some(first));
+    }
+
+    let location = *first.get_location();
+    let mut alternatives = vec![Box::new(first)];
+    while let Token {
+        location: pipe_location,
+        lexeme: Lexeme::Operator("|"),
+        ..
+    } = lexer.peek()
+    {
+        lexer.advance_mut()?;
+        let alternative = match parse_cons_predicate(lexer)? {
+            Some(alternative) => alternative,
+            None => {
+                return Err(ParseError::error(
+                    ErrorCode::InvalidPattern,
+                    pipe_location,
+                    "expected a pattern after |",
+                ))
+            }
+        };
+        alternatives.push(Box::new(alternative));
+    }
+    Ok(Some(Predicate::Or {
+        location,
+        alternatives,
+    }))
+}
+
+fn parse_predicates<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Vec<Box<Predicate<'a>>>> {
+    let mut predicates = Vec::new();
+    loop {
+        match parse_predicate_atom(lexer)? {
+            None => return Ok(predicates),
+            Some(predicate) => predicates.push(Box::new(predicate)),
+        }
+    }
+}
+
+fn parse_identifier<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Identifier<'a>> {
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } if is_keyword(name) => Err(keyword_as_name(name, location)),
+        Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } => {
+            lexer.advance_mut()?;
+            Ok(lexer.identifier(name, location))
+        }
+        _ => Err(ParseError::error(
+            ErrorCode::UnexpectedToken,
+            lexer.location,
+            "expected an identifier here",
+        )),
+    }
+}
+
+/// Returns true when the upcoming tokens form the head of a match arm, i.e. a predicate followed
+/// by `=>` or by an `if` guard. Arms may be separated by semicolons. The lexer itself is left
+/// untouched. Within a guard nothing starts an arm, since the guard runs up to its `=>`.
+fn starts_match_arm(lexer: &mut Lexer) -> bool {
+    if lexer.in_match_guard {
+        return false;
+    }
+    lexer.lookahead(|lexer| {
+        lexer.skip_semicolon().is_ok()
+            && matches!(parse_or_predicate(lexer), Ok(Some(_)))
+            && (lexer.peek_matches(Lexeme::Operator("=>"))
+                || lexer.peek_matches(Lexeme::Identifier("if")))
+    })
+}
+
+/// Parse the guard of a match arm, `if cond`, if there is one. The guard is an ordinary
+/// expression which ends at the arm's `=>`.
+fn parse_match_guard<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Expr<'a>>> {
+    if !lexer.peek_matches(Lexeme::Identifier("if")) {
+        return Ok(None);
+    }
+    lexer.advance()?;
+    lexer.in_match_guard = true;
+    let guard = parse_expr(lexer);
+    lexer.in_match_guard = false;
+    Ok(Some(guard?))
+}
+
+fn parse_match_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    // A match nested within a guard (in parentheses) has arms of its own.
+    let in_match_guard = std::mem::replace(&mut lexer.in_match_guard, false);
+    let match_expr = parse_match_arms(location, lexer);
+    lexer.in_match_guard = in_match_guard;
+    match_expr
+}
+
+fn parse_match_arms<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let subject = parse_expr(lexer)?;
+    let mut pattern_exprs = Vec::new();
+    // Arms continue for as long as the next tokens begin a `predicate =>` or `predicate if`.
+    while starts_match_arm(lexer) {
+        lexer.skip_semicolon()?;
+        let predicate = match parse_or_predicate(lexer)? {
+            Some(predicate) => predicate,
+            None => {
+                return Err(ParseError::error(
+                    ErrorCode::InvalidPattern,
+                    lexer.location,
+                    "expected a predicate for this match arm",
+                ))
+            }
+        };
+        predicate.check_or_patterns()?;
+        predicate.check_duplicate_bindings()?;
+        let guard = parse_match_guard(lexer)?;
+        lexer.chomp(Lexeme::Operator("=>"))?;
+        let expr = parse_expr(lexer)?;
+        pattern_exprs.push(PatternExpr {
+            predicate,
+            guard,
+            expr,
+        });
+    }
+
+    if pattern_exprs.is_empty() {
+        return Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            location,
+            "match expression must have at least one arm",
+        ));
+    }
+
+    Ok(Some(
+        Expr::Match {
+            location,
+            subject: subject.into(),
+            pattern_exprs,
+        }
+        .into(),
+    ))
+}
+
+/// Parse the rest of a `let` at `location`, which may have several bindings separated by
+/// semicolons, as in `let a = 1; b = a + 1 in b`, or by newlines where the next binding lines up
+/// with the first (or anywhere within braces). Each binding is
+/// in scope for those after it, so they nest as one `Expr::Let` per binding. A `let rec` is a
+/// single `Expr::LetRec`, since every binding is in scope for all of them. A binding with parameters, as in `let f x = x`,
+/// binds a lambda. A binding which starts with anything but a name, as in `let (a, b) = pair`,
+/// destructures its value with a pattern instead, giving an `Expr::LetPattern`.
+fn parse_let_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let recursive = lexer.peek_matches(Lexeme::Identifier("rec"));
+    if recursive {
+        lexer.advance_mut()?;
+    }
+    let bindings = lexer.with_layout(|lexer| parse_let_bindings(location, recursive, lexer))?;
+    // The layout of the bindings ends before the token after `in` is read.
+    lexer.advance_mut()?;
+    let mut body = parse_expr(lexer)?;
+    if recursive {
+        // A `let rec` binds only names; see parse_let_pattern.
+        let bindings = bindings
+            .into_iter()
+            .filter_map(|(binding, value)| match binding {
+                LetBinding::Name(binding) => Some((binding, value)),
+                LetBinding::Pattern(_) => None,
+            })
+            .collect();
+        return Ok(Some(
+            Expr::LetRec {
+                location,
+                bindings,
+                body: body.into(),
+            }
+            .into(),
+        ));
+    }
+    for (index, (binding, value)) in bindings.into_iter().enumerate().rev() {
+        let binding_location = match &binding {
+            _ if index == 0 => location,
+            LetBinding::Name(id) => *id.get_location(),
+            LetBinding::Pattern(pattern) => *pattern.get_location(),
+        };
+        body = match binding {
+            LetBinding::Name(binding) => Expr::Let {
+                location: binding_location,
+                binding,
+                value: value.into(),
+                body: body.into(),
+            },
+            LetBinding::Pattern(pattern) => Expr::LetPattern {
+                location: binding_location,
+                pattern: pattern.into(),
+                value: value.into(),
+                body: body.into(),
+            },
+        };
+    }
+    Ok(Some(body.into()))
+}
+
+/// Parse the bindings of a let, up to its `in`.
+fn parse_let_bindings<'a>(
+    location: Location<'a>,
+    recursive: bool,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Vec<(LetBinding<'a>, Expr<'a>)>> {
+    let mut bindings = Vec::new();
+    loop {
+        if !at_let_name(lexer) {
+            let pattern = parse_let_pattern(recursive, lexer)?;
+            expect_let_equals("this pattern", lexer)?;
+            let value = parse_expr(lexer)?;
+            let done = end_let_binding(location, &value, lexer)?;
+            bindings.push((LetBinding::Pattern(pattern), value));
+            if done {
+                return Ok(bindings);
+            }
+            continue;
+        }
+        let binding_id = parse_identifier(lexer)?;
+        let mut param_names = Vec::new();
+        while let Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } = lexer.peek()
+        {
+            if is_keyword(name) {
+                break;
+            }
+            lexer.advance_mut()?;
+            param_names.push(Box::new(Predicate::Irrefutable(Identifier::new(
+                name, location,
+            ))));
+        }
+        reject_keyword(lexer)?;
+        if !lexer.peek_matches(Lexeme::Equals) {
+            // Without an `=`, a parameter which looks like a keyword, as in `let x inn x`, was
+            // most likely meant as one.
+            if let Some(id) = param_names.iter().find_map(|param| match param.as_ref() {
+                Predicate::Irrefutable(id) if suggest_keyword(id.name).is_some() => Some(id),
+                _ => None,
+            }) {
+                return Err(ParseError::unexpected(
+                    Token::new(*id.get_location(), Lexeme::Identifier(id.name)),
+                    format!("`=` after `{}` in this let binding", binding_id.name),
+                ));
+            }
+        }
+        expect_let_equals(&format!("`{}`", binding_id.name), lexer)?;
+        let mut binding_value = parse_expr(lexer)?;
+        if !param_names.is_empty() {
+            binding_value = Expr::Lambda {
+                location: *binding_id.get_location(),
+                params: param_names,
+                body: binding_value.into(),
+            };
+        }
+        let done = end_let_binding(location, &binding_value, lexer)?;
+        bindings.push((LetBinding::Name(binding_id), binding_value));
+        if done {
+            return Ok(bindings);
+        }
+    }
+}
+
+/// The left side of one binding in a let.
+enum LetBinding<'a> {
+    Name(Identifier<'a>),
+    Pattern(Predicate<'a>),
+}
+
+/// Whether the next let binding starts with a name to bind, rather than a pattern.
+fn at_let_name<'a>(lexer: &Lexer<'a>) -> bool {
+    match lexer.peek().lexeme {
+        Lexeme::Identifier(name) => {
+            is_keyword(name)
+                || (bool_literal(name).is_none() && !name.starts_with(char::is_uppercase))
+        }
+        _ => false,
+    }
+}
+
+/// Parse the pattern on the left of a let binding. A constructor takes the atoms after it as
+/// its arguments, as in `let Pair a b = p`. Patterns can't bind a `let rec`, whose bindings
+/// must each be in scope as a whole.
+fn parse_let_pattern<'a>(recursive: bool, lexer: &mut Lexer<'a>) -> ParseResult<'a, Predicate<'a>> {
+    let pattern = match parse_predicate(lexer)? {
+        Some(pattern) => pattern,
+        None => {
+            return Err(ParseError::unexpected(
+                lexer.peek(),
+                "a name or pattern in this let binding",
+            ))
+        }
+    };
+    if recursive {
+        return Err(ParseError::error(
+            ErrorCode::InvalidPattern,
+            *pattern.get_location(),
+            "a `let rec` binding must be a name, not a pattern",
+        ));
+    }
+    pattern.check_or_patterns()?;
+    pattern.check_duplicate_bindings()?;
+    Ok(pattern)
+}
+
+/// Chomp the `=` after `what` in a let binding.
+fn expect_let_equals<'a>(what: &str, lexer: &mut Lexer<'a>) -> ParseResult<'a, ()> {
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Equals,
+            ..
+        } => {
+            lexer.advance_mut()?;
+            Ok(())
+        }
+        token => Err(ParseError::unexpected(
+            token,
+            format!("`=` after {what} in this let binding"),
+        )),
+    }
+}
+
+/// After the `value` of a let binding, return true at the `in` which ends the bindings, or chomp
+/// the `;` before another binding.
+fn end_let_binding<'a>(
+    location: Location<'a>,
+    value: &Expr<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, bool> {
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Identifier("in"),
+            ..
+        } => Ok(true),
+        Token {
+            location: semicolon_location,
+            lexeme: Lexeme::Semicolon,
+            ..
+        } => {
+            lexer.advance_mut()?;
+            if lexer.peek_matches(Lexeme::Identifier("in")) {
+                return Err(ParseError::error(
+                    ErrorCode::UnexpectedToken,
+                    semicolon_location,
+                    "unexpected `;` between the last let binding and `in`",
+                ));
+            }
+            Ok(false)
+        }
+        _ => Err(match misspelled_keyword(value, "in") {
+            Some(id) => ParseError::unexpected(
+                Token::new(*id.get_location(), Lexeme::Identifier(id.name)),
+                format!("`in` to end the let at {}", location),
+            ),
+            None => ParseError::error(
+                ErrorCode::InvalidExpression,
+                location,
+                "this let is missing its `in`; only a statement in a do-block can leave it out",
+            ),
+        }),
+    }
+}
+
+/// The first name in `expr` which looks like a typo of `keyword`, as `els` does in
+/// `if a then b els c`, where the typo was read as an argument instead of ending the branch.
+fn misspelled_keyword<'a>(expr: &Expr<'a>, keyword: &str) -> Option<Identifier<'a>> {
+    struct Finder<'a, 'k> {
+        keyword: &'k str,
+        found: Option<Identifier<'a>>,
+    }
+    impl<'a> Visitor<'a> for Finder<'a, '_> {
+        fn visit_expr(&mut self, expr: &Expr<'a>) {
+            match expr {
+                _ if self.found.is_some() => {}
+                Expr::Symbol { id } if suggest_keyword(id.name) == Some(self.keyword) => {
+                    self.found = Some(id.clone())
+                }
+                _ => walk_expr(self, expr),
+            }
+        }
+    }
+    let mut finder = Finder {
+        keyword,
+        found: None,
+    };
+    finder.visit_expr(expr);
+    finder.found
+}
+
+/// Parse the rest of a do-block at `location`: statements within braces, separated by semicolons
+/// or newlines. A statement is an expression, a `let` without an `in`, or a bind such as
+/// `x <- action`. Each binding is in scope for the rest of the block. The last statement must be
+/// an expression.
+fn parse_do_block<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::LCurly,
+            ..
+        } => lexer.advance()?,
+        token => return Err(ParseError::unexpected(token, "`{` to start this do-block")),
+    };
+    let (statements, result) = parse_statements(location, "do-block", lexer)?;
+    Ok(Some(
+        Expr::Do {
+            location,
+            statements,
+            result: result.into(),
+        }
+        .into(),
+    ))
+}
+
+/// Parse the rest of a block such as `{ log x; let y = x + 1; y * 2 }`, after its opening `{` at
+/// `location`. The statements are as in a do-block, except for binds, and the last is the
+/// block's value.
+fn parse_block_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let (statements, result) = parse_statements(location, "block", lexer)?;
+    Ok(Some(
+        Expr::Block {
+            location,
+            statements,
+            result: result.into(),
+        }
+        .into(),
+    ))
+}
+
+/// Parse the statements of the `what` (a block or do-block) at `location` up to and including its
+/// closing `}`, separated by semicolons or newlines. Only a do-block may contain binds. There
+/// must be at least one statement, and the last must be an expression, which is returned apart
+/// from the statements before it.
+fn parse_statements<'a>(
+    location: Location<'a>,
+    what: &str,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, (Vec<DoStatement<'a>>, Expr<'a>)> {
+    let binds = what == "do-block";
+    let mut statements = Vec::new();
+    let mut last_arrow = None;
+    loop {
+        lexer.skip_semicolon()?;
+        if lexer.peek_matches(Lexeme::RCurly) {
+            break;
+        }
+        last_arrow = if binds { bind_arrow(lexer) } else { None };
+        statements.push(parse_do_statement(
+            location,
+            what,
+            last_arrow.is_some(),
+            lexer,
+        )?);
+        match lexer.peek() {
+            Token {
+                lexeme: Lexeme::Semicolon | Lexeme::RCurly,
+                ..
+            } => (),
+            Token {
+                lexeme: Lexeme::Eof,
+                ..
+            } => {
+                return Err(ParseError::error(
+                    ErrorCode::UnbalancedBracket,
+                    location,
+                    format!("hit EOF before this {what} was closed"),
+                ))
+            }
+            token => {
+                return Err(ParseError::unexpected(
+                    token,
+                    format!("`;` or `}}` after this statement in the {what}"),
+                ))
+            }
+        }
+    }
+    lexer.advance()?;
+    let binding = match statements.pop() {
+        None => {
+            return Err(ParseError::error(
+                ErrorCode::InvalidExpression,
+                location,
+                format!("this {what} is empty; it needs an expression for its value"),
+            ))
+        }
+        Some(DoStatement::Expr(result)) => return Ok((statements, result)),
+        Some(DoStatement::Let { binding, .. } | DoStatement::Bind { binding, .. }) => binding,
+    };
+    Err(ParseError::error(
+        ErrorCode::InvalidExpression,
+        last_arrow.unwrap_or(*binding.get_location()),
+        format!(
+            "a {what} must end with an expression, but this one ends by binding `{}`",
+            binding.name
+        ),
+    ))
+}
+
+/// The location of the `<-` when the next statement is a bind, as in `x <- action`.
+fn bind_arrow<'a>(lexer: &mut Lexer<'a>) -> Option<Location<'a>> {
+    match lexer.peek().lexeme {
+        Lexeme::Identifier(name) if !is_keyword(name) => (),
+        _ => return None,
+    }
+    lexer.lookahead(|lexer| {
+        lexer.advance().ok()?;
+        match lexer.peek() {
+            Token {
+                location,
+                lexeme: Lexeme::Operator("<-"),
+                ..
+            } => Some(location),
+            _ => None,
+        }
+    })
+}
+
+/// Parse one statement of the `what` at `location`, which is a bind when `bind` is set. A `let`
+/// followed by `in` is an ordinary let expression rather than a let statement.
+fn parse_do_statement<'a>(
+    location: Location<'a>,
+    what: &str,
+    bind: bool,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, DoStatement<'a>> {
+    if bind {
+        let binding = parse_identifier(lexer)?;
+        lexer.advance()?;
+        let value = parse_expr(lexer)?;
+        return Ok(DoStatement::Bind { binding, value });
+    }
+    if lexer.peek_matches(Lexeme::Identifier("let")) {
+        let checkpoint = lexer.checkpoint();
+        lexer.advance()?;
+        let binding = parse_identifier(lexer)?;
+        reject_keyword(lexer)?;
+        expect_let_equals(&format!("`{}`", binding.name), lexer)?;
+        let value = parse_expr(lexer)?;
+        if !lexer.peek_matches(Lexeme::Identifier("in")) {
+            return Ok(DoStatement::Let { binding, value });
+        }
+        lexer.restore(checkpoint);
+    }
+    match parse_expr(lexer) {
+        Ok(expr) => Ok(DoStatement::Expr(expr)),
+        Err(err) => Err(match lexer.peek() {
+            Token {
+                lexeme: Lexeme::Eof,
+                ..
+            } => ParseError::error(
+                ErrorCode::UnbalancedBracket,
+                location,
+                format!("hit EOF before this {what} was closed"),
+            ),
+            _ => err,
+        }),
+    }
+}
+
+/// The meaning of a do-block's statements and its final `result` in terms of other expressions,
+/// where `rest` is the meaning of the statements after each one, ending with `result`:
+///
+/// - `let x = value` is `let x = value in rest`, an `Expr::Let`.
+/// - `x <- action` is `action >>= \x -> rest`.
+/// - Any other statement is `statement >> rest`.
+pub fn desugar_do<'a>(statements: &[DoStatement<'a>], result: &Expr<'a>) -> Expr<'a> {
+    statements
+        .iter()
+        .rev()
+        .fold(result.clone(), |body, statement| match statement {
+            DoStatement::Let { binding, value } => Expr::Let {
+                location: *binding.get_location(),
+                binding: binding.clone(),
+                value: value.clone().into(),
+                body: body.into(),
+            },
+            DoStatement::Bind { binding, value } => Expr::BinaryOp {
+                op: Identifier::new(">>=", *binding.get_location()),
+                lhs: value.clone().into(),
+                rhs: Expr::Lambda {
+                    location: *binding.get_location(),
+                    params: vec![Box::new(Predicate::Irrefutable(binding.clone()))],
+                    body: body.into(),
+                }
+                .into(),
+            },
+            DoStatement::Expr(value) => Expr::BinaryOp {
+                op: Identifier::new(">>", *value.get_location()),
+                lhs: value.clone().into(),
+                rhs: body.into(),
+            },
+        })
+}
+
+/// Parse the rest of an `if` at `location`, as in `if a then x else y`. Each branch is an
+/// ordinary expression, so the else branch extends as far as it can. That makes
+/// `if a then x else if b then y else z` a chain nested to the right, without parentheses. An
+/// `else` always belongs to the nearest `if` still waiting for one, so there is no dangling else:
+/// `if a then if b then x else y else z` gives the inner `if` the first `else`.
+fn parse_if_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let condition = parse_expr(lexer)?;
+    chomp_if_keyword(location, "then", &condition, lexer)?;
+    let then_branch = parse_expr(lexer)?;
+    chomp_if_keyword(location, "else", &then_branch, lexer)?;
+    let else_branch = parse_expr(lexer)?;
+    Ok(Some(
+        Expr::If {
+            location,
+            condition: condition.into(),
+            then_branch: then_branch.into(),
+            else_branch: else_branch.into(),
+        }
+        .into(),
+    ))
+}
+
+/// Consume the `then` or `else` of the `if` at `location`, which follows `before`. Within braces,
+/// where newlines are separators, the keyword may start the next line. When it is missing, a
+/// typo of it within `before` is reported instead, since that is most likely where it went.
+fn chomp_if_keyword<'a>(
+    location: Location<'a>,
+    keyword: &'static str,
+    before: &Expr<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, ()> {
+    let checkpoint = lexer.checkpoint();
+    lexer.skip_semicolon()?;
+    if lexer.peek_matches(Lexeme::Identifier(keyword)) {
+        return lexer.advance();
+    }
+    lexer.restore(checkpoint);
+    let token = match misspelled_keyword(before, keyword) {
+        Some(id) => Token::new(*id.get_location(), Lexeme::Identifier(id.name)),
+        None => lexer.peek(),
+    };
+    Err(ParseError::unexpected(
+        token,
+        format!("`{}` to continue the if at {}", keyword, location),
+    ))
+}
+
+fn parse_lambda_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let params = parse_predicates(lexer)?;
+    check_parameters(&params)?;
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Operator("->"),
+            ..
+        } => {}
+        Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } if is_keyword(name) => return Err(keyword_as_name(name, location)),
+        token => {
+            return Err(ParseError::unexpected(
+                token,
+                "a lambda parameter pattern or ->",
+            ))
+        }
+    }
+
+    if params.is_empty() {
+        return Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            location,
+            "lambda must have at least one parameter",
+        ));
+    }
+    lexer.chomp(Lexeme::Operator("->"))?;
+    let body = parse_expr(lexer)?;
+    Ok(Some(
+        Expr::Lambda {
+            location,
+            params,
+            body: body.into(),
+        }
+        .into(),
+    ))
+}
+
+/// Whether `name` is an operator rather than an ordinary name, as in `(+)`.
+pub(crate) fn is_operator_name(name: &str) -> bool {
+    name.chars().next().is_some_and(is_operator_char)
+}
+
+/// `name` as it is written when referred to or defined, which for an operator is in parentheses.
+pub(crate) fn name_source(name: &str) -> String {
+    if is_operator_name(name) {
+        format!("({})", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// The operator at the lexer if it is directly followed by `)`, so that it ends an operator
+/// section such as `(+)` or `(1 +)`.
+fn section_operator<'a>(lexer: &mut Lexer<'a>) -> Option<Identifier<'a>> {
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Operator(op),
+            ..
+        } if !is_reserved_operator(op) => lexer
+            .lookahead(|lexer| lexer.advance().is_ok() && lexer.peek_matches(Lexeme::RParen))
+            .then(|| Identifier::new(op, location)),
+        _ => None,
+    }
+}
+
+/// The lambda for an operator section which is missing one operand, as in `(+ 1)` or `(1 +)`.
+fn section_lambda<'a>(
+    op: Identifier<'a>,
+    operand: Box<Expr<'a>>,
+    operand_is_lhs: bool,
+) -> ParseResult<'a, Box<Expr<'a>>> {
+    let location = *op.get_location();
+    let param = Identifier::new(SECTION_PARAM, location);
+    let param_expr = Box::new(Expr::Symbol { id: param.clone() });
+    let (lhs, rhs) = if operand_is_lhs {
+        (operand, param_expr)
+    } else {
+        (param_expr, operand)
+    };
+    Ok(Expr::Lambda {
+        location,
+        params: vec![Box::new(Predicate::Irrefutable(param))],
+        body: Expr::BinaryOp { op, lhs, rhs }.into(),
+    }
+    .into())
+}
+
+/// The parameter of a section's lambda. It is not a name the source can spell, so it never
+/// captures anything the operand refers to.
+const SECTION_PARAM: &str = "$0";
+
+/// The operator, the operand and whether the operand is on the left of a lambda built by
+/// `section_lambda`.
+fn section_parts<'e, 'a>(expr: &'e Expr<'a>) -> Option<(&'e Identifier<'a>, &'e Expr<'a>, bool)> {
+    let Expr::Lambda { params, body, .. } = expr else {
+        return None;
+    };
+    let is_param = |expr: &Expr| matches!(expr, Expr::Symbol { id } if id.name == SECTION_PARAM);
+    match (params.as_slice(), body.as_ref()) {
+        ([param], Expr::BinaryOp { op, lhs, rhs }) if matches!(param.as_ref(), Predicate::Irrefutable(id) if id.name == SECTION_PARAM) => {
+            if is_param(rhs) {
+                Some((op, lhs, true))
+            } else if is_param(lhs) {
+                Some((op, rhs, false))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Parse the remainder of a parenthesized expression. `(e)` is just `e`, while `()`, `(e,)` and
+/// `(e1, e2, ...)` construct tuples. An operator on its own, as in `(+)`, is that operator as a
+/// function, and a section such as `(+ 1)` or `(1 +)` is a lambda supplying the missing operand.
+/// `(- x)` is a negation rather than a section.
+fn parse_tuple_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    if let Some(op) = section_operator(lexer) {
+        lexer.advance()?;
+        lexer.advance()?;
+        return Ok(Some(Expr::Symbol { id: op }.into()));
+    }
+    if let Token {
+        location: op_location,
+        lexeme: Lexeme::Operator(op),
+        ..
+    } = lexer.peek()
+    {
+        if !is_reserved_operator(op) && !is_prefix_operator(op) {
+            lexer.advance()?;
+            let rhs = parse_expr(lexer)?;
+            lexer.chomp(Lexeme::RParen)?;
+            let op = Identifier::new(op, op_location);
+            return section_lambda(op, rhs.into(), false).map(Some);
+        }
+    }
+
+    let mut dims: Vec<Box<Expr>> = Vec::new();
+    let mut trailing_comma = false;
+    while !lexer.peek_matches(Lexeme::RParen) {
+        dims.push(Box::new(parse_expr(lexer)?));
+        if dims.len() == 1 {
+            if let Some(op) = section_operator(lexer) {
+                lexer.advance()?;
+                lexer.advance()?;
+                let lhs = dims.pop().unwrap();
+                return section_lambda(op, lhs, true).map(Some);
+            }
+        }
+        trailing_comma = lexer.peek_matches(Lexeme::Comma);
+        if trailing_comma {
+            lexer.advance()?;
+        } else {
+            break;
+        }
+    }
+
+    if !lexer.peek_matches(Lexeme::RParen) {
+        return Err(ParseError::error(
+            ErrorCode::UnbalancedBracket,
+            location,
+            match lexer.peek() {
+                Token {
+                    lexeme: Lexeme::Eof,
+                    ..
+                } => "hit EOF before this parenthesis was closed".to_string(),
+                token => format!("expected , or ) to close this parenthesis, found {token}"),
+            },
+        ));
+    }
+    lexer.advance()?;
+
+    if dims.len() == 1 && !trailing_comma {
+        Ok(dims.pop())
+    } else {
+        Ok(Some(
+            Expr::TupleCtor {
+                span: location.to(&lexer.previous_token_end()),
+                dims,
+            }
+            .into(),
+        ))
+    }
+}
+
+/// Parse the remainder of a list expression: either a literal, `[]` or `[e1, e2, ...]` with an
+/// optional trailing comma, a comprehension, `[output | qualifier, ...]`, or a range such as
+/// `[1..10]` or `[0, 2..20]`.
+fn parse_list_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let mut items: Vec<Box<Expr>> = Vec::new();
+    while !lexer.peek_matches(Lexeme::RSquare) {
+        let grouped = parenthesized(lexer);
+        let item = parse_expr(lexer)?;
+        if items.is_empty() && lexer.peek_matches(Lexeme::Operator("|")) {
+            lexer.advance()?;
+            return parse_comprehension(location, Box::new(item), lexer).map(Some);
+        }
+        if let (Expr::Range { .. }, false) = (&item, grouped) {
+            return parse_list_range(location, items, item, lexer).map(Some);
+        }
+        items.push(Box::new(item));
+        if lexer.peek_matches(Lexeme::Comma) {
+            lexer.advance()?;
+        } else {
+            break;
+        }
+    }
+    close_list(location, lexer)?;
+    Ok(Some(
+        Expr::List {
+            span: location.to(&lexer.previous_token_end()),
+            items,
+        }
+        .into(),
+    ))
+}
+
+/// Whether the next expression is wholly within parentheses, as in `(1..10)` but not `(1)..10`.
+/// The lexer itself is left untouched.
+fn parenthesized(lexer: &mut Lexer) -> bool {
+    if !lexer.peek_matches(Lexeme::LParen) {
+        return false;
+    }
+    lexer.lookahead(|lexer| {
+        let mut depth = 0usize;
+        loop {
+            match lexer.peek().lexeme {
+                Lexeme::LParen | Lexeme::LSquare | Lexeme::LCurly | Lexeme::HashSquare => {
+                    depth += 1
+                }
+                Lexeme::RParen | Lexeme::RSquare | Lexeme::RCurly => depth -= 1,
+                Lexeme::Eof => return false,
+                _ => (),
+            }
+            if lexer.advance().is_err() {
+                return false;
+            }
+            if depth == 0 {
+                return matches!(
+                    lexer.peek().lexeme,
+                    Lexeme::Comma | Lexeme::RSquare | Lexeme::Operator("|")
+                );
+            }
+        }
+    })
+}
+
+/// Finish the range within the brackets at `location`, given the `items` before it. A single
+/// item before the range is the start of a stepped range, whose range gives its second element
+/// and its end.
+fn parse_list_range<'a>(
+    location: Location<'a>,
+    mut items: Vec<Box<Expr<'a>>>,
+    range: Expr<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Box<Expr<'a>>> {
+    let Expr::Range {
+        location: range_location,
+        start,
+        end,
+        inclusive,
+        ..
+    } = range
+    else {
+        unreachable!("parse_list_range is only given a range");
+    };
+    let error = |message: &str| {
+        Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            range_location,
+            message,
+        ))
+    };
+    if items.len() > 1 {
+        return error("a stepped range gives only its first two elements, as in `[0, 2..20]`");
+    }
+    let (start, step) = match items.pop() {
+        Some(_) if start.is_none() => {
+            return error("a stepped range needs its second element, as in `[0, 2..20]`")
+        }
+        Some(first) => (Some(first), start),
+        None if start.is_none() => return error("a range in a list needs a start, as in `[1..n]`"),
+        None => (start, None),
+    };
+    if end.is_none() {
+        return error("a range in a list needs an end; `[x..]` is not supported yet");
+    }
+    if lexer.peek_matches(Lexeme::Comma) {
+        return error("a range in a list must be the list's last element, as in `[1..10]`");
+    }
+    close_list(location, lexer)?;
+    Ok(Expr::Range {
+        location,
+        start,
+        step,
+        end,
+        inclusive,
+    }
+    .into())
+}
+
+/// Parse the comma-separated qualifiers of the comprehension at `location`, after its `|`. Each
+/// is either a generator, `pattern <- source`, or a guard, which is any other expression.
+fn parse_comprehension<'a>(
+    location: Location<'a>,
+    output: Box<Expr<'a>>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Box<Expr<'a>>> {
+    let mut generators = Vec::new();
+    let mut guards = Vec::new();
+    loop {
+        match parse_generator(lexer)? {
+            Some(generator) => generators.push(generator),
+            None => guards.push((generators.len(), parse_expr(lexer)?)),
+        }
+        if lexer.peek_matches(Lexeme::Comma) {
+            lexer.advance()?;
+        } else {
+            break;
+        }
+    }
+    close_list(location, lexer)?;
+    Ok(Expr::Comprehension {
+        span: location.to(&lexer.previous_token_end()),
+        output,
+        generators,
+        guards,
+    }
+    .into())
+}
+
+/// Parse a generator of a comprehension, `pattern <- source`, if the next tokens begin one.
+fn parse_generator<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Generator<'a>>> {
+    let checkpoint = lexer.checkpoint();
+    let pattern = match parse_predicate(lexer) {
+        Ok(Some(pattern)) if lexer.peek_matches(Lexeme::Operator("<-")) => pattern,
+        _ => {
+            lexer.restore(checkpoint);
+            return Ok(None);
+        }
+    };
+    lexer.advance()?;
+    pattern.check_or_patterns()?;
+    pattern.check_duplicate_bindings()?;
+    Ok(Some(Generator {
+        pattern: Box::new(pattern),
+        source: parse_expr(lexer)?,
+    }))
+}
+
+/// Consume the `]` closing the list expression at `location`.
+fn close_list<'a>(location: Location<'a>, lexer: &mut Lexer<'a>) -> ParseResult<'a, ()> {
+    if lexer.peek_matches(Lexeme::RSquare) {
+        return lexer.advance();
+    }
+    Err(ParseError::error(
+        ErrorCode::UnbalancedBracket,
+        location,
+        match lexer.peek() {
+            Token {
+                lexeme: Lexeme::Eof,
+                ..
+            } => "hit EOF before this list was closed".to_string(),
+            token => format!("expected , or ] to close this list, found {token}"),
+        },
+    ))
+}
+
+/// Whether the tokens after a `{` begin a record literal: either `}` straight away or a
+/// `field =`. Otherwise the braces are a block, so `{}` is the empty record rather than an empty
+/// block.
+fn starts_record(lexer: &mut Lexer) -> bool {
+    lexer.lookahead(|lexer| {
+        if lexer.skip_semicolon().is_err() {
+            return false;
+        }
+        match lexer.peek().lexeme {
+            Lexeme::RCurly => true,
+            Lexeme::Identifier(name) if !is_keyword(name) => {
+                lexer.advance().is_ok() && lexer.peek_matches(Lexeme::Equals)
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Parse the remainder of a record literal such as `{ name = "ada", age = 36 }`, after its
+/// opening `{` at `location`. Fields are separated by commas or newlines.
+fn parse_record_expr<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let mut fields: Vec<(Identifier<'a>, Expr<'a>)> = Vec::new();
+    loop {
+        lexer.skip_semicolon()?;
+        if lexer.peek_matches(Lexeme::RCurly) {
+            break;
+        }
+        let field = parse_identifier(lexer)?;
+        if fields.iter().any(|(other, _)| other.same_name(&field)) {
+            return Err(ParseError::error(
+                ErrorCode::InvalidExpression,
+                *field.get_location(),
+                format!(
+                    "field `{}` appears more than once in this record",
+                    field.name
+                ),
+            ));
+        }
+        lexer.chomp(Lexeme::Equals)?;
+        fields.push((field, parse_expr(lexer)?));
+        if lexer.peek_matches(Lexeme::Comma) {
+            lexer.advance()?;
+        }
+    }
+    lexer.advance()?;
+    Ok(Some(Expr::Record { location, fields }.into()))
+}
+
+fn is_prefix_operator(op: &str) -> bool {
+    op == "-" || op == "!"
+}
+
+/// Returns true when the token after the current operator token starts right where the operator
+/// ends, as in `-x` (but not `- x`).
+fn operator_is_adjacent_to_operand<'a>(
+    lexer: &mut Lexer<'a>,
+    op: &str,
+    location: Location<'a>,
+) -> bool {
+    lexer.lookahead(|lexer| {
+        if lexer.advance().is_err() {
+            return false;
+        }
+        let token = lexer.peek();
+        token.location.line == location.line
+            && token.location.col == location.col + op.chars().count() as i32
+    })
+}
+
+/// Whether the operator `op` at `location`, found between the arguments of a callsite, is a
+/// prefix operator applied to the next argument, as in `f -x`. It must be written against its
+/// operand, and a `-` must not be written against the argument before it, so that `x-y` and
+/// `x - y` both subtract.
+fn is_prefix_argument<'a>(lexer: &mut Lexer<'a>, op: &str, location: Location<'a>) -> bool {
+    let end = lexer.previous_token_end();
+    let after_operand = end.line == location.line && end.col + 1 == location.col;
+    is_prefix_operator(op)
+        && operator_is_adjacent_to_operand(lexer, op, location)
+        && !(op == "-" && after_operand)
+}
+
+/// Parse a prefix operator application. When the operator is directly adjacent to its operand
+/// (`-x`) it applies to a single term and binds tighter than application, so `f -x` is `f (-x)`.
+/// Otherwise (`- f x`) it applies to the whole following application, but still binds tighter
+/// than any binary operator.
+fn parse_unary_expr<'a>(
+    op: &'a str,
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let adjacent = operator_is_adjacent_to_operand(lexer, op, location);
+    lexer.advance()?;
+    let operand = if adjacent {
+        match parse_callsite_term(lexer)? {
+            Some(operand) => *operand,
+            None => {
+                return Err(ParseError::error(
+                    ErrorCode::InvalidExpression,
+                    location,
+                    format!("expected an operand after prefix {}", op),
+                ))
+            }
+        }
+    } else {
+        parse_callsite(lexer)?
+    };
+    Ok(Some(
+        Expr::UnaryOp {
+            op: Identifier::new(op, location),
+            operand: operand.into(),
+        }
+        .into(),
+    ))
+}
+
+/// Parse the string literal at `location` together with any which directly follow it, so that
+/// `"ab" "cd"` is `"abcd"` and a long string can be split over indented lines. The result is
+/// interpolated if any of the literals is. Literals on separate lines within braces are separate
+/// statements, since a newline there is a semicolon.
+fn parse_string_literals<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, Expr<'a>> {
+    let mut parts = Vec::new();
+    let mut interpolated = false;
+    loop {
+        match lexer.peek() {
+            Token {
+                lexeme: Lexeme::QuotedString(text),
+                ..
+            } => {
+                lexer.advance()?;
+                // Braces are doubled even in a string which embeds nothing.
+                let text = &text[1..text.len() - 1];
+                push_literal(&mut parts, &text.replace("{{", "{").replace("}}", "}"));
+            }
+            Token {
+                location,
+                lexeme: Lexeme::InterpString(text),
+                ..
+            } => {
+                lexer.advance()?;
+                interpolated = true;
+                for part in parse_interp_string(location, text, lexer)? {
+                    match part {
+                        InterpPart::Literal(text) => push_literal(&mut parts, &text),
+                        part => parts.push(part),
+                    }
+                }
+            }
+            _ => break,
+        }
+    }
+    if interpolated {
+        return Ok(Expr::Interp { location, parts });
+    }
+    let value = match parts.pop() {
+        Some(InterpPart::Literal(text)) => text,
+        _ => String::new(),
+    };
+    Ok(Expr::LiteralString { location, value })
+}
+
+/// Append `text` to the last of `parts` if that is text too.
+fn push_literal<'a>(parts: &mut Vec<InterpPart<'a>>, text: &str) {
+    match parts.last_mut() {
+        _ if text.is_empty() => (),
+        Some(InterpPart::Literal(last)) => last.push_str(text),
+        _ => parts.push(InterpPart::Literal(text.to_string())),
+    }
+}
+
+/// Split the interpolated string `text` at `location`, which includes its quotes, into parts.
+/// The lexer has already checked that its braces match.
+fn parse_interp_string<'a>(
+    location: Location<'a>,
+    text: &'a str,
+    lexer: &Lexer<'a>,
+) -> ParseResult<'a, Vec<InterpPart<'a>>> {
+    let inner = &text[1..text.len() - 1];
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    // The location of each character as it is read, starting from the opening quote.
+    let mut char_location = location;
+    let mut chars = inner.char_indices().peekable();
+    while let Some((index, ch)) = chars.next() {
+        char_location.advance(ch);
+        match ch {
+            '{' | '}' if chars.peek().is_some_and(|&(_, next)| next == ch) => {
+                chars.next();
+                char_location.advance(ch);
+                literal.push(ch);
+            }
+            '{' => {
+                let open_location = char_location;
+                let mut depth = 0;
+                let mut end = inner.len();
+                for (index, ch) in chars.by_ref() {
+                    char_location.advance(ch);
+                    match ch {
+                        '{' => depth += 1,
+                        '}' if depth == 0 => {
+                            end = index;
+                            break;
+                        }
+                        '}' => depth -= 1,
+                        _ => (),
+                    }
+                }
+                if !literal.is_empty() {
+                    parts.push(InterpPart::Literal(std::mem::take(&mut literal)));
+                }
+                let source = &inner[index + 1..end];
+                parts.push(InterpPart::Expr(parse_embedded_expr(
+                    open_location,
+                    source,
+                    lexer,
+                )?));
+            }
+            _ => literal.push(ch),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(InterpPart::Literal(literal));
+    }
+    Ok(parts)
+}
+
+/// Parse `source`, an expression embedded in a string just after the `{` at `location`. It is
+/// lexed on its own, but with the same fixities as the rest of the file.
+fn parse_embedded_expr<'a>(
+    location: Location<'a>,
+    source: &'a str,
+    lexer: &Lexer<'a>,
+) -> ParseResult<'a, Expr<'a>> {
+    if source.trim().is_empty() {
+        return Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            location,
+            "expected an expression between these braces",
+        ));
+    }
+    let mut embedded = lexer.embedded(location, source);
+    embedded.advance()?;
+    let expr = parse_expr(&mut embedded)?;
+    match embedded.peek() {
+        Token {
+            lexeme: Lexeme::Eof,
+            ..
+        } => Ok(expr),
+        token => Err(ParseError::unexpected(
+            token,
+            "`}` to end this embedded expression",
+        )),
+    }
+}
+
+/// Parse the rest of a qualified name such as `List::map`, after its first segment `first`.
+fn parse_path<'a>(first: Identifier<'a>, lexer: &mut Lexer<'a>) -> ParseResult<'a, Expr<'a>> {
+    let location = *first.get_location();
+    let mut segments = vec![first];
+    while lexer.peek_matches(Lexeme::PathSep) {
+        lexer.advance()?;
+        match lexer.peek() {
+            Token {
+                lexeme: Lexeme::Identifier(_),
+                ..
+            } => segments.push(parse_identifier(lexer)?),
+            token => return Err(ParseError::unexpected(token, "a name after ::")),
+        }
+    }
+    Ok(Expr::Path { location, segments })
+}
+
+/// Parse a single term of a callsite, including any field accesses, as in `order.customer.name`.
+/// Field access binds tighter than application, so `f x.y` is `f (x.y)`. A `.` is only a field
+/// access when it is written up against the field name; `f . g` is an ordinary binary operator.
+fn parse_callsite_term<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let mut term = match lexer.nested(parse_callsite_atom)? {
+        Some(term) => term,
+        None => return Ok(None),
+    };
+    if let Expr::Symbol { id } = term.as_ref() {
+        if lexer.peek_matches(Lexeme::PathSep) {
+            term = parse_path(id.clone(), lexer)?.into();
+        }
+    }
+    while let Token {
+        location,
+        lexeme: Lexeme::Operator("."),
+        ..
+    } = lexer.peek()
+    {
+        if !operator_is_adjacent_to_operand(lexer, ".", location) {
+            break;
+        }
+        lexer.advance()?;
+        match lexer.peek() {
+            Token {
+                location,
+                lexeme: Lexeme::Identifier(name),
+                ..
+            } if !is_keyword(name) => {
+                lexer.advance()?;
+                term = Expr::FieldAccess {
+                    subject: term,
+                    field: lexer.identifier(name, location),
+                }
+                .into();
+            }
+            token => return Err(ParseError::unexpected(token, "a field name after .")),
+        }
+    }
+    Ok(Some(term))
+}
+
+fn parse_callsite_atom<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    let Token {
+        location, lexeme, ..
+    } = lexer.peek();
+    match lexeme {
+        // A symbol reference.
+        Lexeme::Identifier(name) => {
+            if is_keyword(name) {
+                match name {
+                    "let" => {
+                        lexer.advance_mut()?;
+                        parse_let_expr(location, lexer)
+                    }
+                    "if" => {
+                        lexer.advance_mut()?;
+                        parse_if_expr(location, lexer)
+                    }
+                    "do" => {
+                        lexer.advance_mut()?;
+                        parse_do_block(location, lexer)
+                    }
+                    "match" => {
+                        let location = lexer.advance_mut()?;
+                        parse_match_expr(location, lexer)
+                    }
+                    // Any other keyword ends the expression, as `in` does a let binding.
+                    _ => Ok(None),
+                }
+            } else if let Some(value) = bool_literal(name) {
+                lexer.advance()?;
+                Ok(Some(Expr::LiteralBool { location, value }.into()))
+            } else {
+                lexer.advance()?;
+                Ok(Some(
+                    Expr::Symbol {
+                        id: lexer.identifier(name, location),
+                    }
+                    .into(),
+                ))
+            }
+        }
+        Lexeme::Backslash => {
+            lexer.advance()?;
+            parse_lambda_expr(location, lexer)
+        }
+        Lexeme::Semicolon => {
+            lexer.advance_mut()?;
+            Ok(None)
+        }
+        Lexeme::Equals => Ok(None),
+        Lexeme::Hole(text) => {
+            lexer.advance()?;
+            Ok(Some(
+                Expr::Hole {
+                    location,
+                    name: (text.len() > 1).then(|| &text[1..]),
+                }
+                .into(),
+            ))
+        }
+        Lexeme::LParen => {
+            lexer.advance()?;
+            parse_tuple_expr(location, lexer)
+        }
+        Lexeme::LSquare => {
+            lexer.advance()?;
+            parse_list_expr(location, lexer)
+        }
+        Lexeme::RParen | Lexeme::RSquare | Lexeme::RCurly | Lexeme::Comma => Ok(None),
+        Lexeme::HashSquare => Err(misplaced_attribute(location)),
+        Lexeme::LCurly => {
+            lexer.advance()?;
+            if starts_record(lexer) {
+                parse_record_expr(location, lexer)
+            } else {
+                parse_block_expr(location, lexer)
+            }
+        }
+        Lexeme::Operator(name) if is_prefix_operator(name) => {
+            parse_unary_expr(name, location, lexer)
+        }
+        // Any other operator here is applied as a function, as in `+ 1 2`, unless it belongs
+        // to the grammar.
+        Lexeme::Operator(name) if is_reserved_operator(name) => Err(ParseError::error(
+            ErrorCode::UnexpectedToken,
+            location,
+            format!(
+                "expected an expression, but found `{}`, which is reserved by the grammar \
+                 and cannot be used as a function",
+                name
+            ),
+        )),
+        Lexeme::Operator(name) => {
+            lexer.advance()?;
+            Ok(Some(
+                Expr::Symbol {
+                    id: Identifier::new(name, location),
+                }
+                .into(),
+            ))
+        }
+        Lexeme::QuotedString(_) | Lexeme::InterpString(_) => {
+            Ok(Some(parse_string_literals(location, lexer)?.into()))
+        }
+        Lexeme::Signed(value) => {
+            lexer.advance()?;
+            Ok(Some(Expr::LiteralInteger { location, value }.into()))
+        }
+        Lexeme::Float(value) => {
+            lexer.advance()?;
+            Ok(Some(Expr::LiteralFloat { location, value }.into()))
+        }
+        Lexeme::Char(value) => {
+            lexer.advance()?;
+            Ok(Some(Expr::LiteralChar { location, value }.into()))
+        }
+        Lexeme::Backtick(op) => Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            location,
+            format!(
+                "{} applies a function infix, so it needs an operand on each side; \
+                 to call it directly, drop the backticks",
+                op
+            ),
+        )),
+        Lexeme::Eof => Ok(None),
+        _ => Err(ParseError::not_impl(location)),
+    }
+    /*
+    parse_parentheses,
+    parse_string_literal,
+    parse_do_notation,
+    parse_if_then,
+    parse_match,
+    parse_number,
+    parse_identifier,
+    parse_ctor,
+    */
+}
+
+/// Arguments are callsite terms, except that an argument is never an operator (nor a backticked
+/// name) and never begins a new match arm. This is what lets `match x 1 => a 2 => b` end the
+/// first arm's body before `2`.
+fn parse_callsite_argument<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Box<Expr<'a>>>> {
+    if starts_match_arm(lexer)
+        || section_operator(lexer).is_some()
+        || ternary_operator(lexer).is_some()
+        || matches!(
+            lexer.peek(),
+            Token {
+                lexeme: Lexeme::Backtick(_),
+                ..
+            }
+        )
+    {
+        Ok(None)
+    } else if let Token {
+        location,
+        lexeme: Lexeme::Operator(op),
+        ..
+    } = lexer.peek()
+    {
+        if is_prefix_argument(lexer, op, location) {
+            parse_callsite_term(lexer)
+        } else {
+            // Operators between arguments are binary operators, handled by parse_expr.
+            Ok(None)
+        }
+    } else {
+        parse_callsite_term(lexer)
+    }
+}
+
+fn parse_callsite<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Expr<'a>> {
+    lexer.skip_semicolon()?;
+    let maybe_function = parse_callsite_term(lexer)?;
+
+    match maybe_function {
+        Some(function) => {
+            let arguments = parse_many(parse_callsite_argument, lexer)?;
+            if arguments.is_empty() {
+                Ok(*function)
+            } else {
+                Ok(Expr::Callsite {
+                    span: function.get_location().to(&lexer.previous_token_end()),
+                    function,
+                    arguments,
+                })
+            }
+        }
+        None => match lexer.peek() {
+            Token {
+                location,
+                lexeme: Lexeme::Identifier(name),
+                ..
+            } if is_keyword(name) => Err(ParseError::error(
+                ErrorCode::InvalidExpression,
+                location,
+                format!("expected an expression, but found the keyword `{}`", name),
+            )),
+            _ => Err(ParseError::error(
+                ErrorCode::InvalidExpression,
+                lexer.location,
+                "missing function callsite expression",
+            )),
+        },
+    }
+}
+
+/// The range operator at the lexer, if any, and whether it is inclusive.
+fn range_operator<'a>(lexer: &Lexer<'a>) -> Option<(Location<'a>, bool)> {
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Operator(".."),
+            ..
+        } => Some((location, false)),
+        Token {
+            location,
+            lexeme: Lexeme::Operator("..="),
+            ..
+        } => Some((location, true)),
+        _ => None,
+    }
+}
+
+/// Whether the token after a decl's `=` ends the decl or can only start another item, so that the
+/// decl has no body at all.
+fn at_missing_body<'a>(lexer: &Lexer<'a>) -> bool {
+    match lexer.peek().lexeme {
+        Lexeme::Eof | Lexeme::Semicolon | Lexeme::RCurly | Lexeme::HashSquare => true,
+        Lexeme::Identifier(name) => {
+            matches!(name, "data" | "import" | "module") || fixity_keyword(name).is_some()
+        }
+        _ => false,
+    }
+}
+
+/// Whether the next token cannot begin an expression, so that a range before it such as `1..` is
+/// open-ended.
+fn at_expression_end(lexer: &mut Lexer) -> bool {
+    match lexer.peek().lexeme {
+        Lexeme::RParen
+        | Lexeme::RSquare
+        | Lexeme::RCurly
+        | Lexeme::Comma
+        | Lexeme::Semicolon
+        | Lexeme::Equals
+        | Lexeme::Eof => true,
+        Lexeme::Identifier(name) => is_keyword(name) && !matches!(name, "let" | "match" | "do"),
+        Lexeme::Operator(op) => !is_prefix_operator(op),
+        _ => starts_match_arm(lexer),
+    }
+}
+
+/// The location of the `?` at the lexer if it begins the middle of a conditional `c ? a : b`,
+/// which it does when a `:` follows it before the end of the expression. A lone `?` is otherwise
+/// a hole, so `f ? x` still applies `f` to a hole; where a `:` follows, as in `g ?h 1 : xs`, the
+/// hole has to be named.
+///
+/// When the scan finds no `:`, where it stopped is kept on the lexer, so that the holes after
+/// this one and before that end are told apart without scanning the same tokens again.
+fn ternary_operator<'a>(lexer: &mut Lexer<'a>) -> Option<Location<'a>> {
+    let location = match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Hole("?"),
+            ..
+        } => location,
+        _ => return None,
+    };
+    let nesting_depth = lexer.nesting_depth();
+    if let Some((depth, end)) = lexer.no_ternary_before {
+        if depth == nesting_depth && (location.line, location.col) < (end.line, end.col) {
+            return None;
+        }
+    }
+    let scan = lexer.lookahead(|lexer| {
+        let mut depth = 0usize;
+        loop {
+            lexer.advance().ok()?;
+            let token = lexer.peek();
+            match token.lexeme {
+                Lexeme::LParen | Lexeme::LSquare | Lexeme::LCurly | Lexeme::HashSquare => {
+                    depth += 1
+                }
+                Lexeme::RParen | Lexeme::RSquare | Lexeme::RCurly if depth == 0 => {
+                    return Some(Err(token.location))
+                }
+                Lexeme::RParen | Lexeme::RSquare | Lexeme::RCurly => depth -= 1,
+                Lexeme::Eof => return Some(Err(token.location)),
+                Lexeme::Operator(":") if depth == 0 => return Some(Ok(location)),
+                Lexeme::Semicolon | Lexeme::Comma | Lexeme::Equals | Lexeme::Operator("=>")
+                    if depth == 0 =>
+                {
+                    return Some(Err(token.location))
+                }
+                Lexeme::Identifier("then" | "else" | "in") if depth == 0 => {
+                    return Some(Err(token.location))
+                }
+                _ => (),
+            }
+        }
+    });
+    match scan? {
+        Ok(location) => Some(location),
+        Err(end) => {
+            lexer.no_ternary_before = Some((nesting_depth, end));
+            None
+        }
+    }
+}
+
+/// Parse a full expression: callsites joined by binary operators, optionally forming either end
+/// of a range, optionally the condition of a conditional `c ? a : b`. A conditional binds more
+/// loosely than a range and groups to the right, and is sugar for `if c then a else b`.
+fn parse_expr<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Expr<'a>> {
+    // A `:` within brackets or a nested expression is a cons again.
+    let in_ternary = std::mem::replace(&mut lexer.in_ternary, false);
+    let expr = parse_conditional_expr(lexer);
+    lexer.in_ternary = in_ternary;
+    expr
+}
+
+fn parse_conditional_expr<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Expr<'a>> {
+    let condition = parse_range_expr(lexer)?;
+    let operator_location = match ternary_operator(lexer) {
+        Some(location) => location,
+        None => return Ok(condition),
+    };
+    lexer.advance()?;
+    let in_ternary = std::mem::replace(&mut lexer.in_ternary, true);
+    let then_branch = lexer.nested(parse_conditional_expr);
+    lexer.in_ternary = in_ternary;
+    let then_branch = then_branch?;
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Operator(":"),
+            ..
+        } => lexer.advance()?,
+        Token {
+            lexeme: Lexeme::Eof,
+            ..
+        } => {
+            return Err(ParseError::error(
+                ErrorCode::UnexpectedEof,
+                operator_location,
+                "hit EOF but expected `:` to continue this conditional",
+            ))
+        }
+        token => {
+            return Err(ParseError::unexpected(
+                token,
+                format!("`:` to continue the conditional at {}", operator_location),
+            ))
+        }
+    }
+    let else_branch = lexer.nested(parse_conditional_expr)?;
+    Ok(Expr::If {
+        location: *condition.get_location(),
+        condition: condition.into(),
+        then_branch: then_branch.into(),
+        else_branch: else_branch.into(),
+    })
+}
+
+/// Parse callsites joined by binary operators, optionally forming either end of a range. Ranges
+/// bind more loosely than any binary operator, so `0..n + 1` ends at `n + 1`.
+fn parse_range_expr<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Expr<'a>> {
+    let start = match range_operator(lexer) {
+        Some(_) => None,
+        None => {
+            let start = parse_binary_expr(lexer, 0, None)?;
+            if range_operator(lexer).is_none() {
+                return Ok(start);
+            }
+            Some(Box::new(start))
+        }
+    };
+    let (operator_location, inclusive) = range_operator(lexer).unwrap();
+    lexer.advance()?;
+    let end = if at_expression_end(lexer) {
+        if inclusive {
+            return Err(ParseError::error(
+                ErrorCode::InvalidExpression,
+                operator_location,
+                "an inclusive range needs an end, as in `1..=10`",
+            ));
+        }
+        None
+    } else {
+        Some(Box::new(parse_binary_expr(lexer, 0, None)?))
+    };
+    if start.is_none() && end.is_none() {
+        return Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            operator_location,
+            "a range needs a start or an end",
+        ));
+    }
+    if let Some((location, _)) = range_operator(lexer) {
+        return Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            location,
+            "a range has only one `..`; to give it a step, write its second element \
+             as in `[0, 2..20]`",
+        ));
+    }
+    Ok(Expr::Range {
+        location: start
+            .as_ref()
+            .map_or(operator_location, |start| *start.get_location()),
+        start,
+        step: None,
+        end,
+        inclusive,
+    })
+}
+
+/// Parse operands joined by operators of at least `min_precedence`, using the lexer's fixity
+/// table. `enclosing` is the operator whose right-hand side this is, if any; an operator of the
+/// same precedence which groups differently cannot be mixed with it without parentheses.
+fn parse_binary_expr<'a>(
+    lexer: &mut Lexer<'a>,
+    min_precedence: u8,
+    enclosing: Option<(&'a str, Fixity)>,
+) -> ParseResult<'a, Expr<'a>> {
+    let mut levels = 0;
+    let expr = parse_binary_operands(lexer, min_precedence, enclosing, &mut levels);
+    lexer.ascend(levels);
+    expr
+}
+
+/// The loop of `parse_binary_expr`. Each operator it takes makes the left operand of the next
+/// one a level deeper, so it counts the levels toward the lexer's nesting limit in `levels`.
+fn parse_binary_operands<'a>(
+    lexer: &mut Lexer<'a>,
+    min_precedence: u8,
+    enclosing: Option<(&'a str, Fixity)>,
+    levels: &mut usize,
+) -> ParseResult<'a, Expr<'a>> {
+    let mut lhs = parse_callsite(lexer)?;
+    let mut previous = enclosing;
+    loop {
+        let (op, location) = match lexer.peek() {
+            Token {
+                location,
+                lexeme: Lexeme::Operator(op) | Lexeme::Backtick(op),
+                ..
+            } if !(is_reserved_operator(op) || op == ":" && lexer.in_ternary) => (op, location),
+            Token {
+                location,
+                lexeme: Lexeme::Operator("<-"),
+                ..
+            } => {
+                return Err(ParseError::error(
+                    ErrorCode::InvalidExpression,
+                    location,
+                    "`<-` can only bind a name as a statement in a do-block, \
+                     as in `do { x <- action; f x }`",
+                ))
+            }
+            _ => return Ok(lhs),
+        };
+        if section_operator(lexer).is_some() {
+            // The operand is missing, as in the section `(1 +)`.
+            return Ok(lhs);
+        }
+        let backticked = op.starts_with('`');
+        let fixity = match lexer.fixities.get(op) {
+            Some(fixity) => fixity,
+            None if backticked => BACKTICK_FIXITY,
+            None => {
+                return Err(ParseError::error(
+                    ErrorCode::InvalidExpression,
+                    location,
+                    format!(
+                        "{} has no declared fixity; declare one with `infixl 9 {}` or similar",
+                        op, op
+                    ),
+                ))
+            }
+        };
+        if fixity.precedence < min_precedence {
+            return Ok(lhs);
+        }
+        if op == "-" && starts_match_arm(lexer) {
+            // A negative literal pattern beginning the next arm, as in `0 => a - 1 => b`.
+            return Ok(lhs);
+        }
+        if let Some((previous_op, previous_fixity)) = previous {
+            if previous_fixity.precedence == fixity.precedence
+                && (previous_fixity.associativity != fixity.associativity
+                    || fixity.associativity == Associativity::None)
+            {
+                return Err(ParseError::error(
+                    ErrorCode::InvalidExpression,
+                    location,
+                    format!(
+                        "cannot mix {} ({}) and {} ({}) in the same expression; add parentheses",
+                        previous_op, previous_fixity, op, fixity
+                    ),
+                ));
+            }
+        }
+        previous = Some((op, fixity));
+        lexer.advance()?;
+
+        let token = lexer.peek();
+        if let Lexeme::Operator(next_op) = token.lexeme {
+            if is_prefix_operator(next_op) {
+                // A prefix operator may begin the right-hand side, as in `a - -b`.
+            } else {
+                return Err(ParseError::unexpected(
+                    token,
+                    format!("an operand after {}", op),
+                ));
+            }
+        }
+
+        let min_precedence = match fixity.associativity {
+            Associativity::Right => fixity.precedence,
+            Associativity::Left | Associativity::None => fixity.precedence + 1,
+        };
+        lexer.descend()?;
+        *levels += 1;
+        let rhs =
+            lexer.nested(|lexer| parse_binary_expr(lexer, min_precedence, Some((op, fixity))))?;
+        lhs = if backticked {
+            let mut name_location = location;
+            name_location.col += 1;
+            Expr::Callsite {
+                span: lhs.get_location().to(&lexer.previous_token_end()),
+                function: Expr::Symbol {
+                    id: Identifier::new(&op[1..op.len() - 1], name_location),
+                }
+                .into(),
+                arguments: vec![lhs.into(), rhs.into()],
+            }
+        } else {
+            Expr::BinaryOp {
+                op: Identifier::new(op, location),
+                lhs: lhs.into(),
+                rhs: rhs.into(),
+            }
+        };
+    }
+}
+
+/// Report the second of any two identifiers in `ids` with the same name, noting where the first
+/// was bound.
+fn check_duplicate_ids<'a>(ids: &[&Identifier<'a>], what: &str) -> ParseResult<'a, ()> {
+    for (index, id) in ids.iter().enumerate() {
+        if let Some(first) = ids[..index].iter().find(|other| other.same_name(id)) {
+            return Err(ParseError::error(
+                ErrorCode::InvalidPattern,
+                *id.get_location(),
+                format!("`{}` is bound more than once in {}", id.name, what),
+            )
+            .with_note(
+                *first.get_location(),
+                format!("`{}` is first bound here", id.name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check the parameters of a declaration head or lambda. Each must be a valid pattern on its
+/// own, and together they may bind each name at most once, since `f x x = x` is ambiguous.
+fn check_parameters<'a>(params: &[Box<Predicate<'a>>]) -> ParseResult<'a, ()> {
+    let mut ids = Vec::new();
+    for param in params {
+        param.check_or_patterns()?;
+        param.bound_ids(&mut ids);
+    }
+    check_duplicate_ids(&ids, "these parameters")
+}
+
+pub fn parse_decl<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Decl<'a>>> {
+    lexer.skip_semicolon()?;
+    if let Token {
+        location,
+        lexeme: Lexeme::HashSquare,
+        ..
+    } = lexer.peek()
+    {
+        return Err(misplaced_attribute(location));
+    }
+    reject_keyword(lexer)?;
+    let id = match maybe_id(lexer)? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let predicates = parse_predicates(lexer)?;
+    reject_keyword(lexer)?;
+    check_parameters(&predicates)?;
+    if let Token {
+        location,
+        lexeme: Lexeme::Operator("=="),
+        ..
+    } = lexer.peek()
+    {
+        return Err(ParseError::error(
+            ErrorCode::UnexpectedToken,
+            location,
+            format!(
+                "expected `=` to define `{}`, but found `==`, which compares two values",
+                id.name
+            ),
+        ));
+    }
+    let equals_location = lexer.peek().location;
+    lexer.chomp(Lexeme::Equals)?;
+    if at_missing_body(lexer) {
+        return Err(ParseError::error(
+            ErrorCode::InvalidExpression,
+            equals_location,
+            format!("declaration `{}` has no body", id.name),
+        )
+        .with_note(
+            *id.get_location(),
+            format!("`{}` is declared here", id.name),
+        ));
+    }
+    let expr = parse_expr(lexer)?;
+    let where_decls = lexer.nested(parse_where_decls)?;
+    Ok(Some(Decl {
+        attrs: Vec::new(),
+        clauses: vec![Clause {
+            location: *id.get_location(),
+            predicates: predicates.into_iter().map(|predicate| *predicate).collect(),
+            body: expr,
+            where_decls,
+        }],
+        id,
+    }))
+}
+
+/// Parse the `where` block after a clause's body, if there is one. The block is either a single
+/// decl, as in `f x = g x where g y = y + 1`, or several within braces, separated by semicolons
+/// or newlines. Adjacent clauses of the same name within the block are grouped.
+fn parse_where_decls<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Vec<Decl<'a>>> {
+    let location = match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Identifier("where"),
+            ..
+        } => location,
+        _ => return Ok(Vec::new()),
+    };
+    lexer.advance()?;
+    let mut items = Vec::new();
+    if lexer.peek_matches(Lexeme::LCurly) {
+        lexer.advance()?;
+        loop {
+            lexer.skip_semicolon()?;
+            if lexer.peek_matches(Lexeme::RCurly) {
+                break;
+            }
+            match parse_decl(lexer)? {
+                Some(decl) => items.push(Item::Decl(decl)),
+                None => {
+                    return Err(match lexer.peek() {
+                        Token {
+                            lexeme: Lexeme::Eof,
+                            ..
+                        } => ParseError::error(
+                            ErrorCode::UnbalancedBracket,
+                            location,
+                            "hit EOF before this where block was closed",
+                        ),
+                        token => ParseError::unexpected(token, "a decl or `}` in this where block"),
+                    })
+                }
+            }
+        }
+        lexer.advance()?;
+    } else {
+        match parse_decl(lexer)? {
+            Some(decl) => items.push(Item::Decl(decl)),
+            None => {
+                return Err(ParseError::error(
+                    ErrorCode::InvalidExpression,
+                    location,
+                    "expected a decl after `where`",
+                ))
+            }
+        }
+    }
+    let (items, mut errors) = group_clauses(items);
+    if !errors.is_empty() {
+        return Err(errors.remove(0));
+    }
+    Ok(items
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Decl(decl) => Some(decl),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The error for an attribute at `location` which is not attached to a top-level decl.
+fn misplaced_attribute(location: Location<'_>) -> ParseError<'_> {
+    ParseError::error(
+        ErrorCode::UnexpectedToken,
+        location,
+        "attributes can only be attached to a top-level decl",
+    )
+}
+
+/// Parse the attributes before a decl, such as `#[inline]`, each of which may be followed by a
+/// newline. An attribute's arguments are callsite terms, as in `#[deprecated "use foo2"]`.
+fn parse_attributes<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Vec<Attribute<'a>>> {
+    let mut attrs = Vec::new();
+    while let Token {
+        location,
+        lexeme: Lexeme::HashSquare,
+        ..
+    } = lexer.peek()
+    {
+        lexer.advance()?;
+        let name = parse_identifier(lexer)?;
+        let mut args = Vec::new();
+        loop {
+            match lexer.peek() {
+                Token {
+                    lexeme: Lexeme::RSquare,
+                    ..
+                } => break,
+                Token {
+                    lexeme: Lexeme::Eof,
+                    ..
+                } => {
+                    return Err(ParseError::error(
+                        ErrorCode::UnbalancedBracket,
+                        location,
+                        "hit EOF before this attribute was closed",
+                    ))
+                }
+                token => match parse_callsite_term(lexer)? {
+                    Some(arg) => args.push(*arg),
+                    None => {
+                        return Err(ParseError::unexpected(
+                            token,
+                            "an argument or `]` to close this attribute",
+                        ))
+                    }
+                },
+            }
+        }
+        lexer.advance()?;
+        lexer.skip_semicolon()?;
+        attrs.push(Attribute {
+            location,
+            name,
+            args,
+        });
+    }
+    Ok(attrs)
+}
+
+/// Merge each run of adjacent decls with the same name into a single decl with several clauses,
+/// as in `len [] = 0` followed by `len (x : xs) = add 1 (len xs)`. Any other item between two
+/// decls ends the run. Returns an error for each clause whose arity differs from the first's.
+pub fn group_clauses<'a>(items: Vec<Item<'a>>) -> (Vec<Item<'a>>, Vec<ParseError<'a>>) {
+    let mut grouped: Vec<Item<'a>> = Vec::new();
+    let mut errors = Vec::new();
+    for item in items {
+        let decl = match (item, grouped.last_mut()) {
+            (Item::Decl(decl), Some(Item::Decl(previous))) if previous.id.same_name(&decl.id) => {
+                let (first_location, arity) = {
+                    let first = &previous.clauses[0];
+                    (first.location, first.predicates.len())
+                };
+                for clause in decl.clauses {
+                    if clause.predicates.len() != arity {
+                        errors.push(ParseError::error(
+                            ErrorCode::ArityMismatch,
+                            clause.location,
+                            format!(
+                                "this clause of `{}` has {} parameter(s), but the clause at {} has {}",
+                                decl.id.name,
+                                clause.predicates.len(),
+                                first_location,
+                                arity
+                            ),
+                        ));
+                    }
+                    previous.clauses.push(clause);
+                }
+                previous.attrs.extend(decl.attrs);
+                continue;
+            }
+            (item, _) => item,
+        };
+        grouped.push(decl);
+    }
+    (grouped, errors)
+}
+
+/// Parse an uppercase name, such as that of a type or a constructor. `what` describes the name for
+/// errors.
+fn parse_uppercase_identifier<'a>(
+    lexer: &mut Lexer<'a>,
+    what: &str,
+) -> ParseResult<'a, Identifier<'a>> {
+    match lexer.peek() {
+        Token {
+            location,
+            lexeme: Lexeme::Identifier(name),
+            ..
+        } if name.chars().next().is_some_and(char::is_uppercase) => {
+            lexer.advance()?;
+            Ok(lexer.identifier(name, location))
+        }
+        token => Err(ParseError::unexpected(token, what)),
+    }
+}
+
+/// Parse the rest of a data declaration, after the `data` keyword at `location`. Constructors
+/// are separated by `|`, and each takes zero or more positional arguments.
+fn parse_data_decl<'a>(
+    location: Location<'a>,
+    lexer: &mut Lexer<'a>,
+) -> ParseResult<'a, DataDecl<'a>> {
+    let name = parse_uppercase_identifier(lexer, "the name of the data type")?;
+    let mut type_params = Vec::new();
+    while let Token {
+        location,
+        lexeme: Lexeme::Identifier(param),
+        ..
+    } = lexer.peek()
+    {
+        if is_keyword(param) || param.chars().next().is_some_and(char::is_uppercase) {
+            break;
+        }
+        lexer.advance()?;
+        type_params.push(lexer.identifier(param, location));
+    }
+
+    let mut ctors = Vec::new();
+    // A data type without constructors has no `=`.
+    if lexer.peek_matches(Lexeme::Equals) {
+        loop {
+            lexer.advance()?;
+            let ctor = parse_uppercase_identifier(lexer, "a constructor name")?;
+            let mut arguments = Vec::new();
+            while let Some(argument) = parse_type_atom(lexer)? {
+                arguments.push(argument);
+            }
+            ctors.push((ctor, arguments));
+            if !lexer.peek_matches(Lexeme::Operator("|")) {
+                break;
+            }
+        }
+    }
+    Ok(DataDecl {
+        location,
+        name,
+        type_params,
+        ctors,
+    })
+}
+
+/// Parse a top-level item: a data declaration or a value declaration.
+pub fn parse_item<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<Item<'a>>> {
+    lexer.skip_semicolon()?;
+    let attrs = parse_attributes(lexer)?;
+    if let Some(attr) = attrs.first() {
+        // Only a decl can follow, so an item keyword or EOF leaves the attributes dangling.
+        return match lexer.peek() {
+            Token {
+                lexeme: Lexeme::Identifier(name),
+                ..
+            } if is_keyword(name) => Err(misplaced_attribute(attr.location)),
+            _ => match parse_decl(lexer)? {
+                Some(mut decl) => {
+                    decl.attrs = attrs;
+                    Ok(Some(Item::Decl(decl)))
+                }
+                None => Err(misplaced_attribute(attr.location)),
+            },
+        };
+    }
+    if let Token {
+        location,
+        lexeme: Lexeme::Identifier("data"),
+        ..
+    } = lexer.peek()
+    {
+        lexer.advance()?;
+        return Ok(Some(Item::Data(parse_data_decl(location, lexer)?)));
+    }
+    if let Token {
+        location,
+        lexeme: Lexeme::Identifier("import"),
+        ..
+    } = lexer.peek()
+    {
+        lexer.advance()?;
+        return Ok(Some(Item::Import(parse_import(location, lexer)?)));
+    }
+    if let Token {
+        location,
+        lexeme: Lexeme::Identifier(keyword),
+        ..
+    } = lexer.peek()
+    {
+        if let Some(associativity) = fixity_keyword(keyword) {
+            lexer.advance()?;
+            return Ok(Some(Item::Fixity(parse_fixity_decl(
+                location,
+                associativity,
+                lexer,
+            )?)));
+        }
+    }
+    if let Token {
+        location,
+        lexeme: Lexeme::Identifier("module"),
+        ..
+    } = lexer.peek()
+    {
+        return Err(ParseError::error(
+            ErrorCode::UnexpectedToken,
+            location,
+            "a module header must come before everything else in the file",
+        ));
+    }
+    Ok(parse_decl(lexer)?.map(Item::Decl))
+}
+
+/// The error for a token where an item should start, or nothing at EOF. After the last item of a
+/// file, anything left over is an error rather than being silently ignored.
+pub(crate) fn expect_item_or_eof<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, ()> {
+    lexer.skip_semicolon()?;
+    match lexer.peek() {
+        Token {
+            lexeme: Lexeme::Eof,
+            ..
+        } => Ok(()),
+        token => Err(ParseError::unexpected(token, "a declaration")),
+    }
+}
+
+/// Parse items until EOF, recording errors in `diagnostics` rather than stopping at the first
+/// one. After a broken item, or a token which cannot start one, parsing resumes at the next item.
+pub fn parse_items<'a>(lexer: &mut Lexer<'a>, diagnostics: &mut Diagnostics<'a>) -> Vec<Item<'a>> {
+    let mut items = Vec::new();
+    loop {
+        // As in parse_many, rewind a parser which consumed tokens before finding no item.
+        let checkpoint = lexer.checkpoint();
+        let item = parse_item(lexer).and_then(|item| match item {
+            Some(item) => Ok(Some(item)),
+            None => {
+                lexer.restore(checkpoint);
+                expect_item_or_eof(lexer).map(|()| None)
+            }
+        });
+        match item {
+            Ok(Some(item)) => {
+                if let Item::Decl(decl) = &item {
+                    decl.lint(diagnostics);
+                }
+                items.push(item);
+            }
+            Ok(None) => return items,
+            Err(err) => {
+                diagnostics.error(err);
+                // If the lexer itself cannot get past the error there is nothing left to recover.
+                if lexer.recover_to_semicolon().is_err() {
+                    return items;
+                }
+            }
+        }
+    }
+}
+
+pub fn parse_many<'a, T, P>(parser: P, lexer: &mut Lexer<'a>) -> Result<Vec<T>, ParseError<'a>>
+where
+    T: 'a + std::fmt::Debug + HasLocation<'a>,
+    P: 'a + Fn(&mut Lexer<'a>) -> Result<Option<T>, ParseError<'a>>,
+{
+    let mut objects = Vec::new();
+    loop {
+        // Parsers may consume tokens before deciding there is nothing for them, so rewind.
+        let checkpoint = lexer.checkpoint();
+        match parser(lexer)? {
+            Some(object) => {
+                // A parser which succeeds without consuming anything would loop forever.
+                if !lexer.has_advanced_since(&checkpoint) {
+                    return Err(ParseError::error(
+                        ErrorCode::Internal,
+                        lexer.location,
+                        "parser made no progress",
+                    ));
+                }
+                // let loc = object.get_location();
+                // println!("{}: info: found a thing! {:?}", loc, object);
+                objects.push(object);
+            }
+            None => {
+                lexer.restore(checkpoint);
+                return Ok(objects);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorLevel;
+    use crate::interner::Interner;
+    use crate::lexer::DEFAULT_MAX_DEPTH;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Parse `input` as a single single-clause decl, returning its clause.
+    fn parse_one(input: &'static str) -> Clause<'static> {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance().unwrap();
+        let mut decls = parse_many(parse_decl, &mut lexer).unwrap();
+        assert_eq!(decls.len(), 1);
+        assert_eq!(decls[0].clauses.len(), 1);
+        decls.remove(0).clauses.remove(0)
+    }
+
+    /// A compact rendering of an expression's structure for assertions.
+    fn shape(expr: &Expr) -> String {
+        match expr {
+            Expr::Symbol { id } => id.name.to_string(),
+            Expr::LiteralInteger { value, .. } => value.to_string(),
+            Expr::Callsite {
+                function,
+                arguments,
+                ..
+            } => {
+                let arguments: Vec<String> = arguments.iter().map(|arg| shape(arg)).collect();
+                format!("({} {})", shape(function), arguments.join(" "))
+            }
+            Expr::BinaryOp { op, lhs, rhs } => {
+                format!("({} {} {})", op.name, shape(lhs), shape(rhs))
+            }
+            Expr::UnaryOp { op, operand } => format!("(unary {} {})", op.name, shape(operand)),
+            Expr::LiteralString { value, .. } => format!("\"{}\"", value),
+            Expr::LiteralChar { value, .. } => char_source(*value),
+            Expr::LiteralBool { value, .. } => bool_source(*value).to_string(),
+            Expr::Path { segments, .. } => path_source(segments),
+            Expr::FieldAccess { subject, field } => format!("{}.{}", shape(subject), field.name),
+            Expr::Record { fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!(" {}={}", field.name, shape(value)))
+                    .collect();
+                format!("{{{} }}", fields.concat())
+            }
+            Expr::TupleCtor { dims, .. } => {
+                let dims: Vec<String> = dims.iter().map(|dim| shape(dim)).collect();
+                format!(
+                    "(tuple{})",
+                    dims.iter().map(|dim| format!(" {dim}")).collect::<String>()
+                )
+            }
+            expr => format!("{:?}", expr),
+        }
+    }
+
+    #[test]
+    fn sexpr_of_a_literal() {
+        assert_eq!(to_sexpr(&parse_one("f = 42").body), "(lit-int 42)");
+        assert_eq!(
+            to_sexpr(&parse_one("f = \"hi\"").body),
+            "(lit-string \"hi\")"
+        );
+    }
+
+    #[test]
+    fn sexpr_of_a_callsite() {
+        assert_eq!(
+            to_sexpr(&parse_one("f x = g x 1 (h, 2.5)").body),
+            "(callsite (symbol g) (symbol x) (lit-int 1) (tuple (symbol h) (lit-float 2.5)))"
+        );
+    }
+
+    #[test]
+    fn sexpr_of_a_let_expression() {
+        assert_eq!(
+            to_sexpr(&parse_one("f = let y = -x in y * 2").body),
+            "(let y (unary-op - (symbol x)) (binary-op * (symbol y) (lit-int 2)))"
+        );
+    }
+
+    #[test]
+    fn sexpr_of_a_match() {
+        assert_eq!(
+            to_sexpr(&parse_one("f m = match m Just (x, _) if x > 0 => x _ => 0").body),
+            "(match (symbol m) \
+             (arm (pat-ctor Just (pat-tuple (pat-bind x) (pat-bind _))) \
+             (guard (binary-op > (symbol x) (lit-int 0))) (symbol x)) \
+             (arm (pat-bind _) (lit-int 0)))"
+        );
+    }
+
+    fn parse_decls(input: &str) -> Vec<Decl<'_>> {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance().unwrap();
+        parse_many(parse_decl, &mut lexer).unwrap()
+    }
+
+    /// A decl's structure without locations, for comparing trees parsed from different sources.
+    fn decl_sexpr(decl: &Decl) -> String {
+        sexpr(
+            "decl",
+            std::iter::once(decl.id.name.to_string()).chain(decl.clauses.iter().map(|clause| {
+                sexpr(
+                    "clause",
+                    clause
+                        .predicates
+                        .iter()
+                        .map(|predicate| predicate_to_sexpr(predicate))
+                        .chain(std::iter::once(to_sexpr(&clause.body)))
+                        .chain(clause.where_decls.iter().map(decl_sexpr)),
+                )
+            })),
+        )
+    }
+
+    #[test]
+    fn pretty_print_round_trips() {
+        let source = "\
+            f x (Just y) [a, b] (h : t) = g x (y + 1) * 2 - -x\n\
+            g = let x = \\a b -> a $ b 1 in x.left.right (1, \"two\", 3.5)\n\
+            h (Pair Nothing _) = - f x : xs ++ { name = n, age = 36 } : ys\n\
+            k m = match m (Just x | Left x) if x > 0 => (match x 1 => a) 0.0 => -2 _ => h (- m) m\n\
+            l n@{ name = Just _, .. } = (f 1) (n, ) (a - b) - c ^ (d ^ e) ^ f\n\
+            r = let rec go n = stop n; stop n = go n in go 1\n\
+            c ('\\n', x) = g 'a' '\\'' '\\u{7}' x\n\
+            not b = match b True => False False => True\n\
+            m xs = List::map Char::to_upper xs\n\
+            s xs = fold (+) 0 (map (* 2) xs) (x ++)\n\
+            q n = g (0..n + 1) (1..) (..=n)\n\
+            d ps = map (\\(a, Just b) -> a + b) ps\n\
+            w x = g x where { g 0 = 1; g y = h y where h z = z * x }\n\
+            i a b = (if a then 1 else 2) + (if b then if a then 3 else 4 else 5)\n\
+            t x = \"{{x}} = {x + 1}, {g x}\"\n\
+            u p = let (a, (b, _)) = p; Pair c d = b in a + c * d\n\
+            v x = g do { let y = x; h y; z <- k y; y + z }\n\
+            b x = f { log x; let y = x + 1; y * 2 } { x }\n\
+            e xs = [[], [1, 2], [x * 2 | Just x <- xs, x > 0], [(a, b) | ok, (a, b) <- zip xs xs]]\n\
+            o n = g [1..n] [0, 2..=n * 2] [(0..n)] [(1..), 2] (n, [n, n - 1..0])\n\
+            j x = g \"a{{\" \"b\" (\"c\" \"{x}\" \"d\")\n";
+        let decls = parse_decls(source);
+        let printed = pretty_print(&decls, &FixityTable::builtin());
+        let reparsed = parse_decls(&printed);
+        assert_eq!(decls.len(), reparsed.len());
+        for (decl, reparsed) in decls.iter().zip(reparsed.iter()) {
+            assert_eq!(decl_sexpr(decl), decl_sexpr(reparsed), "{}", printed);
+        }
+        assert_eq!(pretty_print(&reparsed, &FixityTable::builtin()), printed);
+    }
+
+    #[test]
+    fn pretty_print_uses_minimal_parentheses() {
+        let decls =
+            parse_decls("f x = ((a + (b * c)) - ((d - e)))\ng (Just (x)) = (((f x) (y z)))");
+        assert_eq!(
+            pretty_print(&decls, &FixityTable::builtin()),
+            "f x = a + b * c - (d - e)\ng (Just x) = (f x) (y z)\n"
+        );
+    }
+
+    #[derive(Default)]
+    struct SymbolCounter {
+        symbols: usize,
+        bindings: usize,
+    }
+
+    impl<'a> Visitor<'a> for SymbolCounter {
+        fn visit_expr(&mut self, expr: &Expr<'a>) {
+            if let Expr::Symbol { .. } = expr {
+                self.symbols += 1;
+            }
+            walk_expr(self, expr)
+        }
+
+        fn visit_predicate(&mut self, predicate: &Predicate<'a>) {
+            if let Predicate::Irrefutable(_) = predicate {
+                self.bindings += 1;
+            }
+            walk_predicate(self, predicate)
+        }
+    }
+
+    #[test]
+    fn visitor_counts_symbols() {
+        let decls = parse_decls(
+            "f x (a, b) = let y = g x in match y Just z if z > a => h z.w { k = b } _ => -y + (x, 1)",
+        );
+        let mut counter = SymbolCounter::default();
+        counter.visit_decl(&decls[0]);
+        // g x y z a h z b y x
+        assert_eq!(counter.symbols, 10);
+        // x a b z _
+        assert_eq!(counter.bindings, 5);
+    }
+
+    fn body_shape(input: &'static str) -> String {
+        shape(&parse_one(input).body)
+    }
+
+    fn match_arms<'a>(expr: &'a Expr<'static>) -> &'a Vec<PatternExpr<'static>> {
+        match expr {
+            Expr::Match { pattern_exprs, .. } => pattern_exprs,
+            _ => panic!("expected a match expression, found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn match_retains_its_subject() {
+        let decl = parse_one("f x = match g x (Just y) => y _ => 0");
+        assert_eq!(
+            to_sexpr(&decl.body),
+            "(match (callsite (symbol g) (symbol x)) \
+             (arm (pat-ctor Just (pat-bind y)) (symbol y)) (arm (pat-bind _) (lit-int 0)))"
+        );
+    }
+
+    #[test]
+    fn char_literals() {
+        let decl = parse_one(r"is_newline c = match c '\n' => True _ => False");
+        assert!(matches!(
+            match_arms(&decl.body)[0].predicate,
+            Predicate::Char { value: '\n', .. }
+        ));
+        assert_eq!(
+            to_sexpr(&parse_one(r"f x = g 'a' x '\u{7}'").body),
+            r"(callsite (symbol g) (lit-char 'a') (symbol x) (lit-char '\u{7}'))"
+        );
+        let decl = parse_one(r"f ('\'', x) = x");
+        assert_eq!(
+            predicate_to_sexpr(&decl.predicates[0]),
+            r"(pat-tuple (pat-char '\'') (pat-bind x))"
+        );
+    }
+
+    #[test]
+    fn bool_literals() {
+        assert_eq!(
+            to_sexpr(&parse_one("f b = match b True => 1 False => 0").body),
+            "(match (symbol b) (arm (pat-bool true) (lit-int 1)) (arm (pat-bool false) (lit-int 0)))"
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("not b = match b True => False False => True").body),
+            "(match (symbol b) (arm (pat-bool true) (lit-bool false)) \
+             (arm (pat-bool false) (lit-bool true)))"
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("f (True, x) = g False x").body),
+            "(callsite (symbol g) (lit-bool false) (symbol x))"
+        );
+        // Only the capitalized names are literals.
+        let decl = parse_one("f true = true");
+        assert_eq!(predicate_to_sexpr(&decl.predicates[0]), "(pat-bind true)");
+        assert_eq!(to_sexpr(&decl.body), "(symbol true)");
+    }
+
+    #[test]
+    fn match_literal_arms() {
+        let decl = parse_one("f x = match x 1 => a 2 => b");
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 2);
+        assert!(matches!(
+            arms[0].predicate,
+            Predicate::Integer { value: 1, .. }
+        ));
+        assert!(matches!(arms[0].expr, Expr::Symbol { .. }));
+        assert!(matches!(
+            arms[1].predicate,
+            Predicate::Integer { value: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn match_ctor_arms_with_sub_patterns() {
+        let decl = parse_one("f m = match m Just x => g x Nothing => z");
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 2);
+        match &arms[0].predicate {
+            Predicate::Ctor { ctor_id, dims } => {
+                assert_eq!(ctor_id.name, "Just");
+                assert_eq!(dims.len(), 1);
+            }
+            predicate => panic!("expected a ctor predicate, found {:?}", predicate),
+        }
+        assert!(matches!(arms[0].expr, Expr::Callsite { .. }));
+        assert!(matches!(arms[1].predicate, Predicate::Ctor { .. }));
+    }
+
+    #[test]
+    fn match_guards() {
+        let decl = parse_one(
+            "f x = match x 0 => zero n if isPositive n => pos n n if n < -10 => tiny _ => neg",
+        );
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 4);
+        let guards: Vec<Option<String>> = arms
+            .iter()
+            .map(|arm| arm.guard.as_ref().map(shape))
+            .collect();
+        assert_eq!(
+            guards,
+            vec![
+                None,
+                Some("(isPositive n)".to_string()),
+                Some("(< n -10)".to_string()),
+                None
+            ]
+        );
+        assert_eq!(shape(&arms[1].expr), "(pos n)");
+        assert_eq!(shape(&arms[2].expr), "tiny");
+
+        let decl = parse_one("f m = match m Just n if n > 0 => n Just _ => 0");
+        let arms = match_arms(&decl.body);
+        assert!(matches!(&arms[0].predicate, Predicate::Ctor { dims, .. } if dims.len() == 1));
+        assert_eq!(arms[0].guard.as_ref().map(shape).unwrap(), "(> n 0)");
+    }
+
+    #[test]
+    fn match_guard_containing_a_match() {
+        let decl = parse_one("f x = match x n if (match n 0 => False _ => True) => n _ => 0");
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 2);
+        let guard = arms[0].guard.as_ref().unwrap();
+        assert_eq!(match_arms(guard).len(), 2);
+        assert_eq!(shape(&arms[0].expr), "n");
+        assert!(arms[1].guard.is_none());
+    }
+
+    #[test]
+    fn match_trailing_irrefutable_arm() {
+        let decl = parse_one("f x = match x 0 => zero; _ => other");
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 2);
+        assert!(matches!(&arms[1].predicate, Predicate::Irrefutable(id) if id.name == "_"));
+    }
+
+    #[test]
+    fn lambda_as_argument() {
+        let decl = parse_one("f xs = map (\\x -> add x 1) xs");
+        match &decl.body {
+            Expr::Callsite { arguments, .. } => {
+                assert_eq!(arguments.len(), 2);
+                match arguments[0].as_ref() {
+                    Expr::Lambda { params, body, .. } => {
+                        assert_eq!(params.len(), 1);
+                        assert!(
+                            matches!(params[0].as_ref(), Predicate::Irrefutable(id) if id.name == "x")
+                        );
+                        assert!(matches!(body.as_ref(), Expr::Callsite { .. }));
+                    }
+                    expr => panic!("expected a lambda, found {:?}", expr),
+                }
+                assert!(matches!(arguments[1].as_ref(), Expr::Symbol { .. }));
+            }
+            expr => panic!("expected a callsite, found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn lambda_params_are_patterns() {
+        let decl = parse_one("f = \\(a, b) -> a");
+        match &decl.body {
+            Expr::Lambda { params, .. } => {
+                assert_eq!(params.len(), 1);
+                assert!(
+                    matches!(params[0].as_ref(), Predicate::Tuple { dims, .. } if dims.len() == 2)
+                );
+            }
+            expr => panic!("expected a lambda, found {:?}", expr),
+        }
+        assert_eq!(
+            to_sexpr(&parse_one("f = \\(Just x) [y] z -> x").body),
+            "(lambda ((pat-ctor Just (pat-bind x)) (pat-list (pat-bind y)) z) (symbol x))"
+        );
+        let err = parse_err("f = \\(a, a) -> a");
+        assert!(err.starts_with("test.mv:1:10: error[E0005]:"), "{}", err);
+    }
+
+    fn parse_items_ok(source: &'static str) -> (Vec<Decl<'static>>, Vec<String>) {
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let mut diagnostics = Diagnostics::new(source);
+        let decls = parse_items(&mut lexer, &mut diagnostics)
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Decl(decl) => Some(decl),
+                _ => None,
+            })
+            .collect();
+        assert!(!diagnostics.has_errors());
+        let warnings = diagnostics
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect();
+        (decls, warnings)
+    }
+
+    #[test]
+    fn attributes() {
+        let (decls, warnings) =
+            parse_items_ok("#[inline]\n#[deprecated \"use foo2\"]\nfoo x = x\nbar = 1");
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let attrs = decls[0].attrs();
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].name.name, "inline");
+        assert!(attrs[0].args.is_empty());
+        assert_eq!(attrs[1].name.name, "deprecated");
+        assert_eq!(
+            attrs[1].args.iter().map(to_sexpr).collect::<Vec<_>>(),
+            vec!["(lit-string \"use foo2\")"]
+        );
+        assert!(decls[1].attrs().is_empty());
+        assert_eq!(
+            pretty_print(&decls, &FixityTable::builtin()),
+            "#[inline]\n#[deprecated \"use foo2\"]\nfoo x = x\nbar = 1\n"
+        );
+
+        let (decls, warnings) = parse_items_ok("#[lint_allow unused] #[inline] f = 1");
+        assert_eq!(decls[0].attrs().len(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].starts_with("test.mv:1:3: warning[W0004]: unknown attribute `lint_allow`"),
+            "{}",
+            warnings[0]
+        );
+    }
+
+    #[test]
+    fn misplaced_attributes() {
+        for (input, line, col) in [
+            ("f = 1\n#[inline]", 2, 1),
+            ("f = 1\n#[inline]\n", 2, 1),
+            ("#[inline]\ndata Bool = T | F", 1, 1),
+            ("f = g #[inline] 1", 1, 7),
+            ("f = 1 where #[inline] g = 2", 1, 13),
+        ] {
+            let err = parse_err(input);
+            assert!(
+                err.starts_with(&format!("test.mv:{}:{}: error[E0001]:", line, col)),
+                "{}: {}",
+                input,
+                err
+            );
+            assert!(err.contains("attributes can only be attached to a top-level decl"));
+        }
+        let err = parse_err("#[deprecated \"x\"");
+        assert!(err.starts_with("test.mv:1:1: error[E0003]:"), "{}", err);
+    }
+
+    #[test]
+    fn where_clauses() {
+        let clause = parse_one("f x = g x where g y = y + 1");
+        assert_eq!(to_sexpr(&clause.body), "(callsite (symbol g) (symbol x))");
+        assert_eq!(clause.where_decls.len(), 1);
+        assert_eq!(
+            decl_sexpr(&clause.where_decls[0]),
+            "(decl g (clause (pat-bind y) (binary-op + (symbol y) (lit-int 1))))"
+        );
+        let decl =
+            parse_decls("f x = a\n  where {\n    a = b x\n    b 0 = 1\n    b n = n\n  }").remove(0);
+        let where_decls = decl.clauses[0].where_decls();
+        assert_eq!(where_decls.len(), 2);
+        assert_eq!(where_decls[1].clauses.len(), 2);
+        let err = parse_err("f x = g x where");
+        assert!(err.starts_with("test.mv:1:11: error[E0006]:"), "{}", err);
+        let err = parse_err("f x = g x where { g = 1");
+        assert!(err.starts_with("test.mv:1:11: error[E0003]:"), "{}", err);
+    }
+
+    #[test]
+    fn interpolated_strings() {
+        assert_eq!(
+            to_sexpr(&parse_one("f b = \"a{b}c\"").body),
+            "(interp \"a\" (symbol b) \"c\")"
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("f x = \"{{x}} is {x + 1}\"").body),
+            "(interp \"{x} is \" (binary-op + (symbol x) (lit-int 1)))"
+        );
+        // Locations inside the braces are those in the file.
+        match &parse_one("f x = \"ab{g x}\"").body {
+            Expr::Interp { parts, .. } => {
+                assert_eq!(parts.len(), 2);
+                let expr = parts[1].expr().unwrap();
+                assert_eq!((expr.get_location().line, expr.get_location().col), (1, 11));
+            }
+            body => panic!("expected an interpolated string, found {:?}", body),
+        }
+        let err = parse_err("f x = \"ab{x = 1}\"");
+        assert!(err.starts_with("test.mv:1:13: error[E0001]:"), "{}", err);
+        let err = parse_err("f x = \"a{ }b\"");
+        assert!(err.starts_with("test.mv:1:9: error[E0006]:"), "{}", err);
+    }
+
+    #[test]
+    fn adjacent_string_literals() {
+        let source = [
+            "f x = g (h \"one \"",
+            "         \"two \"",
+            "         \"three\") x",
+        ]
+        .join("\n");
+        assert_eq!(
+            to_sexpr(&parse_decls(&source).remove(0).clauses[0].body),
+            "(callsite (symbol g) (callsite (symbol h) (lit-string \"one two three\")) (symbol x))"
+        );
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(
+            sexpr_of("f = \"a{{\" \"\" \"}}b\""),
+            "(lit-string \"a{}b\")"
+        );
+        // Interpolation in any of the literals makes the whole an interpolated string.
+        assert_eq!(
+            sexpr_of("f x = \"{{x}} is \" \"{x}\" \"!\""),
+            "(interp \"{x} is \" (symbol x) \"!\")"
+        );
+        // Within braces a newline separates statements, so the literals stay apart.
+        let Expr::Block { statements, .. } = parse_one("f = {\n  log \"a\"\n  \"b\"\n}").body
+        else {
+            panic!("expected a block");
+        };
+        assert_eq!(statements.len(), 1);
+    }
+
+    #[test]
+    fn if_expressions() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(
+            sexpr_of("f x = if x > 0 then x else -x"),
+            "(if (binary-op > (symbol x) (lit-int 0)) (symbol x) (unary-op - (symbol x)))"
+        );
+        // An else-if chain nests to the right, here spread over indented continuation lines.
+        let chain = [
+            "classify n =",
+            "  if n < 0 then \"negative\"",
+            "  else if n == 0 then \"zero\"",
+            "  else if n < 10 then \"small\"",
+            "  else if n < 100 then \"medium\"",
+            "  else if n < 1000 then \"large\"",
+            "  else \"huge\"",
+        ]
+        .join("\n");
+        assert_eq!(
+            to_sexpr(&parse_decls(&chain).remove(0).clauses[0].body),
+            "(if (binary-op < (symbol n) (lit-int 0)) (lit-string \"negative\") \
+             (if (binary-op == (symbol n) (lit-int 0)) (lit-string \"zero\") \
+             (if (binary-op < (symbol n) (lit-int 10)) (lit-string \"small\") \
+             (if (binary-op < (symbol n) (lit-int 100)) (lit-string \"medium\") \
+             (if (binary-op < (symbol n) (lit-int 1000)) (lit-string \"large\") \
+             (lit-string \"huge\"))))))"
+        );
+        // An else belongs to the nearest if which does not have one yet.
+        assert_eq!(
+            sexpr_of("f a b = if a then if b then 1 else 2 else 3"),
+            "(if (symbol a) (if (symbol b) (lit-int 1) (lit-int 2)) (lit-int 3))"
+        );
+        // The else branch extends as far as it can.
+        assert_eq!(
+            sexpr_of("f a = if a then 1 else 2 + 3"),
+            "(if (symbol a) (lit-int 1) (binary-op + (lit-int 2) (lit-int 3)))"
+        );
+        assert_eq!(
+            sexpr_of("f x = match x 0 => if x then a else b _ => c"),
+            "(match (symbol x) (arm (pat-int 0) (if (symbol x) (symbol a) (symbol b))) \
+             (arm (pat-bind _) (symbol c)))"
+        );
+        let decl = parse_decls("f = 1 where {\n  g a = if a then 1\n  else 2\n}").remove(0);
+        assert_eq!(
+            to_sexpr(&decl.clauses[0].where_decls[0].clauses[0].body),
+            "(if (symbol a) (lit-int 1) (lit-int 2))"
+        );
+        let err = parse_err("f a = if a else 2");
+        assert!(err.starts_with("test.mv:1:12: error[E0001]:"), "{}", err);
+        let err = parse_err("f a = if a then 1");
+        assert!(err.starts_with("test.mv:1:18: error[E0004]:"), "{}", err);
+    }
+
+    #[test]
+    fn keyword_expressions_as_arguments() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        let match_sexpr =
+            "(match (symbol x) (arm (pat-int 1) (symbol a)) (arm (pat-bind _) (symbol b)))";
+        let let_sexpr = "(let z (symbol x) (symbol z))";
+        let lambda_sexpr = "(lambda (z) (symbol z))";
+        for (argument, expected) in [
+            ("match x 1 => a _ => b", match_sexpr),
+            ("match x\n  1 => a\n  _ => b", match_sexpr),
+            ("let z = x in z", let_sexpr),
+            ("\\z -> z", lambda_sexpr),
+            (
+                "if x then a else b",
+                "(if (symbol x) (symbol a) (symbol b))",
+            ),
+        ] {
+            let application = format!("f x y = g ({}) y", argument);
+            assert_eq!(
+                to_sexpr(&parse_decls(&application).remove(0).clauses[0].body),
+                format!("(callsite (symbol g) {} (symbol y))", expected),
+                "{}",
+                application
+            );
+            let tuple = format!("f x y = ({}, {})", argument, argument);
+            assert_eq!(
+                to_sexpr(&parse_decls(&tuple).remove(0).clauses[0].body),
+                format!("(tuple {} {})", expected, expected),
+                "{}",
+                tuple
+            );
+        }
+        // The closing paren ends the innermost expression, even within an arm or a lambda body.
+        assert_eq!(
+            sexpr_of("f x y = g (match x 1 => (match y 2 => a) _ => b) (\\z -> match z _ => z) y"),
+            "(callsite (symbol g) \
+             (match (symbol x) (arm (pat-int 1) (match (symbol y) (arm (pat-int 2) (symbol a)))) \
+             (arm (pat-bind _) (symbol b))) \
+             (lambda (z) (match (symbol z) (arm (pat-bind _) (symbol z)))) (symbol y))"
+        );
+        assert_eq!(
+            sexpr_of("f x = g (let rec h n = h n in h) x"),
+            "(callsite (symbol g) \
+             (let-rec (binding h (lambda (n) (callsite (symbol h) (symbol n)))) (symbol h)) \
+             (symbol x))"
+        );
+        let err = parse_err("f x = g (do x) x");
+        assert!(err.starts_with("test.mv:1:13: error[E0001]:"), "{}", err);
+        assert!(err.contains("`{` to start this do-block"), "{}", err);
+    }
+
+    #[test]
+    fn lambda_with_several_params() {
+        let decl = parse_one("f = \\x y -> add x y");
+        assert!(matches!(&decl.body, Expr::Lambda { params, .. } if params.len() == 2));
+    }
+
+    #[test]
+    fn lambda_without_params_is_an_error() {
+        let mut lexer = Lexer::new("test.mv", "f = \\-> 1");
+        lexer.advance().unwrap();
+        let err = parse_many(parse_decl, &mut lexer).unwrap_err();
+        assert!(err.to_string().contains("at least one parameter"));
+    }
+
+    #[test]
+    fn binary_precedence() {
+        assert_eq!(body_shape("f = a + b * c"), "(+ a (* b c))");
+        assert_eq!(body_shape("f = a * b + c"), "(+ (* a b) c)");
+        assert_eq!(body_shape("f = a == b + 1 && c"), "(&& (== a (+ b 1)) c)");
+    }
+
+    #[test]
+    fn binary_associativity() {
+        assert_eq!(body_shape("f = a - b - c"), "(- (- a b) c)");
+        assert_eq!(body_shape("f = g $ h $ x"), "($ g ($ h x))");
+        assert_eq!(body_shape("f = a ^ b ^ c"), "(^ a (^ b c))");
+    }
+
+    #[test]
+    fn application_binds_tighter_than_operators() {
+        assert_eq!(body_shape("f = g x + h y"), "(+ (g x) (h y))");
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(body_shape("f = (a + b) * c"), "(* (+ a b) c)");
+        assert_eq!(body_shape("f = a - (b - c)"), "(- a (- b c))");
+    }
+
+    #[test]
+    fn unit_literals_and_patterns() {
+        let decl = parse_one("main () = print \"hi\"");
+        assert_eq!(predicate_to_sexpr(&decl.predicates[0]), "(pat-tuple)");
+        assert_eq!(
+            to_sexpr(&parse_one("f = main ()").body),
+            "(callsite (symbol main) (tuple))"
+        );
+        let decls = parse_decls("main () = print \"hi\"\nf = main ( )");
+        assert_eq!(
+            pretty_print(&decls, &FixityTable::builtin()),
+            "main () = print \"hi\"\nf = main ()\n"
+        );
+    }
+
+    #[test]
+    fn predicates_borrow_the_source_not_the_lexer() {
+        // Both predicates are alive at once, which only compiles if neither result holds a borrow
+        // of the lexer.
+        let lexer = &mut Lexer::new("test.mv", "(a, b) [c]");
+        lexer.advance().unwrap();
+        let first = parse_predicate(lexer).unwrap().unwrap();
+        let second = parse_predicate(lexer).unwrap().unwrap();
+        assert_eq!(
+            predicate_to_sexpr(&first),
+            "(pat-tuple (pat-bind a) (pat-bind b))"
+        );
+        assert_eq!(predicate_to_sexpr(&second), "(pat-list (pat-bind c))");
+        assert!(lexer.at_eof());
+    }
+
+    #[test]
+    fn ctor_patterns_in_decl_heads() {
+        let params = |input| -> Vec<String> {
+            parse_one(input)
+                .predicates
+                .iter()
+                .map(|predicate| predicate_to_sexpr(predicate))
+                .collect()
+        };
+        assert_eq!(
+            params("f (Just x) y = x"),
+            vec!["(pat-ctor Just (pat-bind x))", "(pat-bind y)"]
+        );
+        assert_eq!(
+            params("f Nothing y = y"),
+            vec!["(pat-ctor Nothing)", "(pat-bind y)"]
+        );
+        // A bare constructor takes no arguments, so this has two parameters.
+        assert_eq!(
+            params("f Just x = x"),
+            vec!["(pat-ctor Just)", "(pat-bind x)"]
+        );
+        // In parentheses, a constructor's arguments are themselves atoms.
+        assert_eq!(
+            params("f (Pair (Just a) Nothing) = a"),
+            vec!["(pat-ctor Pair (pat-ctor Just (pat-bind a)) (pat-ctor Nothing))"]
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("f = \\Just x -> x").body),
+            "(lambda ((pat-ctor Just) x) (symbol x))"
+        );
+        let decls = parse_decls("f (Just x) Nothing y = x");
+        assert_eq!(
+            pretty_print(&decls, &FixityTable::builtin()),
+            "f (Just x) Nothing y = x\n"
+        );
+    }
+
+    #[test]
+    fn parenthesized_predicates() {
+        let param = |input| predicate_to_sexpr(&parse_one(input).predicates[0]);
+        assert_eq!(param("f () = 1"), "(pat-tuple)");
+        assert_eq!(param("f (x) = x"), "(pat-bind x)");
+        assert_eq!(param("f (x,) = x"), "(pat-tuple (pat-bind x))");
+        assert_eq!(
+            param("f (x, y) = x"),
+            "(pat-tuple (pat-bind x) (pat-bind y))"
+        );
+        assert_eq!(
+            param("f (x, y,) = x"),
+            "(pat-tuple (pat-bind x) (pat-bind y))"
+        );
+        assert_eq!(
+            param("f (x, (y, z,), ()) = x"),
+            "(pat-tuple (pat-bind x) (pat-tuple (pat-bind y) (pat-bind z)) (pat-tuple))"
+        );
+        for (input, col) in [
+            ("f (x,, y) = x", 6),
+            ("f (, x) = x", 4),
+            ("f (x, y,,) = x", 9),
+        ] {
+            let err = parse_err(input);
+            assert!(
+                err.starts_with(&format!("test.mv:1:{}: error[E0001]:", col)),
+                "{}: {}",
+                input,
+                err
+            );
+        }
+        let err = parse_err("f (x, y");
+        assert!(err.starts_with("test.mv:1:8: error[E0004]:"), "{}", err);
+    }
+
+    #[test]
+    fn grouped_and_tuple_predicates() {
+        let clause = parse_one("f (x) (w,) (a, b) (c, d,) ((Just y)) = x");
+        let predicates: Vec<String> = clause
+            .predicates
+            .iter()
+            .map(|predicate| predicate_to_sexpr(predicate))
+            .collect();
+        assert_eq!(
+            predicates,
+            vec![
+                "(pat-bind x)",
+                "(pat-tuple (pat-bind w))",
+                "(pat-tuple (pat-bind a) (pat-bind b))",
+                "(pat-tuple (pat-bind c) (pat-bind d))",
+                "(pat-ctor Just (pat-bind y))",
+            ]
+        );
+        assert!(matches!(&clause.predicates[0], Predicate::Irrefutable(_)));
+        assert!(matches!(
+            &clause.predicates[1],
+            Predicate::Tuple { dims, .. } if dims.len() == 1
+        ));
+        assert!(matches!(
+            &clause.predicates[2],
+            Predicate::Tuple { dims, .. } if dims.len() == 2
+        ));
+        let decls = parse_decls("f (x) (w,) (a, b,) = x");
+        assert_eq!(
+            pretty_print(&decls, &FixityTable::builtin()),
+            "f x (w,) (a, b) = x\n"
+        );
+    }
+
+    #[test]
+    fn operators_in_function_position() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(
+            sexpr_of("f = + 1 2"),
+            "(callsite (symbol +) (lit-int 1) (lit-int 2))"
+        );
+        assert_eq!(sexpr_of("f = ++ xs"), "(callsite (symbol ++) (symbol xs))");
+        let err = parse_err("f = => 1");
+        assert!(
+            err.starts_with(
+                "test.mv:1:5: error[E0001]: expected an expression, but found `=>`, which is \
+                 reserved by the grammar"
+            ),
+            "{}",
+            err
+        );
+        assert!(parse_err("f = -> 1").starts_with("test.mv:1:5: error[E0001]:"));
+        assert!(parse_err("f = 1 + => 2").starts_with("test.mv:1:9: error[E0001]:"));
+        assert!(parse_err("f = g (| x)").starts_with("test.mv:1:8: error[E0001]:"));
+    }
+
+    #[test]
+    fn operator_definitions() {
+        let decl = parse_decls("(+) a b = add a b").remove(0);
+        assert_eq!(decl.id.name, "+");
+        let location = decl.id.get_location();
+        assert_eq!((location.line, location.col), (1, 2));
+        assert_eq!(decl.clauses[0].predicates.len(), 2);
+        let decls = parse_decls("(<>) x y = concat x y\nf = fold (<>) empty");
+        assert_eq!(
+            pretty_print(&decls, &FixityTable::builtin()),
+            "(<>) x y = concat x y\nf = fold (<>) empty\n"
+        );
+        let err = parse_err("() a = a");
+        assert!(
+            err.starts_with("test.mv:1:1: error[E0001]: expected an operator between"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let decl = parse_one("f (x,) [a, b,] (c,\n d,\n) = (x,)");
+        let patterns: Vec<String> = decl
+            .predicates
+            .iter()
+            .map(|predicate| predicate_to_sexpr(predicate))
+            .collect();
+        assert_eq!(
+            patterns,
+            vec![
+                "(pat-tuple (pat-bind x))",
+                "(pat-list (pat-bind a) (pat-bind b))",
+                "(pat-tuple (pat-bind c) (pat-bind d))",
+            ]
+        );
+        assert_eq!(to_sexpr(&decl.body), "(tuple (symbol x))");
+        assert_eq!(
+            to_sexpr(&parse_one("f = (1,\n 2,\n)").body),
+            "(tuple (lit-int 1) (lit-int 2))"
+        );
+        for (input, col) in [
+            ("f (a,,) = a", 6),
+            ("f (,a) = a", 4),
+            ("f [a,,] = a", 6),
+            ("f [,a] = a", 4),
+            ("f = (a,,)", 8),
+            ("f = (,a)", 6),
+        ] {
+            let err = parse_err(input);
+            assert!(err.starts_with(&format!("test.mv:1:{}:", col)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(sexpr_of("f = 1..10"), "(range (lit-int 1) (lit-int 10))");
+        assert_eq!(
+            sexpr_of("f = 1..=10"),
+            "(range-inclusive (lit-int 1) (lit-int 10))"
+        );
+        assert_eq!(sexpr_of("f = 1.."), "(range (lit-int 1) _)");
+        assert_eq!(
+            sexpr_of("f n = ..n + 1"),
+            "(range _ (binary-op + (symbol n) (lit-int 1)))"
+        );
+        assert_eq!(
+            sexpr_of("f n = g (1..) (n..=n * 2)"),
+            "(callsite (symbol g) (range (lit-int 1) _) \
+             (range-inclusive (symbol n) (binary-op * (symbol n) (lit-int 2))))"
+        );
+        let err = parse_err("f = 1..=");
+        assert!(err.starts_with("test.mv:1:6: error[E0006]:"), "{}", err);
+        let err = parse_err("f = ..");
+        assert!(err.starts_with("test.mv:1:5: error[E0006]:"), "{}", err);
+    }
+
+    #[test]
+    fn list_ranges() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(sexpr_of("f = [1..10]"), sexpr_of("f = 1..10"));
+        assert_eq!(
+            sexpr_of("f n = [1..=n]"),
+            "(range-inclusive (lit-int 1) (symbol n))"
+        );
+        assert_eq!(
+            sexpr_of("f = [0, 2..20]"),
+            "(range (lit-int 0) (step (lit-int 2)) (lit-int 20))"
+        );
+        assert_eq!(
+            sexpr_of("f n = [n, n - 1..=0]"),
+            "(range-inclusive (symbol n) (step (binary-op - (symbol n) (lit-int 1))) (lit-int 0))"
+        );
+        // In parentheses a range is an ordinary element.
+        assert_eq!(
+            sexpr_of("f = [(1..10)]"),
+            "(list (range (lit-int 1) (lit-int 10)))"
+        );
+        assert!(matches!(
+            parse_one("f = [0, 2..20]").body,
+            Expr::Range { step: Some(_), .. }
+        ));
+
+        let err = parse_err("f = [1..2..3]");
+        assert!(err.starts_with("test.mv:1:10: error[E0006]:"), "{}", err);
+        assert!(err.contains("only one `..`"), "{}", err);
+        assert!(parse_err("f = 1..2..3").starts_with("test.mv:1:9: error[E0006]:"));
+        let err = parse_err("f x = [x..]");
+        assert!(err.starts_with("test.mv:1:8: error[E0006]:"), "{}", err);
+        assert!(err.contains("not supported yet"), "{}", err);
+        let err = parse_err("f = [..10]");
+        assert!(err.contains("needs a start"), "{}", err);
+        let err = parse_err("f = [0, ..10]");
+        assert!(err.contains("needs its second element"), "{}", err);
+        let err = parse_err("f = [0, 1, 2..10]");
+        assert!(err.starts_with("test.mv:1:12: error[E0006]:"), "{}", err);
+        let err = parse_err("f = [1..10, 11]");
+        assert!(err.contains("last element"), "{}", err);
+    }
+
+    #[test]
+    fn list_literals() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(sexpr_of("f = []"), "(list)");
+        assert_eq!(
+            sexpr_of("f x = [x, x + 1, g x,]"),
+            "(list (symbol x) (binary-op + (symbol x) (lit-int 1)) (callsite (symbol g) (symbol x)))"
+        );
+        assert_eq!(
+            sexpr_of("f = g [1] [[2]]"),
+            "(callsite (symbol g) (list (lit-int 1)) (list (list (lit-int 2))))"
+        );
+        let err = parse_err("f = [1, 2");
+        assert!(err.starts_with("test.mv:1:5: error[E0003]:"), "{}", err);
+        let err = parse_err("f = [1, 2 = 3]");
+        assert!(
+            err.contains("expected , or ] to close this list, found"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn list_comprehensions() {
+        let decl = parse_one("f xs = [ x * 2 | x <- xs, x > 0 ]");
+        let Expr::Comprehension {
+            output,
+            generators,
+            guards,
+            ..
+        } = &decl.body
+        else {
+            panic!("expected a comprehension, found {:?}", decl.body);
+        };
+        assert_eq!(to_sexpr(output), "(binary-op * (symbol x) (lit-int 2))");
+        assert_eq!(generators.len(), 1);
+        assert_eq!(predicate_to_sexpr(&generators[0].pattern), "(pat-bind x)");
+        assert_eq!(to_sexpr(&generators[0].source), "(symbol xs)");
+        assert_eq!(guards.len(), 1);
+        assert_eq!(guards[0].0, 1);
+        assert_eq!(
+            to_sexpr(&guards[0].1),
+            "(binary-op > (symbol x) (lit-int 0))"
+        );
+
+        // Qualifiers keep their order, and a generator may destructure.
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(
+            sexpr_of("f ps = [a | ready, (a, Just b) <- ps, b, c <- g a]"),
+            "(comprehension (symbol a) (guard (symbol ready)) \
+             (generator (pat-tuple (pat-bind a) (pat-ctor Just (pat-bind b))) (symbol ps)) \
+             (guard (symbol b)) (generator (pat-bind c) (callsite (symbol g) (symbol a))))"
+        );
+
+        let err = parse_err("f xs = [x | x <- xs");
+        assert!(err.starts_with("test.mv:1:8: error[E0003]:"), "{}", err);
+        let err = parse_err("f xs = [x | (x, x) <- xs]");
+        assert!(err.starts_with("test.mv:1:17: error[E0005]:"), "{}", err);
+        assert!(parse_err("f xs = [x | ]").contains("error[E0006]"));
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(
+            sexpr_of("f c = c ? 1 : 2"),
+            sexpr_of("f c = if c then 1 else 2")
+        );
+        assert_eq!(
+            sexpr_of("f x = x > 0 ? x + 1 : -x"),
+            sexpr_of("f x = if x > 0 then x + 1 else -x")
+        );
+        // The `:` ending the middle is not a cons, but one within brackets or the last operand is.
+        assert_eq!(
+            sexpr_of("f c xs = c ? (0 : xs) : 1 : xs"),
+            sexpr_of("f c xs = if c then 0 : xs else 1 : xs")
+        );
+        // Conditionals group to the right, in the middle as well as at the end.
+        assert_eq!(
+            sexpr_of("f a b = a ? 1 : b ? 2 : 3"),
+            sexpr_of("f a b = if a then 1 else if b then 2 else 3")
+        );
+        assert_eq!(
+            sexpr_of("f a b = a ? b ? 1 : 2 : 3"),
+            sexpr_of("f a b = if a then if b then 1 else 2 else 3")
+        );
+        assert_eq!(
+            sexpr_of("f a = g (a ? 1 : 2) 3"),
+            sexpr_of("f a = g (if a then 1 else 2) 3")
+        );
+        // Without a `:` to follow it, a lone `?` is still a hole.
+        assert_eq!(
+            sexpr_of("f = g ? 1"),
+            "(callsite (symbol g) (hole) (lit-int 1))"
+        );
+        assert_eq!(
+            sexpr_of("f = g ? (h ? 1) ?"),
+            "(callsite (symbol g) (hole) (callsite (symbol h) (hole) (lit-int 1)) (hole))"
+        );
+        // With one, a hole consed onto a list is written as a named hole.
+        assert_eq!(
+            sexpr_of("f g xs = g ?h 1 : xs"),
+            "(binary-op : (callsite (symbol g) (hole h) (lit-int 1)) (symbol xs))"
+        );
+        assert_eq!(
+            sexpr_of("f g xs = g ? 1 : xs"),
+            sexpr_of("f g xs = if g then 1 else xs")
+        );
+        let err = parse_err("f c = c ? 1 : ");
+        assert!(err.starts_with("test.mv:1:15: error[E0006]:"), "{}", err);
+        // A lambda in the middle extends as far as it can, taking the `:` with it.
+        let err = parse_err("f c = c ? \\x -> x : 2");
+        assert!(err.starts_with("test.mv:1:9: error[E0004]:"), "{}", err);
+        assert_eq!(
+            sexpr_of("f c = c ? (1 : 2)"),
+            "(callsite (symbol c) (hole) (binary-op : (lit-int 1) (lit-int 2)))"
+        );
+    }
+
+    #[test]
+    fn holes() {
+        let sexpr_of = |input| to_sexpr(&parse_one(input).body);
+        assert_eq!(sexpr_of("f = ?todo"), "(hole todo)");
+        assert_eq!(
+            sexpr_of("f x = g ? (x + ?rest)"),
+            "(callsite (symbol g) (hole) (binary-op + (symbol x) (hole rest)))"
+        );
+        let source = "f = g ?todo ?";
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let mut diagnostics = Diagnostics::new(source);
+        assert_eq!(parse_items(&mut lexer, &mut diagnostics).len(), 1);
+        assert!(!diagnostics.has_errors());
+        let warnings: Vec<String> = diagnostics
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect();
+        assert_eq!(warnings.len(), 2);
+        assert!(
+            warnings[0].starts_with("test.mv:1:7: warning[W0003]: found hole `?todo`"),
+            "{}",
+            warnings[0]
+        );
+        assert!(
+            warnings[1].starts_with("test.mv:1:13: warning[W0003]: found hole `?`"),
+            "{}",
+            warnings[1]
+        );
+    }
+
+    #[test]
+    fn operator_sections() {
+        assert_eq!(
+            to_sexpr(&parse_one("f xs = fold (+) 0 xs").body),
+            "(callsite (symbol fold) (symbol +) (lit-int 0) (symbol xs))"
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("f = (+ 1)").body),
+            "(lambda ($0) (binary-op + (symbol $0) (lit-int 1)))"
+        );
+        // The parameter can't capture any of the names the operand uses.
+        assert_eq!(
+            to_sexpr(&parse_one("f x = (x - 1 -)").body),
+            "(lambda ($0) (binary-op - (binary-op - (symbol x) (lit-int 1)) (symbol $0)))"
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("f x y z a b c = (+ (x + y + z + a + b + c))").body),
+            "(lambda ($0) (binary-op + (symbol $0) (binary-op + (binary-op + (binary-op + \
+             (binary-op + (binary-op + (symbol x) (symbol y)) (symbol z)) (symbol a)) (symbol b)) \
+             (symbol c))))"
+        );
+        assert_eq!(to_sexpr(&parse_one("f = (-)").body), "(symbol -)");
+        assert_eq!(
+            to_sexpr(&parse_one("f = (- 1)").body),
+            "(unary-op - (lit-int 1))"
+        );
+    }
+
+    #[test]
+    fn qualified_names() {
+        assert_eq!(to_sexpr(&parse_one("f = A::B").body), "(path A B)");
+        assert_eq!(
+            to_sexpr(&parse_one("f xs = A::B::c xs.length").body),
+            "(callsite (path A B c) (field-access (symbol xs) length))"
+        );
+        assert_eq!(body_shape("f = x : List::empty"), "(: x List::empty)");
+        let err = parse_err("f = A::");
+        assert!(err.starts_with("test.mv:1:8: error[E0004]:"), "{}", err);
+        let err = parse_err("f = A::1");
+        assert!(err.starts_with("test.mv:1:8: error[E0001]:"), "{}", err);
+        let err = parse_err("f = A::in");
+        assert!(err.contains("`in` is a keyword"), "{}", err);
+    }
+
+    #[test]
+    fn backtick_application() {
+        assert_eq!(body_shape("f = x `div` y"), "(div x y)");
+        assert_eq!(body_shape("f = g x `div` h y"), "(div (g x) (h y))");
+        assert_eq!(body_shape("f = a `f` b `g` c"), "(g (f a b) c)");
+        assert_eq!(
+            body_shape("f = a + b `div` c == d"),
+            "(== (+ a (div b c)) d)"
+        );
+        assert_eq!(body_shape("f = (a + b) `div` (g c)"), "(div (+ a b) (g c))");
+        assert_eq!(
+            body_shape("f = a `elem` (xs `zip` ys)"),
+            "(elem a (zip xs ys))"
+        );
+    }
+
+    #[test]
+    fn backticked_names_must_be_infix() {
+        let err = parse_err("f = `div` x y");
+        assert!(err.starts_with("test.mv:1:5: error[E0006]:"), "{}", err);
+        assert!(err.contains("drop the backticks"), "{}", err);
+        let err = parse_err("f = x + `div` y");
+        assert!(err.starts_with("test.mv:1:9: error[E0006]:"), "{}", err);
+    }
+
+    #[test]
+    fn prefix_operators() {
+        assert_eq!(body_shape("f = -x"), "(unary - x)");
+        assert_eq!(body_shape("f = - -x"), "(unary - (unary - x))");
+        assert_eq!(body_shape("f = !(a && b)"), "(unary ! (&& a b))");
+        assert_eq!(body_shape("f = - g x + 1"), "(+ (unary - (g x)) 1)");
+    }
+
+    #[test]
+    fn prefix_minus_needs_adjacency_in_argument_position() {
+        assert_eq!(body_shape("f = g -x"), "(g (unary - x))");
+        assert_eq!(body_shape("f = g - x"), "(- g x)");
+        assert_eq!(body_shape("f = a - -b"), "(- a (unary - b))");
+        assert_eq!(body_shape("f = a -1"), "(a -1)");
+        assert_eq!(body_shape("f = a - 1"), "(- a 1)");
+        // A `-` written against the operand before it subtracts, whatever follows it.
+        assert_eq!(body_shape("f = x-y"), "(- x y)");
+        assert_eq!(body_shape("f = n-1"), "(- n 1)");
+        assert_eq!(body_shape("f = g x-1"), "(- (g x) 1)");
+        assert_eq!(body_shape("f = (g x)-y"), "(- (g x) y)");
+        assert_eq!(body_shape("f = g (-1)"), "(g -1)");
+        assert_eq!(body_shape("f = g (-x)"), "(g (unary - x))");
+    }
+
+    #[test]
+    fn callsites_and_tuples_span_their_children() {
+        match parse_one("f = g a \"bc\"").body {
+            Expr::Callsite { span, .. } => {
+                assert_eq!((span.start.line, span.start.col), (1, 5));
+                assert_eq!((span.end.line, span.end.col), (1, 12));
+            }
+            body => panic!("expected a callsite, found {:?}", body),
+        }
+        match parse_one("f = (a,\n  b)").body {
+            Expr::TupleCtor { span, .. } => {
+                assert_eq!((span.start.line, span.start.col), (1, 5));
+                assert_eq!((span.end.line, span.end.col), (2, 4));
+            }
+            body => panic!("expected a tuple, found {:?}", body),
+        }
+    }
+
+    #[test]
+    fn tuple_expressions() {
+        assert_eq!(body_shape("f = (a, b)"), "(tuple a b)");
+        assert_eq!(body_shape("f = (a + 1, g x, c)"), "(tuple (+ a 1) (g x) c)");
+        assert_eq!(body_shape("f = ()"), "(tuple)");
+        assert_eq!(body_shape("f = (a,)"), "(tuple a)");
+        assert_eq!(body_shape("f = (a)"), "a");
+        assert_eq!(body_shape("f = g (a, b) c"), "(g (tuple a b) c)");
+    }
+
+    #[test]
+    fn parse_many_stops_a_parser_that_makes_no_progress() {
+        let mut lexer = Lexer::new("test.mv", "a b");
+        lexer.advance().unwrap();
+        let err = parse_many(
+            |lexer: &mut Lexer<'static>| Ok(Some(Identifier::new("a", lexer.location))),
+            &mut lexer,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Internal);
+        assert!(
+            err.to_string().contains("parser made no progress"),
+            "{}",
+            err
+        );
+        assert_eq!(lexer.peek().lexeme, Lexeme::Identifier("a"));
+    }
+
+    #[test]
+    fn maybe_id_leaves_the_lexer_alone_when_there_is_no_id() {
+        for input in ["let", "(a)", "= b", ""] {
+            let lexer = &mut Lexer::new("test.mv", input);
+            lexer.advance().unwrap();
+            let before = (lexer.peek(), lexer.location);
+            assert!(maybe_id(lexer).unwrap().is_none(), "{:?}", input);
+            assert_eq!((lexer.peek(), lexer.location), before, "{:?}", input);
+        }
+        let lexer = &mut Lexer::new("test.mv", "a b");
+        lexer.advance().unwrap();
+        assert_eq!(maybe_id(lexer).unwrap().unwrap().name, "a");
+        assert_eq!(lexer.peek().lexeme, Lexeme::Identifier("b"));
+    }
+
+    #[test]
+    fn unclosed_tuple_points_at_the_open_paren() {
+        let mut lexer = Lexer::new("test.mv", "f = (a, b");
+        lexer.advance().unwrap();
+        let err = parse_many(parse_decl, &mut lexer).unwrap_err();
+        assert!(err.to_string().starts_with("test.mv:1:5:"), "{}", err);
+    }
+
+    #[test]
+    fn adjacent_operators_point_at_the_second() {
+        let mut lexer = Lexer::new("test.mv", "f = a + * b");
+        lexer.advance().unwrap();
+        let err = parse_many(parse_decl, &mut lexer).unwrap_err();
+        assert!(err.to_string().starts_with("test.mv:1:9:"), "{}", err);
+    }
+
+    #[test]
+    fn parse_items_reports_every_broken_item() {
+        let source = "f = a + * b\ng x = x\nh = \\-> 1\nk = 1";
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let mut diagnostics = Diagnostics::new(source);
+        let items = parse_items(&mut lexer, &mut diagnostics);
+        let names: Vec<&str> = items
+            .iter()
+            .map(|item| match item {
+                Item::Decl(decl) => decl.id.name,
+                Item::Data(data_decl) => data_decl.name.name,
+                Item::Import(import) => import.path.parts[0].name,
+                Item::Fixity(fixity_decl) => fixity_decl.operator.name,
+            })
+            .collect();
+        assert_eq!(names, vec!["g", "k"]);
+        assert_eq!(diagnostics.errors().len(), 2);
+        assert!(diagnostics.errors()[0]
+            .to_string()
+            .starts_with("test.mv:1:"));
+        assert!(diagnostics.errors()[1]
+            .to_string()
+            .starts_with("test.mv:3:"));
+    }
+
+    #[test]
+    fn unused_let_bindings_warn_without_failing() {
+        let source = "f = let x = 1 in 2\ng = let y = 1 in let _z = 2 in y";
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let mut diagnostics = Diagnostics::new(source);
+        let items = parse_items(&mut lexer, &mut diagnostics);
+        assert_eq!(items.len(), 2);
+        assert!(!diagnostics.has_errors());
+        assert_eq!(diagnostics.warnings().len(), 1);
+        let warning = &diagnostics.warnings()[0];
+        assert!(warning.is_warning());
+        assert!(matches!(warning.level(), ErrorLevel::Warning));
+        assert!(
+            warning
+                .to_string()
+                .starts_with("test.mv:1:9: warning[W0001]: unused let binding `x`"),
+            "{}",
+            warning
+        );
+    }
+
+    #[test]
+    fn negative_and_float_literal_patterns() {
+        let decl = parse_one("f x = match x -1 => a 0 => b - 1 => c -0 => d");
+        let values: Vec<i64> = match_arms(&decl.body)
+            .iter()
+            .map(|arm| match arm.predicate {
+                Predicate::Integer { value, .. } => value,
+                ref predicate => panic!("expected an integer pattern, found {:?}", predicate),
+            })
+            .collect();
+        assert_eq!(values, vec![-1, 0, -1, 0]);
+        assert_eq!(shape(&match_arms(&decl.body)[1].expr), "b");
+
+        let decl = parse_one("f (a, - 1.5) = a");
+        match &decl.predicates[0] {
+            Predicate::Tuple { dims, .. } => {
+                assert!(
+                    matches!(dims[1].as_ref(), Predicate::Float { value, .. } if *value == -1.5)
+                )
+            }
+            predicate => panic!("expected a tuple pattern, found {:?}", predicate),
+        }
+    }
+
+    #[test]
+    fn float_patterns_warn() {
+        let source = "f (a, 1.5) = a\ng x = match x 2.0 => 1 _ => 0";
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let mut diagnostics = Diagnostics::new(source);
+        assert_eq!(parse_items(&mut lexer, &mut diagnostics).len(), 2);
+        assert!(!diagnostics.has_errors());
+        let warnings: Vec<String> = diagnostics
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect();
+        assert_eq!(warnings.len(), 2);
+        assert!(
+            warnings[0].starts_with("test.mv:1:7: warning[W0002]:"),
+            "{}",
+            warnings[0]
+        );
+        assert!(
+            warnings[1].starts_with("test.mv:2:15: warning[W0002]:"),
+            "{}",
+            warnings[1]
+        );
+    }
+
+    #[test]
+    fn record_literals() {
+        assert_eq!(
+            body_shape("f = { name = \"ada\", age = 30 + 6 }"),
+            "{ name=\"ada\" age=(+ 30 6) }"
+        );
+        assert_eq!(
+            body_shape("f = g {\n  name = \"ada\"\n  age = h 36\n} x"),
+            "(g { name=\"ada\" age=(h 36) } x)"
+        );
+        assert_eq!(body_shape("f = {}"), "{ }");
+    }
+
+    #[test]
+    fn field_access() {
+        assert_eq!(
+            body_shape("f = order.customer.address"),
+            "order.customer.address"
+        );
+        assert_eq!(body_shape("f = g x.y z"), "(g x.y z)");
+        assert_eq!(
+            body_shape("f = (make_point 1 2).x + p.y"),
+            "(+ (make_point 1 2).x p.y)"
+        );
+        assert_eq!(body_shape("f = -p.x"), "(unary - p.x)");
+        // With spaces, `.` is an ordinary operator, such as composition.
+        assert_eq!(body_shape("f = g . h"), "(. g h)");
+        assert!(matches!(
+            parse_one("f = 1.5").body,
+            Expr::LiteralFloat { value, .. } if value == 1.5
+        ));
+    }
+
+    #[test]
+    fn field_access_needs_a_field_name() {
+        let err = parse_err("f = p.(x)");
+        assert!(err.starts_with("test.mv:1:7: error[E0001]:"), "{}", err);
+        assert!(err.contains("expected a field name after ."), "{}", err);
+    }
+
+    #[test]
+    fn record_literal_errors() {
+        let err = parse_err("f = { a = 1, b = 2, a = 3 }");
+        assert!(err.starts_with("test.mv:1:21: error[E0006]:"), "{}", err);
+        assert!(err.contains("field `a` appears more than once"), "{}", err);
+        // Without a leading `field =`, braces are a block rather than a record.
+        assert!(matches!(parse_one("f = { g x }").body, Expr::Block { .. }));
+    }
+
+    /// Render a record pattern's fields as `name` or `name=pattern`, with a trailing `..`.
+    fn record_fields(predicate: &Predicate) -> Vec<String> {
+        match predicate {
+            Predicate::Record { fields, rest, .. } => {
+                let mut rendered: Vec<String> = fields
+                    .iter()
+                    .map(|(field, predicate)| match predicate.as_deref() {
+                        None => field.name.to_string(),
+                        Some(Predicate::Irrefutable(id)) => format!("{}={}", field.name, id.name),
+                        Some(predicate) => format!("{}={:?}", field.name, predicate),
+                    })
+                    .collect();
+                if *rest {
+                    rendered.push("..".to_string());
+                }
+                rendered
+            }
+            predicate => panic!("expected a record pattern, found {:?}", predicate),
+        }
+    }
+
+    #[test]
+    fn record_patterns() {
+        let decl = parse_one("get_name { name = n } = n");
+        assert_eq!(record_fields(&decl.predicates[0]), vec!["name=n"]);
+
+        let decl = parse_one("f {\n  name\n  age = a\n  ..\n} = a");
+        assert_eq!(
+            record_fields(&decl.predicates[0]),
+            vec!["name", "age=a", ".."]
+        );
+        let mut names = Vec::new();
+        decl.predicates[0].bound_names(&mut names);
+        assert_eq!(names, vec!["name", "a"]);
+
+        let decl = parse_one("f (Person { name, .. }) = name");
+        match &decl.predicates[0] {
+            Predicate::Ctor { ctor_id, dims } => {
+                assert_eq!(ctor_id.name, "Person");
+                assert_eq!(record_fields(&dims[0]), vec!["name", ".."]);
+            }
+            predicate => panic!("expected a ctor pattern, found {:?}", predicate),
+        }
+
+        let decl = parse_one("f r = match r { age = 0 } => baby { age } => age");
+        assert_eq!(match_arms(&decl.body).len(), 2);
+    }
+
+    #[test]
+    fn record_pattern_errors() {
+        let err = parse_err("f { a, b = x, a } = x");
+        assert!(err.starts_with("test.mv:1:15: error[E0005]:"), "{}", err);
+        assert!(parse_err("f { .., a } = a").starts_with("test.mv:1:9: error[E0001]:"));
+        assert!(parse_err("f { a = } = a").starts_with("test.mv:1:5: error[E0005]:"));
+    }
+
+    fn parse_data(input: &'static str) -> DataDecl<'static> {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance().unwrap();
+        let mut items = parse_many(parse_item, &mut lexer).unwrap();
+        assert_eq!(items.len(), 1);
+        match items.pop().unwrap() {
+            Item::Data(data_decl) => data_decl,
+            item => panic!("expected a data declaration, found {:?}", item),
+        }
+    }
+
+    /// Render each constructor of a data declaration with its number of arguments.
+    fn ctor_arities(data_decl: &DataDecl) -> Vec<String> {
+        data_decl
+            .ctors
+            .iter()
+            .map(|(ctor, arguments)| format!("{}/{}", ctor.name, arguments.len()))
+            .collect()
+    }
+
+    #[test]
+    fn data_decl_enum() {
+        let data_decl = parse_data("data Color = Red | Green | Blue");
+        assert_eq!(data_decl.name.name, "Color");
+        assert!(data_decl.type_params.is_empty());
+        assert_eq!(ctor_arities(&data_decl), vec!["Red/0", "Green/0", "Blue/0"]);
+        assert_eq!(data_decl.get_location().col, 1);
+        assert_eq!(data_decl.ctors[2].0.get_location().col, 28);
+    }
+
+    #[test]
+    fn data_decl_with_type_params() {
+        let data_decl = parse_data("data Either e a = Left e | Right (Maybe a) | Both [e] (e, a)");
+        let params: Vec<&str> = data_decl.type_params.iter().map(|id| id.name).collect();
+        assert_eq!(params, vec!["e", "a"]);
+        assert_eq!(
+            ctor_arities(&data_decl),
+            vec!["Left/1", "Right/1", "Both/2"]
+        );
+        assert!(matches!(
+            &data_decl.ctors[1].1[0],
+            TypeExpr::Application { arguments, .. } if arguments.len() == 1
+        ));
+        assert!(matches!(&data_decl.ctors[2].1[0], TypeExpr::List { .. }));
+        assert!(
+            matches!(&data_decl.ctors[2].1[1], TypeExpr::Tuple { dims, .. } if dims.len() == 2)
+        );
+    }
+
+    #[test]
+    fn multi_line_data_decl() {
+        let source = "data Shape\n  = Circle Float\n  | Rect Float Float\narea s = 0";
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let items = parse_many(parse_item, &mut lexer).unwrap();
+        assert_eq!(items.len(), 2);
+        match &items[0] {
+            Item::Data(data_decl) => {
+                assert_eq!(ctor_arities(data_decl), vec!["Circle/1", "Rect/2"]);
+                assert_eq!(data_decl.ctors[1].0.get_location().line, 3);
+            }
+            item => panic!("expected a data declaration, found {:?}", item),
+        }
+        assert!(matches!(&items[1], Item::Decl(decl) if decl.id.name == "area"));
+    }
+
+    #[test]
+    fn data_decl_errors() {
+        assert!(parse_err("data maybe a = Nothing").starts_with("test.mv:1:6: error[E0001]:"));
+        assert!(
+            parse_err("data Maybe a = Nothing | just a").starts_with("test.mv:1:26: error[E0001]:")
+        );
+    }
+
+    fn parse_err(input: &'static str) -> String {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance().unwrap();
+        parse_many(parse_item, &mut lexer).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn list_patterns() {
+        let decl = parse_one("f [] [w] [x, (a, b), y : ys] = x");
+        let dims: Vec<usize> = decl
+            .predicates
+            .iter()
+            .map(|predicate| match predicate {
+                Predicate::List { dims, .. } => dims.len(),
+                predicate => panic!("expected a list pattern, found {:?}", predicate),
+            })
+            .collect();
+        assert_eq!(dims, vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn list_pattern_in_match_arm() {
+        let decl = parse_one("f xs = match xs [] => 0 [x] => 1 _ => 2");
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 3);
+        assert!(matches!(arms[0].predicate, Predicate::List { .. }));
+    }
+
+    #[test]
+    fn errors_carry_codes() {
+        assert!(parse_err("f = a + * b").contains("error[E0001]:"));
+        assert!(parse_err("f = (a, b").contains("error[E0003]:"));
+        assert!(parse_err("f x = match x").contains("error[E0006]:"));
+    }
+
+    #[test]
+    fn as_patterns() {
+        let decl = parse_one("f whole@(Pair a b) = whole");
+        match &decl.predicates[0] {
+            Predicate::As { binding, pattern } => {
+                assert_eq!(binding.name, "whole");
+                assert!(
+                    matches!(pattern.as_ref(), Predicate::Ctor { dims, .. } if dims.len() == 2)
+                );
+            }
+            predicate => panic!("expected an as-pattern, found {:?}", predicate),
+        }
+        assert_eq!(decl.predicates[0].get_location().col, 3);
+    }
+
+    #[test]
+    fn nested_as_patterns() {
+        let decl = parse_one("f a@(b@(C x)) = x");
+        match &decl.predicates[0] {
+            Predicate::As { pattern, .. } => {
+                assert!(
+                    matches!(pattern.as_ref(), Predicate::As { binding, .. } if binding.name == "b")
+                )
+            }
+            predicate => panic!("expected an as-pattern, found {:?}", predicate),
+        }
+    }
+
+    #[test]
+    fn as_pattern_without_pattern() {
+        let err = parse_err("f x@ = x");
+        assert!(err.starts_with("test.mv:1:4: error[E0005]:"), "{}", err);
+    }
+
+    /// The alternatives of an or-pattern, rendered with `shape`-like brevity.
+    fn or_alternatives(predicate: &Predicate) -> Vec<String> {
+        match predicate {
+            Predicate::Or { alternatives, .. } => alternatives
+                .iter()
+                .map(|alternative| match alternative.as_ref() {
+                    Predicate::Integer { value, .. } => value.to_string(),
+                    Predicate::Ctor { ctor_id, dims } => format!("{}/{}", ctor_id.name, dims.len()),
+                    Predicate::Irrefutable(id) => id.name.to_string(),
+                    predicate => format!("{:?}", predicate),
+                })
+                .collect(),
+            predicate => panic!("expected an or-pattern, found {:?}", predicate),
+        }
+    }
+
+    #[test]
+    fn or_patterns_in_match_arms() {
+        let decl = parse_one("f x = match x 1 | 2 | 3 => \"small\" _ => \"big\"");
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 2);
+        assert_eq!(or_alternatives(&arms[0].predicate), vec!["1", "2", "3"]);
+        assert_eq!(arms[0].predicate.get_location().col, 15);
+
+        let decl = parse_one("f x = match x Just 1 | Just 2 => 0 (Nothing | Just _) => 1");
+        let arms = match_arms(&decl.body);
+        assert_eq!(
+            or_alternatives(&arms[0].predicate),
+            vec!["Just/1", "Just/1"]
+        );
+        assert_eq!(
+            or_alternatives(&arms[1].predicate),
+            vec!["Nothing/0", "Just/1"]
+        );
+    }
+
+    #[test]
+    fn or_pattern_alternatives_bind_the_same_names() {
+        let decl = parse_one("f x = match x (Left y | Right y) => y");
+        let arms = match_arms(&decl.body);
+        assert_eq!(
+            or_alternatives(&arms[0].predicate),
+            vec!["Left/1", "Right/1"]
+        );
+
+        let err = parse_err("f x = match x Left y | Right z => y");
+        assert!(err.starts_with("test.mv:1:24: error[E0005]:"), "{}", err);
+        assert!(err.contains("expected [y], found [z]"), "{}", err);
+        assert!(parse_err("f (1 | ) = 0").starts_with("test.mv:1:6: error[E0005]:"));
+        let err = parse_err("f (Just (a, _) | Just (_, b)) = 1");
+        assert!(err.starts_with("test.mv:1:18: error[E0005]:"), "{}", err);
+    }
+
+    #[test]
+    fn patterns_bind_each_name_once() {
+        let err = parse_err("f (x, x) = x");
+        assert!(err.starts_with("test.mv:1:7: error[E0005]:"), "{}", err);
+        assert!(err.contains("`x` is bound more than once"), "{}", err);
+        let err = parse_err("f p = match p (a, Just a) => a");
+        assert!(err.starts_with("test.mv:1:24: error[E0005]:"), "{}", err);
+        let err = parse_err("f (xs@(x : xs)) = x");
+        assert!(err.starts_with("test.mv:1:12: error[E0005]:"), "{}", err);
+        parse_one("f (x, _, _) = x");
+        parse_one("f (Left x | Right x) = x");
+    }
+
+    #[test]
+    fn parameters_bind_each_name_once() {
+        let err = parse_err("f x x = x");
+        assert!(err.starts_with("test.mv:1:5: error[E0005]:"), "{}", err);
+        assert!(
+            err.contains("`x` is bound more than once in these parameters\n"),
+            "{}",
+            err
+        );
+        assert!(!err.contains("first at"), "{}", err);
+        assert!(
+            err.contains("test.mv:1:3: note: `x` is first bound here"),
+            "{}",
+            err
+        );
+        let err = parse_err("f (a, (b, x)) (Just x) = x");
+        assert!(err.starts_with("test.mv:1:21: error[E0005]:"), "{}", err);
+        assert!(err.contains("test.mv:1:11: note:"), "{}", err);
+        let err = parse_err("f = \\x (y, x) -> y");
+        assert!(err.starts_with("test.mv:1:12: error[E0005]:"), "{}", err);
+        let err = parse_err("f p = match p (a, (b, a)) => a");
+        assert!(err.starts_with("test.mv:1:23: error[E0005]:"), "{}", err);
+        parse_one("f _ _ = 1");
+        parse_one("f (_, x) _ (_, y) = x");
+        parse_one("f = \\_ _ -> 1");
+        parse_one("f x = \\x -> x");
+    }
+
+    #[test]
+    fn let_with_several_bindings() {
+        assert_eq!(
+            to_sexpr(&parse_one("f x = let a = x; b = a + 1 in b").body),
+            "(let a (symbol x) (let b (binary-op + (symbol a) (lit-int 1)) (symbol b)))"
+        );
+        let body = parse_one("f x = { y = let a = x\n b = a\n c = b in c }").body;
+        let Expr::Record { fields, .. } = &body else {
+            panic!("expected a record, got {:?}", body);
+        };
+        assert_eq!(
+            to_sexpr(&fields[0].1),
+            "(let a (symbol x) (let b (symbol a) (let c (symbol b) (symbol c))))"
+        );
+    }
+
+    #[test]
+    fn let_bindings_separated_by_layout() {
+        let expected = "(let a (symbol x) (let b (symbol a) (symbol b)))";
+        for source in [
+            "f x =\n  let a = x\n      b = a\n  in b",
+            "f x = let a = x\n          b = a\n      in b",
+            "f x = g (let a = x\n             b = a\n         in b)",
+            "f x =\n  let a = x\n      b = a\n  in\n      b",
+        ] {
+            let body = parse_one(source).body;
+            let body = match &body {
+                Expr::Callsite { arguments, .. } => arguments[0].as_ref(),
+                body => body,
+            };
+            assert_eq!(to_sexpr(body), expected, "{}", source);
+        }
+        // A binding's value may continue on a line indented past the bindings' column.
+        assert_eq!(
+            to_sexpr(&parse_one("f x =\n  let a = g\n        x\n      b = a\n  in b").body),
+            "(let a (callsite (symbol g) (symbol x)) (let b (symbol a) (symbol b)))"
+        );
+        // The bindings of a nested let line up with each other, not with the outer ones.
+        assert_eq!(
+            to_sexpr(
+                &parse_one(
+                    "f x =\n  let a = let c = x\n              d = c\n          in d\n      b = a\n  in b"
+                )
+                .body
+            ),
+            "(let a (let c (symbol x) (let d (symbol c) (symbol d))) (let b (symbol a) (symbol b)))"
+        );
+    }
+
+    #[test]
+    fn let_statements_in_do_blocks() {
+        let body =
+            parse_one("f x = do {\n    let a = x\n    let b = a + 1\n    let c = b * 2\n    c\n}")
+                .body;
+        let Expr::Do {
+            statements, result, ..
+        } = &body
+        else {
+            panic!("expected a do-block, got {:?}", body);
+        };
+        assert_eq!(statements.len(), 3);
+        assert_eq!(
+            to_sexpr(&body),
+            "(do (let a (symbol x)) (let b (binary-op + (symbol a) (lit-int 1))) \
+             (let c (binary-op * (symbol b) (lit-int 2))) (symbol c))"
+        );
+        // The same as nesting each let around the rest of the block.
+        assert_eq!(
+            to_sexpr(&desugar_do(statements, result)),
+            to_sexpr(&parse_one("f x = let a = x in let b = a + 1 in let c = b * 2 in c").body)
+        );
+        assert_eq!(
+            to_sexpr(&match &parse_one("f = do { g 1; let y = 2 in y }").body {
+                Expr::Do {
+                    statements, result, ..
+                } => desugar_do(statements, result),
+                body => panic!("expected a do-block, got {:?}", body),
+            }),
+            "(binary-op >> (callsite (symbol g) (lit-int 1)) (let y (lit-int 2) (symbol y)))"
+        );
+
+        let err = parse_err("f = do { let a = 1 }");
+        assert!(err.starts_with("test.mv:1:14: error[E0006]:"), "{}", err);
+        assert!(err.contains("must end with an expression"), "{}", err);
+        let err = parse_err("f = do { }");
+        assert!(err.starts_with("test.mv:1:5: error[E0006]:"), "{}", err);
+        let err = parse_err("f = do { a b");
+        assert!(err.starts_with("test.mv:1:5: error[E0003]:"), "{}", err);
+        // Outside a do-block, a let needs its `in`.
+        let err = parse_err("f = let a = 1\ng = a");
+        assert!(err.starts_with("test.mv:1:5: error[E0006]:"), "{}", err);
+        assert!(err.contains("missing its `in`"), "{}", err);
+        assert!(err.contains("do-block"), "{}", err);
+    }
+
+    #[test]
+    fn block_expressions() {
+        let body = parse_one("f x = {\n    log x\n    let y = x + 1\n    y * 2\n}").body;
+        let Expr::Block {
+            statements, result, ..
+        } = &body
+        else {
+            panic!("expected a block, got {:?}", body);
+        };
+        assert_eq!(statements.len(), 2);
+        assert!(matches!(statements[0], DoStatement::Expr(_)));
+        assert!(matches!(statements[1], DoStatement::Let { .. }));
+        assert_eq!(to_sexpr(result), "(binary-op * (symbol y) (lit-int 2))");
+        assert_eq!(
+            to_sexpr(&parse_one("f x = { g x; h x }").body),
+            "(block (callsite (symbol g) (symbol x)) (callsite (symbol h) (symbol x)))"
+        );
+        // Braces starting with `field =`, or empty braces, are still a record.
+        assert!(matches!(parse_one("f = {}").body, Expr::Record { .. }));
+        assert!(matches!(
+            parse_one("f x = { a = x }").body,
+            Expr::Record { .. }
+        ));
+
+        let err = parse_err("f x = { g x; let y = x }");
+        assert!(err.starts_with("test.mv:1:18: error[E0006]:"), "{}", err);
+        assert!(
+            err.contains("a block must end with an expression"),
+            "{}",
+            err
+        );
+        let err = parse_err("f x = { y <- g x; y }");
+        assert!(err.starts_with("test.mv:1:11: error[E0006]:"), "{}", err);
+        let err = parse_err("f x = { g x h");
+        assert!(err.starts_with("test.mv:1:7: error[E0003]:"), "{}", err);
+        assert!(err.contains("this block was closed"), "{}", err);
+    }
+
+    #[test]
+    fn binds_in_do_blocks() {
+        let body = parse_one(
+            "main = do {\n    line <- read_line\n    let n = parse line\n    print n\n    \
+             m <- ask n\n    pure (n + m)\n}",
+        )
+        .body;
+        let Expr::Do {
+            statements, result, ..
+        } = &body
+        else {
+            panic!("expected a do-block, got {:?}", body);
+        };
+        assert_eq!(statements.len(), 4);
+        assert!(matches!(statements[0], DoStatement::Bind { .. }));
+        assert!(matches!(statements[1], DoStatement::Let { .. }));
+        assert!(matches!(statements[2], DoStatement::Expr(_)));
+        assert!(matches!(statements[3], DoStatement::Bind { .. }));
+        assert_eq!(
+            to_sexpr(&body),
+            "(do (bind line (symbol read_line)) \
+             (let n (callsite (symbol parse) (symbol line))) \
+             (callsite (symbol print) (symbol n)) \
+             (bind m (callsite (symbol ask) (symbol n))) \
+             (callsite (symbol pure) (binary-op + (symbol n) (symbol m))))"
+        );
+        assert_eq!(
+            to_sexpr(&desugar_do(statements, result)),
+            "(binary-op >>= (symbol read_line) (lambda (line) \
+             (let n (callsite (symbol parse) (symbol line)) \
+             (binary-op >> (callsite (symbol print) (symbol n)) \
+             (binary-op >>= (callsite (symbol ask) (symbol n)) (lambda (m) \
+             (callsite (symbol pure) (binary-op + (symbol n) (symbol m)))))))))"
+        );
+
+        let err = parse_err("f = do { a; x <- g a }");
+        assert!(err.starts_with("test.mv:1:15: error[E0006]:"), "{}", err);
+        assert!(err.contains("must end with an expression"), "{}", err);
+        let err = parse_err("f = x <- g 1");
+        assert!(err.starts_with("test.mv:1:7: error[E0006]:"), "{}", err);
+        assert!(
+            err.contains("only bind a name as a statement in a do-block"),
+            "{}",
+            err
+        );
+        let err = parse_err("f = do { g x <- h; x }");
+        assert!(err.starts_with("test.mv:1:14: error[E0006]:"), "{}", err);
+    }
+
+    #[test]
+    fn recursive_and_function_style_lets() {
+        let body = parse_one("g = let rec f = \\x -> f x in f 1").body;
+        assert!(matches!(body, Expr::LetRec { .. }));
+        assert_eq!(
+            to_sexpr(&body),
+            "(let-rec (binding f (lambda (x) (callsite (symbol f) (symbol x)))) \
+             (callsite (symbol f) (lit-int 1)))"
+        );
+        let body = parse_one("f = let rec go n = go n in go 10").body;
+        assert_eq!(
+            to_sexpr(&body),
+            "(let-rec (binding go (lambda (n) (callsite (symbol go) (symbol n)))) \
+             (callsite (symbol go) (lit-int 10)))"
+        );
+        // The bindings of one `let rec` form a single group, unlike nested `let rec`s.
+        let body = parse_one("f = let rec even n = odd n; odd n = even n in even 2").body;
+        let Expr::LetRec { bindings, body, .. } = &body else {
+            panic!("expected a recursive let, got {:?}", body);
+        };
+        assert_eq!(bindings.len(), 2);
+        assert!(matches!(**body, Expr::Callsite { .. }));
+        let body = parse_one("f = let rec a = b in let rec b = a in a").body;
+        let Expr::LetRec { bindings, body, .. } = &body else {
+            panic!("expected a recursive let, got {:?}", body);
+        };
+        assert_eq!(bindings.len(), 1);
+        assert!(matches!(**body, Expr::LetRec { .. }));
+        assert!(matches!(
+            parse_one("f = let g x y = x in g").body,
+            Expr::Let { .. }
+        ));
+    }
+
+    #[test]
+    fn let_patterns() {
+        let body = parse_one("f pair = let (a, b) = pair in a").body;
+        assert!(matches!(body, Expr::LetPattern { .. }));
+        assert_eq!(
+            to_sexpr(&body),
+            "(let (pat-tuple (pat-bind a) (pat-bind b)) (symbol pair) (symbol a))"
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("f p = let (a, (b, c)) = p in c").body),
+            "(let (pat-tuple (pat-bind a) (pat-tuple (pat-bind b) (pat-bind c))) (symbol p) \
+             (symbol c))"
+        );
+        assert_eq!(
+            to_sexpr(&parse_one("f p = let Pair x (Just y) = p; z = x in y").body),
+            "(let (pat-ctor Pair (pat-bind x) (pat-ctor Just (pat-bind y))) (symbol p) \
+             (let z (symbol x) (symbol y)))"
+        );
+        // Refutable patterns are left for exhaustiveness checking.
+        assert!(matches!(
+            parse_one("f = let 0 = 1 in 2").body,
+            Expr::LetPattern { .. }
+        ));
+        assert!(matches!(
+            parse_one("f = let Nothing = g in 2").body,
+            Expr::LetPattern { .. }
+        ));
+        // A plain name is still the simple form.
+        assert!(matches!(
+            parse_one("f = let a = 1 in a").body,
+            Expr::Let { .. }
+        ));
+        let err = parse_err("f = let rec (a, b) = p in a");
+        assert!(err.starts_with("test.mv:1:13: error[E0005]:"), "{}", err);
+        let err = parse_err("f = let (a, a) = p in a");
+        assert!(err.starts_with("test.mv:1:13: error[E0005]:"), "{}", err);
+        let err = parse_err("f = let (a, b) p in a");
+        assert!(err.starts_with("test.mv:1:16: error[E0001]:"), "{}", err);
+        assert!(err.contains("`=` after this pattern"), "{}", err);
+
+        let source = "f p = let (a, b, _c) = p in a";
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let mut diagnostics = Diagnostics::new(source);
+        parse_items(&mut lexer, &mut diagnostics);
+        assert_eq!(diagnostics.warnings().len(), 1);
+        assert!(diagnostics.warnings()[0]
+            .to_string()
+            .starts_with("test.mv:1:15: warning[W0001]: unused let binding `b`"));
+    }
+
+    #[test]
+    fn let_binding_errors() {
+        let err = parse_err("f = let a = 1; b 2 in b");
+        assert!(err.starts_with("test.mv:1:18: error[E0001]:"), "{}", err);
+        assert!(err.contains("expected `=` after `b`"), "{}", err);
+        let err = parse_err("f = let a = 1; in a");
+        assert!(err.starts_with("test.mv:1:14: error[E0001]:"), "{}", err);
+        let err = parse_err("f = let a = 1; b = 2");
+        assert!(err.starts_with("test.mv:1:5: error[E0006]:"), "{}", err);
+        assert!(err.contains("missing its `in`"), "{}", err);
+    }
+
+    #[test]
+    fn decl_equals_is_distinct_from_operators() {
+        assert_eq!(to_sexpr(&parse_one("foo = 1").body), "(lit-int 1)");
+        assert_eq!(body_shape("foo = a == b"), "(== a b)");
+        let err = parse_err("foo == 1");
+        assert!(err.starts_with("test.mv:1:5: error[E0001]:"), "{}", err);
+        assert!(err.contains("expected `=` to define `foo`"), "{}", err);
+    }
+
+    #[test]
+    fn decl_without_a_body() {
+        let err = parse_err("f x =");
+        assert_eq!(
+            err,
+            "test.mv:1:5: error[E0006]: declaration `f` has no body\n\
+             test.mv:1:1: note: `f` is declared here"
+        );
+        let err = parse_err("f x =\ng = 1");
+        assert!(err.starts_with("test.mv:1:5: error[E0006]:"), "{}", err);
+        assert!(err.contains("declaration `f` has no body"), "{}", err);
+        let err = parse_err("  f =\ndata T = T");
+        assert!(err.starts_with("test.mv:1:5: error[E0006]:"), "{}", err);
+
+        // The next decl still parses once the broken one is reported.
+        let source = "f x =\ng = 1";
+        let mut lexer = Lexer::new("test.mv", source);
+        lexer.advance().unwrap();
+        let mut diagnostics = Diagnostics::new(source);
+        let items = parse_items(&mut lexer, &mut diagnostics);
+        assert_eq!(items.len(), 1);
+        assert_eq!(diagnostics.errors().len(), 1);
+        assert_eq!(diagnostics.errors()[0].notes().len(), 1);
+    }
+
+    #[test]
+    fn nesting_limit_can_be_changed() {
+        let parse_with_limit = |input: &'static str, max_depth: usize| {
+            let mut lexer = Lexer::new("test.mv", input);
+            lexer.set_max_depth(max_depth);
+            lexer.advance().unwrap();
+            parse_many(parse_item, &mut lexer).map_err(|err| err.to_string())
+        };
+        assert!(parse_with_limit("f = (((1)))", 4).is_ok());
+        let err = parse_with_limit("f = ((((1))))", 4).unwrap_err();
+        assert!(err.starts_with("test.mv:1:9: error[E0009]:"), "{}", err);
+        let err = parse_with_limit("f ((x)) = x", 2).unwrap_err();
+        assert!(err.starts_with("test.mv:1:5: error[E0009]:"), "{}", err);
+        assert!(parse_with_limit("f = ((((((((1))))))))", DEFAULT_MAX_DEPTH).is_ok());
+    }
+
+    #[test]
+    fn keywords_are_listed_in_one_place() {
+        let mut expected = vec![
+            "as", "data", "do", "else", "if", "import", "in", "infix", "infixl", "infixr", "let",
+            "match", "module", "rec", "then", "type", "where",
+        ];
+        let mut listed = keywords().to_vec();
+        listed.sort();
+        expected.sort();
+        assert_eq!(listed, expected);
+        assert!(keywords().iter().all(|keyword| is_keyword(keyword)));
+        assert!(!is_keyword("lets"));
+        assert!(!is_keyword("Match"));
+    }
+
+    #[test]
+    fn keywords_cannot_be_bound() {
+        for (input, col, keyword) in [
+            ("then x = x", 1, "then"),
+            ("f type = 1", 3, "type"),
+            ("f = let in = 3 in in", 9, "in"),
+            ("f = let g where = 3 in g", 11, "where"),
+            ("f = \\x match -> x", 8, "match"),
+        ] {
+            let err = parse_err(input);
+            assert!(
+                err.starts_with(&format!(
+                    "test.mv:1:{}: error[E0001]: `{}` is a keyword and cannot be used as a name",
+                    col, keyword
+                )),
+                "{}",
+                err
+            );
+        }
+        let err = parse_err("f = then");
+        assert!(
+            err.starts_with(
+                "test.mv:1:5: error[E0006]: expected an expression, but found the keyword `then`"
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn misspelled_keywords_get_suggestions() {
+        let err = parse_err("f = let x inn x");
+        assert!(err.starts_with("test.mv:1:11: error[E0001]:"), "{}", err);
+        assert!(err.ends_with("did you mean `in`?"), "{}", err);
+        assert!(!parse_err("f = let x y in x").contains("did you mean"));
+        let err = parse_err("f x = let y inn y");
+        assert!(err.starts_with("test.mv:1:13: error[E0001]:"), "{}", err);
+        assert!(err.ends_with("did you mean `in`?"), "{}", err);
+        // A parameter which merely resembles a keyword is fine when the binding has its `=`.
+        parse_one("f = let g i = i + 1 in g 1");
+        let err = parse_err("import Foo ass Bar");
+        assert!(err.starts_with("test.mv:1:12: error[E0001]:"), "{}", err);
+        assert!(err.ends_with("did you mean `as`?"), "{}", err);
+        assert!(!parse_err("f (x y) = x").contains("did you mean"));
+        // A typo of `then`, `else` or `in` is read as an argument, and found again once the
+        // keyword turns out to be missing.
+        let err = parse_err("f = if a then b els c");
+        assert!(err.starts_with("test.mv:1:17: error[E0001]:"), "{}", err);
+        assert!(
+            err.contains("expected `else` to continue the if"),
+            "{}",
+            err
+        );
+        assert!(err.ends_with("did you mean `else`?"), "{}", err);
+        let err = parse_err("f = if a then b + g ese c");
+        assert!(err.starts_with("test.mv:1:21: error[E0001]:"), "{}", err);
+        assert!(err.ends_with("did you mean `else`?"), "{}", err);
+        let err = parse_err("f = if a thn b else c");
+        assert!(err.starts_with("test.mv:1:10: error[E0001]:"), "{}", err);
+        assert!(err.ends_with("did you mean `then`?"), "{}", err);
+        let err = parse_err("f = let x = 1 inn x");
+        assert!(err.starts_with("test.mv:1:15: error[E0001]:"), "{}", err);
+        assert!(err.contains("expected `in` to end the let"), "{}", err);
+        assert!(err.ends_with("did you mean `in`?"), "{}", err);
+        // Names shorter than three letters are never taken for typos.
+        assert!(!parse_err("data t = A").contains("did you mean"));
+        assert!(!parse_err("f = if a then b el c").contains("did you mean"));
+    }
+
+    #[test]
+    fn list_pattern_errors() {
+        assert!(parse_err("f [x,, y] = x").starts_with("test.mv:1:6:"));
+        let err = parse_err("f [x y] = x");
+        assert!(err.starts_with("test.mv:1:3:"), "{}", err);
+        assert!(err.contains("close this list pattern"), "{}", err);
+    }
+
+    /// Flatten a chain of cons patterns into the names of its parts.
+    fn cons_parts(predicate: &Predicate) -> Vec<String> {
+        match predicate {
+            Predicate::Cons { head, tail } => {
+                let mut parts = cons_parts(head);
+                parts.extend(cons_parts(tail));
+                parts
+            }
+            Predicate::Irrefutable(id) => vec![id.name.to_string()],
+            Predicate::Ctor { ctor_id, .. } => vec![ctor_id.name.to_string()],
+            predicate => vec![format!("{:?}", predicate)],
+        }
+    }
+
+    #[test]
+    fn cons_pattern_in_decl_head() {
+        let decl = parse_one("sum (x : xs) = add x (sum xs)");
+        assert_eq!(decl.predicates.len(), 1);
+        assert_eq!(cons_parts(&decl.predicates[0]), vec!["x", "xs"]);
+    }
+
+    #[test]
+    fn cons_pattern_is_right_associative() {
+        let decl = parse_one("f (x : y : rest) = rest");
+        match &decl.predicates[0] {
+            Predicate::Cons { head, tail } => {
+                assert!(matches!(head.as_ref(), Predicate::Irrefutable(_)));
+                assert!(matches!(tail.as_ref(), Predicate::Cons { .. }));
+            }
+            predicate => panic!("expected a cons pattern, found {:?}", predicate),
+        }
+    }
+
+    #[test]
+    fn cons_pattern_in_match_arm() {
+        let decl = parse_one("len xs = match xs [] => 0; Just x : rest => 1 + len rest");
+        let arms = match_arms(&decl.body);
+        assert_eq!(arms.len(), 2);
+        assert_eq!(cons_parts(&arms[1].predicate), vec!["Just", "rest"]);
+        assert!(matches!(arms[1].expr, Expr::BinaryOp { .. }));
+    }
+
+    #[test]
+    fn match_without_arms_is_an_error() {
+        let mut lexer = Lexer::new("test.mv", "f x = match x");
+        lexer.advance().unwrap();
+        assert!(parse_many(parse_decl, &mut lexer).is_err());
+    }
+
+    #[test]
+    fn lookahead_backtracks_without_cloning_the_lexer() {
+        let source = "f (a, b) = match [x | x <- (1..a)] (-1) if b => { y = -b } _ => c ? (+) : d";
+        let tokens = |look_ahead: bool| {
+            let mut lexer = Lexer::new("test.mv", source);
+            lexer.advance().unwrap();
+            let mut tokens = Vec::new();
+            while !lexer.at_eof() {
+                let token = lexer.peek();
+                if look_ahead {
+                    starts_match_arm(&mut lexer);
+                    starts_record(&mut lexer);
+                    parenthesized(&mut lexer);
+                    section_operator(&mut lexer);
+                    ternary_operator(&mut lexer);
+                    bind_arrow(&mut lexer);
+                    at_expression_end(&mut lexer);
+                }
+                tokens.push(token);
+                lexer.advance().unwrap();
+            }
+            tokens
+        };
+        let clones = crate::lexer::clone_count();
+        assert_eq!(tokens(true), tokens(false));
+        parse_one(source);
+        assert_eq!(crate::lexer::clone_count(), clones);
+    }
+
+    #[test]
+    fn interned_identifiers_carry_their_symbols() {
+        let interner = Rc::new(RefCell::new(Interner::new()));
+        let mut lexer = Lexer::with_interner("test.mv", "f foo = bar foo", interner.clone());
+        lexer.advance().unwrap();
+        let decl = parse_many(parse_decl, &mut lexer).unwrap().remove(0);
+        let param = match &decl.clauses[0].predicates[0] {
+            Predicate::Irrefutable(id) => id.symbol(),
+            predicate => panic!("expected a binding, found {:?}", predicate),
+        };
+        let argument = match &decl.clauses[0].body {
+            Expr::Callsite { arguments, .. } => match arguments[0].as_ref() {
+                Expr::Symbol { id } => id.symbol(),
+                expr => panic!("expected a symbol, found {:?}", expr),
+            },
+            expr => panic!("expected a callsite, found {:?}", expr),
+        };
+        assert_eq!(param, interner.borrow().get("foo"));
+        assert_eq!(argument, param);
+        assert_eq!(decl.id.symbol(), interner.borrow().get("f"));
+
+        let interner = Rc::new(RefCell::new(Interner::new()));
+        let mut lexer = Lexer::with_interner("test.mv", "f x y x = x", interner);
+        lexer.advance().unwrap();
+        assert!(parse_many(parse_decl, &mut lexer).is_err());
     }
 }
@@ -0,0 +1,384 @@
+use crate::error::{Diagnostics, ErrorCode, ParseError};
+use crate::fixity::FixityTable;
+use crate::identifier::Identifier;
+use crate::location::HasLocation;
+use crate::module::Module;
+use crate::parser::{is_operator_name, name_source, Decl, DoStatement, Expr, InterpPart, Item};
+
+/// The names visible at some point in a decl body, innermost last.
+struct Scope<'a> {
+    globals: Vec<&'a str>,
+    locals: Vec<&'a str>,
+    /// The operators every module has, which need no definition.
+    builtin_operators: FixityTable<'a>,
+}
+
+impl<'a> Scope<'a> {
+    fn contains(&self, name: &str) -> bool {
+        self.locals.iter().rev().any(|local| *local == name)
+            || self.globals.contains(&name)
+            || (is_operator_name(name) && self.builtin_operators.get(name).is_some())
+    }
+
+    /// Run `f` with `names` in scope, removing them again afterwards.
+    fn with<F>(&mut self, names: &[&'a str], f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        let depth = self.locals.len();
+        self.locals.extend_from_slice(names);
+        f(self);
+        self.locals.truncate(depth);
+    }
+}
+
+fn unbound_symbol<'a>(id: &Identifier<'a>) -> ParseError<'a> {
+    ParseError::error(
+        ErrorCode::UnboundSymbol,
+        *id.get_location(),
+        format!("`{}` is not defined", name_source(id.name)),
+    )
+}
+
+/// Resolve the statements of a block or do-block, and its result if it is separate from them.
+/// Each binding is in scope for everything after it.
+fn resolve_statements<'a>(
+    statements: &[DoStatement<'a>],
+    result: &Expr<'a>,
+    scope: &mut Scope<'a>,
+    errors: &mut Vec<ParseError<'a>>,
+) {
+    let mut names = Vec::new();
+    for statement in statements {
+        scope.with(&names, |scope| {
+            resolve_expr(statement.value(), scope, errors)
+        });
+        names.extend(statement.binding().map(|binding| binding.name));
+    }
+    scope.with(&names, |scope| resolve_expr(result, scope, errors));
+}
+
+fn resolve_expr<'a>(expr: &Expr<'a>, scope: &mut Scope<'a>, errors: &mut Vec<ParseError<'a>>) {
+    match expr {
+        Expr::Symbol { id } => {
+            if !scope.contains(id.name) {
+                errors.push(unbound_symbol(id));
+            }
+        }
+        Expr::Lambda { params, body, .. } => {
+            let mut names = Vec::new();
+            for param in params {
+                param.bound_names(&mut names);
+            }
+            scope.with(&names, |scope| resolve_expr(body, scope, errors));
+        }
+        Expr::Let {
+            binding,
+            value,
+            body,
+            ..
+        } => {
+            resolve_expr(value, scope, errors);
+            scope.with(&[binding.name], |scope| resolve_expr(body, scope, errors));
+        }
+        Expr::LetRec { bindings, body, .. } => {
+            let group: Vec<&str> = bindings.iter().map(|(binding, _)| binding.name).collect();
+            scope.with(&group, |scope| {
+                for (_, value) in bindings {
+                    resolve_expr(value, scope, errors);
+                }
+                resolve_expr(body, scope, errors);
+            });
+        }
+        Expr::LetPattern {
+            pattern,
+            value,
+            body,
+            ..
+        } => {
+            resolve_expr(value, scope, errors);
+            let mut names = Vec::new();
+            pattern.bound_names(&mut names);
+            scope.with(&names, |scope| resolve_expr(body, scope, errors));
+        }
+        Expr::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            resolve_expr(condition, scope, errors);
+            resolve_expr(then_branch, scope, errors);
+            resolve_expr(else_branch, scope, errors);
+        }
+        // Qualified names refer to other modules, which are resolved separately.
+        Expr::Path { .. } => {}
+        Expr::Hole { .. } => {}
+        Expr::LiteralInteger { .. }
+        | Expr::LiteralFloat { .. }
+        | Expr::LiteralString { .. }
+        | Expr::LiteralChar { .. }
+        | Expr::LiteralBool { .. } => {}
+        Expr::Interp { parts, .. } => {
+            for expr in parts.iter().filter_map(InterpPart::expr) {
+                resolve_expr(expr, scope, errors);
+            }
+        }
+        Expr::Do {
+            statements, result, ..
+        }
+        | Expr::Block {
+            statements, result, ..
+        } => resolve_statements(statements, result, scope, errors),
+        Expr::Match {
+            subject,
+            pattern_exprs,
+            ..
+        } => {
+            resolve_expr(subject, scope, errors);
+            for arm in pattern_exprs {
+                let mut names = Vec::new();
+                arm.predicate.bound_names(&mut names);
+                scope.with(&names, |scope| {
+                    if let Some(guard) = &arm.guard {
+                        resolve_expr(guard, scope, errors);
+                    }
+                    resolve_expr(&arm.expr, scope, errors);
+                });
+            }
+        }
+        Expr::Callsite {
+            function,
+            arguments,
+            ..
+        } => {
+            resolve_expr(function, scope, errors);
+            for argument in arguments {
+                resolve_expr(argument, scope, errors);
+            }
+        }
+        Expr::TupleCtor { dims, .. } => {
+            for dim in dims {
+                resolve_expr(dim, scope, errors);
+            }
+        }
+        Expr::List { items, .. } => {
+            for item in items {
+                resolve_expr(item, scope, errors);
+            }
+        }
+        Expr::Comprehension {
+            output,
+            generators,
+            guards,
+            ..
+        } => {
+            // bound[i] is how many names the first i generators bind.
+            let mut bound = vec![0];
+            let mut names = Vec::new();
+            for generator in generators {
+                scope.with(&names, |scope| {
+                    resolve_expr(&generator.source, scope, errors)
+                });
+                generator.pattern.bound_names(&mut names);
+                bound.push(names.len());
+            }
+            for (after, guard) in guards {
+                scope.with(&names[..bound[*after]], |scope| {
+                    resolve_expr(guard, scope, errors)
+                });
+            }
+            scope.with(&names, |scope| resolve_expr(output, scope, errors));
+        }
+        Expr::Record { fields, .. } => {
+            for (_, value) in fields {
+                resolve_expr(value, scope, errors);
+            }
+        }
+        // Field names are resolved against the record's type, which is not known here.
+        Expr::FieldAccess { subject, .. } => resolve_expr(subject, scope, errors),
+        Expr::BinaryOp { op, lhs, rhs } => {
+            resolve_expr(lhs, scope, errors);
+            if !scope.contains(op.name) {
+                errors.push(unbound_symbol(op));
+            }
+            resolve_expr(rhs, scope, errors);
+        }
+        Expr::UnaryOp { operand, .. } => resolve_expr(operand, scope, errors),
+        Expr::Range {
+            start, step, end, ..
+        } => {
+            for bound in [start, step, end].into_iter().flatten() {
+                resolve_expr(bound, scope, errors);
+            }
+        }
+    }
+}
+
+/// Find every symbol in `module` which does not refer to a parameter, a `let` or lambda binding,
+/// a match arm binding, a top-level decl, a data constructor or a name imported by a selective
+/// import, in source order. An import of everything a module exports, or under an alias, could
+/// bring in any name, so no symbol is reported unbound in a module with one.
+pub fn unbound_symbols<'a>(module: &Module<'a>) -> Vec<ParseError<'a>> {
+    let mut globals = Vec::new();
+    for item in &module.items {
+        match item {
+            Item::Decl(decl) => globals.push(decl.id.name),
+            Item::Data(data_decl) => {
+                globals.extend(data_decl.ctors.iter().map(|(ctor, _)| ctor.name))
+            }
+            Item::Import(import) => match (&import.alias, &import.names) {
+                (None, Some(names)) => globals.extend(names.iter().map(|name| name.name)),
+                _ => return Vec::new(),
+            },
+            Item::Fixity(_) => {}
+        }
+    }
+    let mut scope = Scope {
+        globals,
+        locals: Vec::new(),
+        builtin_operators: FixityTable::builtin(),
+    };
+    let mut errors = Vec::new();
+    for item in &module.items {
+        if let Item::Decl(decl) = item {
+            resolve_decl(decl, &mut scope, &mut errors);
+        }
+    }
+    errors
+}
+
+/// Resolve each clause of `decl` with its parameters and the names of its `where` decls in
+/// scope. The `where` decls are resolved within the same scope, so they can refer to the
+/// clause's parameters and to each other.
+fn resolve_decl<'a>(decl: &Decl<'a>, scope: &mut Scope<'a>, errors: &mut Vec<ParseError<'a>>) {
+    for clause in &decl.clauses {
+        let mut names = Vec::new();
+        for predicate in &clause.predicates {
+            predicate.bound_names(&mut names);
+        }
+        names.extend(
+            clause
+                .where_decls
+                .iter()
+                .map(|where_decl| where_decl.id.name),
+        );
+        scope.with(&names, |scope| {
+            resolve_expr(&clause.body, scope, errors);
+            for where_decl in &clause.where_decls {
+                resolve_decl(where_decl, scope, errors);
+            }
+        });
+    }
+}
+
+/// Report every unbound symbol in `module` as an error in `diagnostics`.
+pub fn resolve_names<'a>(module: &Module<'a>, diagnostics: &mut Diagnostics<'a>) {
+    for err in unbound_symbols(module) {
+        diagnostics.error(err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn unbound(input: &'static str) -> Vec<String> {
+        unbound_symbols(&parse_str("test.mv", input).unwrap())
+            .iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn reports_only_unbound_symbols() {
+        let errors = unbound("f x = g x y\ng a = a");
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("test.mv:1:11: error[E0008]: `y` is not defined"),
+            "{}",
+            errors[0]
+        );
+    }
+
+    #[test]
+    fn imported_names_are_bound() {
+        assert!(
+            unbound("import Data.List (map, filter)\nf xs = map g (filter g xs)\ng = 1").is_empty()
+        );
+        let errors = unbound("import Data.List (map)\nf xs = foldr map xs");
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("test.mv:2:8: error[E0008]: `foldr` is not defined"),
+            "{}",
+            errors[0]
+        );
+        // Any name at all may come from an import of a whole module, or from an aliased one.
+        assert!(unbound("import Data.List\nf xs = foldr map xs").is_empty());
+        assert!(unbound("import Data.Map as M (insert)\nf = M.lookup insert").is_empty());
+    }
+
+    #[test]
+    fn operators_must_be_builtin_or_defined() {
+        let errors = unbound("infixl 6 <>\nf a b = a <> b + (<>) a b");
+        assert_eq!(errors.len(), 2);
+        assert!(
+            errors[0].starts_with("test.mv:2:11: error[E0008]: `(<>)` is not defined"),
+            "{}",
+            errors[0]
+        );
+        assert!(errors[1].starts_with("test.mv:2:19:"), "{}", errors[1]);
+        assert!(unbound("infixl 6 <>\n(<>) x y = x\nf a b = a <> b + (<>) a b").is_empty());
+    }
+
+    #[test]
+    fn respects_let_lambda_and_match_scopes() {
+        assert!(unbound("f x = let y = x in \\z -> y z").is_empty());
+        assert!(
+            unbound("data Maybe a = Nothing | Just a\nf m = match m Just v => v Nothing => m")
+                .is_empty()
+        );
+        // A let binding is not in scope in its own value, nor a lambda parameter outside it.
+        assert_eq!(unbound("f = let y = y in (\\z -> z) z").len(), 2);
+        assert!(unbound("f = let rec go n = go n in go 1").is_empty());
+        assert!(unbound("f = \\(a, b) [c] -> a b c").is_empty());
+        assert!(unbound("f = let rec even n = odd n; odd n = even n in even 2").is_empty());
+        // Separate `let rec`s are separate groups, so the outer one cannot see the inner.
+        let errors = unbound("f = let rec a = b in let rec b = a in a");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("`b` is not defined"), "{}", errors[0]);
+        assert_eq!(unbound("f = let go n = go n in go 1").len(), 1);
+        assert_eq!(unbound("f = let a = b; b = 1 in a").len(), 1);
+        assert!(unbound("f p = let (a, Just b) = p in a b").is_empty());
+        assert!(unbound("f g = do { a <- g; let b = a; g b; g a b }").is_empty());
+        assert_eq!(unbound("f g = do { g a; a <- g; a }").len(), 1);
+        assert!(unbound("f g = { let a = g; g a; a }").is_empty());
+        assert_eq!(unbound("f g = { g a; let a = g; a }").len(), 1);
+        assert_eq!(unbound("f = let (a, b) = a in b").len(), 1);
+        assert!(unbound("f = True").is_empty());
+        assert_eq!(unbound("f a = if a then b else let c = a in c").len(), 1);
+        assert_eq!(unbound("f a = \"{a} and {b}\"").len(), 1);
+        assert_eq!(unbound("f = true").len(), 1);
+        assert!(unbound("f xs = [(x, y) | x <- xs, x > 0, y <- [x, 1], y < x]").is_empty());
+        // A generator's names are in scope only after it.
+        assert_eq!(unbound("f xs = [x | x > 0, x <- xs]").len(), 1);
+        assert_eq!(unbound("f = [x | x <- x]").len(), 1);
+        // Each match arm binds only for itself.
+        let errors = unbound("f m = match m (a, b) if a => b _ => a");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("test.mv:1:37:"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn where_decls_are_scoped_to_their_clause() {
+        assert!(unbound("f x = g x where g y = x + y").is_empty());
+        assert!(unbound("f x = a where { a = b x; b y = y }").is_empty());
+        let errors = unbound(
+            "f 0 = g 1 where g y = y
+f n = g n",
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("test.mv:2:7:"), "{}", errors[0]);
+    }
+}
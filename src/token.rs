@@ -2,11 +2,45 @@ use crate::lexer::Lexeme;
 use crate::location::Location;
 use std::fmt;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// From `#` to the end of the line.
+    Comment,
+}
+
+/// Source text between tokens which the parser ignores. Only a lossless lexer keeps it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub location: Location<'a>,
+    pub text: &'a str,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 #[allow(dead_code)]
 pub struct Token<'a> {
     pub location: Location<'a>,
     pub lexeme: Lexeme<'a>,
+    /// The token's source text. Empty unless lexing losslessly.
+    pub text: &'a str,
+    /// The whitespace and comments before this token. Empty unless lexing losslessly.
+    pub leading_trivia: Vec<Trivia<'a>>,
+    /// The whitespace and comments after this token on the same line. Only `tokenize_lossless`
+    /// fills this in, since it needs to see the next token.
+    pub trailing_trivia: Vec<Trivia<'a>>,
+}
+
+impl<'a> Token<'a> {
+    pub fn new(location: Location<'a>, lexeme: Lexeme<'a>) -> Self {
+        Token {
+            location,
+            lexeme,
+            text: "",
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+        }
+    }
 }
 
 impl<'a> std::fmt::Display for Token<'a> {
@@ -0,0 +1,222 @@
+use crate::error::{ErrorCode, ParseError, ParseResult};
+use crate::identifier::Identifier;
+use crate::lexer::{Lexeme, Lexer};
+use crate::location::{HasLocation, Location};
+use crate::parser::is_keyword;
+use crate::token::Token;
+
+/// The syntax of a type, as written in data declarations.
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum TypeExpr<'a> {
+    /// A concrete type or type constructor, such as `Int` or `Maybe`.
+    Named(Identifier<'a>),
+    /// A type variable, such as `a`.
+    Variable(Identifier<'a>),
+    Application {
+        function: Box<TypeExpr<'a>>,
+        arguments: Vec<TypeExpr<'a>>,
+    },
+    /// `(a, b)`, or `()` for the unit type.
+    Tuple {
+        location: Location<'a>,
+        dims: Vec<TypeExpr<'a>>,
+    },
+    /// `[a]`
+    List {
+        location: Location<'a>,
+        element: Box<TypeExpr<'a>>,
+    },
+    /// `a -> b`. A function of several arguments is curried, so `a -> b -> c` is
+    /// `a -> (b -> c)`.
+    Function {
+        parameter: Box<TypeExpr<'a>>,
+        result: Box<TypeExpr<'a>>,
+    },
+}
+
+impl<'a> HasLocation<'a> for TypeExpr<'a> {
+    fn get_location(&self) -> &Location<'a> {
+        match self {
+            TypeExpr::Named(id) => id.get_location(),
+            TypeExpr::Variable(id) => id.get_location(),
+            TypeExpr::Application {
+                function,
+                arguments: _,
+            } => function.get_location(),
+            TypeExpr::Tuple { location, dims: _ } => location,
+            TypeExpr::List {
+                location,
+                element: _,
+            } => location,
+            TypeExpr::Function {
+                parameter,
+                result: _,
+            } => parameter.get_location(),
+        }
+    }
+}
+
+/// Parse a single type term: a name, a type variable, or a bracketed type. Returns None if the
+/// next token cannot begin a type, leaving it unconsumed.
+pub fn parse_type_atom<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, Option<TypeExpr<'a>>> {
+    let token = lexer.peek();
+    match token.lexeme {
+        Lexeme::Identifier(name) if !is_keyword(name) => {
+            lexer.advance()?;
+            let id = lexer.identifier(name, token.location);
+            if name.chars().next().is_some_and(char::is_uppercase) {
+                Ok(Some(TypeExpr::Named(id)))
+            } else {
+                Ok(Some(TypeExpr::Variable(id)))
+            }
+        }
+        Lexeme::LParen => {
+            lexer.advance()?;
+            let mut dims = Vec::new();
+            while !lexer.peek_matches(Lexeme::RParen) {
+                dims.push(parse_type_expr(lexer)?);
+                if lexer.peek_matches(Lexeme::Comma) {
+                    lexer.advance()?;
+                } else {
+                    break;
+                }
+            }
+            close_bracket(lexer, token.location, Lexeme::RParen)?;
+            if dims.len() == 1 {
+                Ok(dims.pop())
+            } else {
+                Ok(Some(TypeExpr::Tuple {
+                    location: token.location,
+                    dims,
+                }))
+            }
+        }
+        Lexeme::LSquare => {
+            lexer.advance()?;
+            let element = parse_type_expr(lexer)?;
+            close_bracket(lexer, token.location, Lexeme::RSquare)?;
+            Ok(Some(TypeExpr::List {
+                location: token.location,
+                element: element.into(),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parse a whole type, such as `(a -> b) -> [a] -> [b]`. The function arrow binds more loosely
+/// than application and groups to the right.
+pub fn parse_type_expr<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, TypeExpr<'a>> {
+    let parameter = parse_type_application(lexer)?;
+    if !lexer.peek_matches(Lexeme::Operator("->")) {
+        return Ok(parameter);
+    }
+    lexer.advance()?;
+    Ok(TypeExpr::Function {
+        parameter: parameter.into(),
+        result: lexer.nested(parse_type_expr)?.into(),
+    })
+}
+
+/// Parse a type term applied to zero or more arguments, such as `Either e (Maybe a)`.
+pub fn parse_type_application<'a>(lexer: &mut Lexer<'a>) -> ParseResult<'a, TypeExpr<'a>> {
+    let function = match lexer.nested(parse_type_atom)? {
+        Some(function) => function,
+        None => return Err(ParseError::unexpected(lexer.peek(), "a type")),
+    };
+    let mut arguments = Vec::new();
+    while let Some(argument) = lexer.nested(parse_type_atom)? {
+        arguments.push(argument);
+    }
+    if arguments.is_empty() {
+        Ok(function)
+    } else {
+        Ok(TypeExpr::Application {
+            function: function.into(),
+            arguments,
+        })
+    }
+}
+
+fn close_bracket<'a>(
+    lexer: &mut Lexer<'a>,
+    open_location: Location<'a>,
+    close: Lexeme<'a>,
+) -> ParseResult<'a, ()> {
+    if lexer.peek_matches(close.clone()) {
+        return lexer.advance();
+    }
+    Err(ParseError::error(
+        ErrorCode::UnbalancedBracket,
+        open_location,
+        match lexer.peek() {
+            Token {
+                lexeme: Lexeme::Eof,
+                ..
+            } => "hit EOF before this type was closed".to_string(),
+            token => format!("expected {:?} to close this type, found {token}", close),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(type_expr: &TypeExpr) -> String {
+        match type_expr {
+            TypeExpr::Named(id) | TypeExpr::Variable(id) => id.name.to_string(),
+            TypeExpr::Application {
+                function,
+                arguments,
+            } => {
+                let arguments: Vec<String> = arguments.iter().map(shape).collect();
+                format!("({} {})", shape(function), arguments.join(" "))
+            }
+            TypeExpr::Tuple { dims, .. } => {
+                let dims: Vec<String> = dims.iter().map(shape).collect();
+                format!("(tuple {})", dims.join(" "))
+            }
+            TypeExpr::List { element, .. } => format!("[{}]", shape(element)),
+            TypeExpr::Function { parameter, result } => {
+                format!("(-> {} {})", shape(parameter), shape(result))
+            }
+        }
+    }
+
+    fn parse(input: &'static str) -> ParseResult<'static, TypeExpr<'static>> {
+        let mut lexer = Lexer::new("test.mv", input);
+        lexer.advance()?;
+        parse_type_expr(&mut lexer)
+    }
+
+    #[test]
+    fn arrows_group_right_and_bind_loosest() {
+        assert_eq!(
+            shape(&parse("(a -> b) -> [a] -> [b]").unwrap()),
+            "(-> (-> a b) (-> [a] [b]))"
+        );
+        assert_eq!(
+            shape(&parse("Maybe a -> Either e (Maybe b)").unwrap()),
+            "(-> (Maybe a) (Either e (Maybe b)))"
+        );
+        assert_eq!(
+            shape(&parse("(a, b -> c) -> [a -> b]").unwrap()),
+            "(-> (tuple a (-> b c)) [(-> a b)])"
+        );
+        assert_eq!(shape(&parse("()").unwrap()), "(tuple )");
+        let location = *parse("  a -> b").unwrap().get_location();
+        assert_eq!((location.line, location.col), (1, 3));
+    }
+
+    #[test]
+    fn type_errors() {
+        let err = parse("a ->").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:5: error[E0004]:"), "{}", err);
+        let err = parse("(a -> b").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:1: error[E0003]:"), "{}", err);
+        let err = parse("-> a").unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:1: error[E0001]:"), "{}", err);
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn write_fixture(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mueve-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mueve"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn run_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mueve"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn reads_program_from_stdin() {
+    let output = run_with_stdin(&[], "id x = x");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("parsing '<stdin>'"));
+}
+
+#[test]
+fn reads_program_from_stdin_with_dash() {
+    let output = run_with_stdin(&["-"], "id x = )");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("<stdin>:1:"));
+}
+
+#[test]
+fn dump_tokens_prints_one_token_per_line() {
+    let path = write_fixture("dump_tokens.mv", "id x = x");
+    let output = run(&["--dump-tokens", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(
+        lines[0].ends_with(":1:1: Identifier(\"id\")"),
+        "{}",
+        lines[0]
+    );
+    assert!(lines[2].ends_with(":1:6: Equals"), "{}", lines[2]);
+}
+
+#[test]
+fn check_brackets_reports_mismatches() {
+    let path = write_fixture("check_brackets.mv", "f = g (a]\nh = 1");
+    let output = run(&["--check-brackets", path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.contains("error["))
+        .collect();
+    assert_eq!(lines.len(), 2, "{}", stderr);
+    assert!(lines[0].contains(":1:9: error[E0003]:"), "{}", lines[0]);
+    assert!(lines[1].contains(":1:7: error[E0003]:"), "{}", lines[1]);
+
+    let path = write_fixture("check_brackets_ok.mv", "f = g (a) [b]");
+    let output = run(&["--check-brackets", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn dump_ast_pretty_prints_decls() {
+    let path = write_fixture("dump_ast.mv", "add a b = a + b\ninc x = add x 1");
+    let output = run(&["--dump-ast", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("Module {"), "{}", stdout);
+    assert!(stdout.contains("    Decl {"), "{}", stdout);
+    assert!(stdout.contains("Callsite {"), "{}", stdout);
+}
+
+#[test]
+fn error_format_json_emits_one_object_per_error() {
+    let path = write_fixture("error_format_json.mv", "f = a + * b\ng x = match x\n");
+    let output = run(&["--error-format=json", path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", stderr);
+    for line in &lines {
+        assert!(line.starts_with("{\"file\":\""), "{}", line);
+        assert!(line.ends_with("\"}"), "{}", line);
+    }
+    assert!(lines[0].contains("\"line\":1,\"col\":9,\"level\":\"error\",\"code\":\"E0001\""));
+    assert!(lines[1].contains("\"line\":2,"));
+}
+
+#[test]
+fn compiles_the_sample_program() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sample.mv");
+    let output = run(&[path]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.is_empty(), "{}", stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Parsed Module {"), "{}", stdout);
+}
+
+#[test]
+fn deeply_nested_input_is_an_error_rather_than_a_crash() {
+    let depth = 100_000;
+    let source = format!("f = {}1{}\n", "(".repeat(depth), ")".repeat(depth));
+    let path = write_fixture("deeply_nested.mv", &source);
+    let output = run(&[path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(":1:205: error[E0009]: expression nesting too deep"),
+        "{}",
+        stderr
+    );
+}
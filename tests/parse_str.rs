@@ -0,0 +1,97 @@
+use mueve::location::HasLocation;
+use mueve::{parse_str, parse_str_with_diagnostics, to_sexpr, Item};
+
+#[test]
+fn parse_str_returns_a_module() {
+    let module = parse_str("test.mv", "module Math\nadd_one x = add x 1").unwrap();
+    assert_eq!(module.name.unwrap().to_string(), "Math");
+    assert_eq!(module.items.len(), 1);
+    let location = module.items[0].get_location();
+    assert_eq!(location.filename, "test.mv");
+    assert_eq!(location.line, 2);
+}
+
+#[test]
+fn decls_can_be_read_through_their_accessors() {
+    let module = parse_str(
+        "test.mv",
+        "data Pair a b = Pair a b | Empty\nswap (Pair a b) = Pair b a where c = 1\nswap p = p",
+    )
+    .unwrap();
+    let Item::Data(data_decl) = &module.items[0] else {
+        panic!("expected a data decl, got {:?}", module.items[0]);
+    };
+    assert_eq!(data_decl.name().name, "Pair");
+    let params: Vec<_> = data_decl.type_params().iter().map(|id| id.name).collect();
+    assert_eq!(params, ["a", "b"]);
+    let ctors: Vec<_> = data_decl
+        .ctors()
+        .iter()
+        .map(|(ctor, args)| (ctor.name, args.len()))
+        .collect();
+    assert_eq!(ctors, [("Pair", 2), ("Empty", 0)]);
+    let Item::Decl(decl) = &module.items[1] else {
+        panic!("expected a decl, got {:?}", module.items[1]);
+    };
+    assert_eq!(decl.id().name, "swap");
+    let clause = &decl.clauses()[0];
+    assert_eq!(clause.predicates().len(), 1);
+    assert_eq!(
+        to_sexpr(clause.body()),
+        "(callsite (symbol Pair) (symbol b) (symbol a))"
+    );
+    assert_eq!(clause.where_decls()[0].id().name, "c");
+    assert!(decl.clauses()[1].where_decls().is_empty());
+}
+
+#[test]
+fn parse_str_reports_errors() {
+    let err = parse_str("test.mv", "f x = ").unwrap_err();
+    assert!(err.to_string().starts_with("test.mv:1:"));
+}
+
+#[test]
+fn decl_after_unparseable_line_still_parses() {
+    let (module, diagnostics) = parse_str_with_diagnostics("test.mv", "f = ) =\ng x = x");
+    assert_eq!(diagnostics.errors().len(), 1);
+    assert_eq!(module.items.len(), 1);
+    assert_eq!(module.items[0].get_location().line, 2);
+}
+
+#[test]
+fn deeply_nested_input_fails_without_overflowing() {
+    let depth = 100_000;
+    let cases = [
+        format!("f = {}1{}", "(".repeat(depth), ")".repeat(depth)),
+        format!("f = {}1", "(".repeat(depth)),
+        format!("f {}x{} = x", "(".repeat(depth), ")".repeat(depth)),
+        format!("f = 1{}", " ^ 1".repeat(depth)),
+        format!("f = 1{}", " + 1".repeat(depth)),
+        format!("f = 1{}", " `div` 1".repeat(depth)),
+        format!("f = {}1", "- ".repeat(depth)),
+        format!("f (x{}) = x", " : x".repeat(depth)),
+        format!("data T = T {}A{}", "(".repeat(depth), ")".repeat(depth)),
+        format!("f = 1 where {}h = 1", "g = 1 where ".repeat(1000)),
+    ];
+    // The default limit is meant for a main thread's 8 MiB stack, which test threads don't get.
+    std::thread::Builder::new()
+        .stack_size(8 << 20)
+        .spawn(move || {
+            for input in &cases {
+                let (_, diagnostics) = parse_str_with_diagnostics("test.mv", input);
+                assert!(
+                    diagnostics.errors().iter().any(|err| err
+                        .to_string()
+                        .contains("error[E0009]: expression nesting too deep")),
+                    "{:?}",
+                    diagnostics.errors().first().map(|err| err.to_string())
+                );
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    // Nesting well within the limit is fine.
+    let input = format!("f = {}1{}", "(".repeat(50), ")".repeat(50));
+    assert!(parse_str("test.mv", &input).is_ok());
+}
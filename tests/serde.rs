@@ -0,0 +1,35 @@
+#![cfg(feature = "serde")]
+
+use mueve::{decls_to_json, parse_str, Decl, Item};
+
+fn decls(module: mueve::Module) -> Vec<Decl> {
+    module
+        .items
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Decl(decl) => Some(decl),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn decls_serialize_to_json() {
+    let module = parse_str("test.mv", "half x = div x 2.5\nname = \"mu\"").unwrap();
+    let json = decls_to_json(&decls(module));
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let decls = value.as_array().unwrap();
+    assert_eq!(decls.len(), 2);
+    assert_eq!(decls[0]["id"]["name"], "half");
+    assert_eq!(decls[0]["id"]["location"]["line"], 1);
+    assert_eq!(
+        decls[0]["clauses"][0]["predicates"][0]["Irrefutable"]["name"],
+        "x"
+    );
+    let arguments = &decls[0]["clauses"][0]["body"]["Callsite"]["arguments"];
+    assert_eq!(arguments[1]["LiteralFloat"]["value"], 2.5);
+    assert_eq!(
+        decls[1]["clauses"][0]["body"]["LiteralString"]["value"],
+        "mu"
+    );
+}
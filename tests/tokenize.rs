@@ -0,0 +1,410 @@
+use mueve::{tokenize, tokenize_lossless, Lexeme, Token, Trivia, TriviaKind};
+
+#[test]
+fn tokenize_snippet() {
+    let tokens = tokenize("test.mv", "inc x = add x 1").unwrap();
+    let lexemes: Vec<Lexeme> = tokens.into_iter().map(|token| token.lexeme).collect();
+    assert_eq!(
+        lexemes,
+        vec![
+            Lexeme::Identifier("inc"),
+            Lexeme::Identifier("x"),
+            Lexeme::Equals,
+            Lexeme::Identifier("add"),
+            Lexeme::Identifier("x"),
+            Lexeme::Signed(1),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_records_locations() {
+    let tokens = tokenize("test.mv", "a (b)").unwrap();
+    assert_eq!(tokens.len(), 4);
+    assert_eq!(tokens[0].location.col, 1);
+    assert_eq!(tokens[1].location.col, 3);
+}
+
+fn lexemes(input: &'static str) -> Vec<Lexeme<'static>> {
+    tokenize("test.mv", input)
+        .unwrap()
+        .into_iter()
+        .map(|token| token.lexeme)
+        .collect()
+}
+
+#[test]
+fn top_level_newlines_separate() {
+    assert_eq!(
+        lexemes("a\n\nb\n"),
+        vec![
+            Lexeme::Identifier("a"),
+            Lexeme::Semicolon,
+            Lexeme::Identifier("b"),
+        ]
+    );
+}
+
+#[test]
+fn indented_lines_continue() {
+    assert_eq!(
+        lexemes("a\n  b\nc"),
+        vec![
+            Lexeme::Identifier("a"),
+            Lexeme::Identifier("b"),
+            Lexeme::Semicolon,
+            Lexeme::Identifier("c"),
+        ]
+    );
+}
+
+#[test]
+fn newlines_inside_parens_are_whitespace() {
+    assert_eq!(
+        lexemes("(a\nb)"),
+        vec![
+            Lexeme::LParen,
+            Lexeme::Identifier("a"),
+            Lexeme::Identifier("b"),
+            Lexeme::RParen,
+        ]
+    );
+}
+
+#[test]
+fn newlines_track_lines() {
+    let tokens = tokenize("test.mv", "a\nbc\n  d").unwrap();
+    let locations: Vec<(i32, i32)> = tokens
+        .iter()
+        .map(|token| (token.location.line, token.location.col))
+        .collect();
+    assert_eq!(locations, vec![(1, 1), (1, 2), (2, 1), (3, 3)]);
+}
+
+#[test]
+fn cons_does_not_glue_onto_equals() {
+    assert_eq!(
+        lexemes("f (x:xs) = x"),
+        vec![
+            Lexeme::Identifier("f"),
+            Lexeme::LParen,
+            Lexeme::Identifier("x"),
+            Lexeme::Operator(":"),
+            Lexeme::Identifier("xs"),
+            Lexeme::RParen,
+            Lexeme::Equals,
+            Lexeme::Identifier("x"),
+        ]
+    );
+}
+
+#[test]
+fn float_literals() {
+    assert_eq!(
+        lexemes("1.5 -0.25 1..2"),
+        vec![
+            Lexeme::Float(1.5),
+            Lexeme::Float(-0.25),
+            Lexeme::Signed(1),
+            Lexeme::Operator(".."),
+            Lexeme::Signed(2),
+        ]
+    );
+}
+
+#[test]
+fn dots_lex_by_maximal_munch() {
+    assert_eq!(
+        lexemes("x.y..z ... ..= 1..=2"),
+        vec![
+            Lexeme::Identifier("x"),
+            Lexeme::Operator("."),
+            Lexeme::Identifier("y"),
+            Lexeme::Operator(".."),
+            Lexeme::Identifier("z"),
+            Lexeme::Operator("..."),
+            Lexeme::Operator("..="),
+            Lexeme::Signed(1),
+            Lexeme::Operator("..="),
+            Lexeme::Signed(2),
+        ]
+    );
+}
+
+#[test]
+// `3.14` is the literal under test, not an approximation of pi.
+#[allow(clippy::approx_constant)]
+fn negative_and_extreme_float_literals() {
+    let floats = |input: &str| -> Vec<f64> {
+        tokenize("test.mv", input)
+            .unwrap()
+            .into_iter()
+            .map(|token| match token.lexeme {
+                Lexeme::Float(value) => value,
+                lexeme => panic!("expected a float, found {:?}", lexeme),
+            })
+            .collect()
+    };
+    assert_eq!(floats("-3.14 -0.0"), vec![-3.14, 0.0]);
+    assert!(floats("-0.0")[0].is_sign_negative());
+    let max = format!("{:.1}", f64::MAX);
+    assert_eq!(floats(&max), vec![f64::MAX]);
+    assert_eq!(floats(&format!("-{}", max)), vec![f64::MIN]);
+    let smallest_subnormal = format!("{:.400}", f64::from_bits(1));
+    assert_eq!(floats(&smallest_subnormal), vec![f64::from_bits(1)]);
+
+    for input in [format!("1{}", max), format!("-1{}", max)] {
+        let err = tokenize("test.mv", &input).unwrap_err().to_string();
+        assert!(err.starts_with("test.mv:1:1: error[E0001]:"), "{}", err);
+        assert!(err.contains("too large to represent as a float"), "{}", err);
+    }
+}
+
+#[test]
+fn integer_literals_must_fit_in_64_bits() {
+    assert_eq!(
+        lexemes("9223372036854775807 -9223372036854775808"),
+        vec![Lexeme::Signed(i64::MAX), Lexeme::Signed(i64::MIN)]
+    );
+    let err = tokenize("test.mv", "f = 9223372036854775808")
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("test.mv:1:5: error[E0001]:"), "{}", err);
+    assert!(err.contains("does not fit in a 64-bit integer"), "{}", err);
+}
+
+#[test]
+fn leading_zeros_are_rejected() {
+    assert_eq!(
+        lexemes("0 -0 0.5 10"),
+        vec![
+            Lexeme::Signed(0),
+            Lexeme::Signed(0),
+            Lexeme::Float(0.5),
+            Lexeme::Signed(10),
+        ]
+    );
+    for (input, col) in [("0123", 1), ("f -007", 3), ("00.5", 1)] {
+        let err = tokenize("test.mv", input).unwrap_err().to_string();
+        assert!(
+            err.starts_with(&format!("test.mv:1:{}: error[E0001]:", col)),
+            "{}",
+            err
+        );
+        assert!(err.contains("leading zero"), "{}", err);
+    }
+}
+
+#[test]
+fn numbers_must_not_run_into_names() {
+    for (input, col, number) in [
+        ("0x10", 1, "0x10"),
+        ("f 12abc", 3, "12abc"),
+        ("-1_000", 1, "-1_000"),
+        ("x = 1.5e3", 5, "1.5e3"),
+    ] {
+        let err = tokenize("test.mv", input).unwrap_err().to_string();
+        assert!(
+            err.starts_with(&format!("test.mv:1:{}: error[E0001]:", col)),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains(&format!("`{}` is not a number", number)),
+            "{}",
+            err
+        );
+    }
+    assert_eq!(
+        lexemes("x1 1.5.x 1..n"),
+        vec![
+            Lexeme::Identifier("x1"),
+            Lexeme::Float(1.5),
+            Lexeme::Operator("."),
+            Lexeme::Identifier("x"),
+            Lexeme::Signed(1),
+            Lexeme::Operator(".."),
+            Lexeme::Identifier("n"),
+        ]
+    );
+}
+
+#[test]
+fn minus_after_an_operand_is_not_a_sign() {
+    assert_eq!(
+        lexemes("n-1 (-1) [x, -2] f -3 (n)-4"),
+        vec![
+            Lexeme::Identifier("n"),
+            Lexeme::Operator("-"),
+            Lexeme::Signed(1),
+            Lexeme::LParen,
+            Lexeme::Signed(-1),
+            Lexeme::RParen,
+            Lexeme::LSquare,
+            Lexeme::Identifier("x"),
+            Lexeme::Comma,
+            Lexeme::Signed(-2),
+            Lexeme::RSquare,
+            Lexeme::Identifier("f"),
+            Lexeme::Signed(-3),
+            Lexeme::LParen,
+            Lexeme::Identifier("n"),
+            Lexeme::RParen,
+            Lexeme::Operator("-"),
+            Lexeme::Signed(4),
+        ]
+    );
+}
+
+#[test]
+fn interpolated_strings() {
+    assert_eq!(
+        lexemes(r#"f "a{b}c" "{{b}}" "{ {x = 1}.x }""#),
+        vec![
+            Lexeme::Identifier("f"),
+            Lexeme::InterpString(r#""a{b}c""#),
+            Lexeme::QuotedString(r#""{{b}}""#),
+            Lexeme::InterpString(r#""{ {x = 1}.x }""#),
+        ]
+    );
+    for (input, col) in [
+        (r#"f = "a}b""#, 7),
+        (r#"f = "a{b""#, 7),
+        (r#"f = "a{g "x"}""#, 7),
+    ] {
+        let err = tokenize("test.mv", input).unwrap_err().to_string();
+        assert!(
+            err.starts_with(&format!("test.mv:1:{}: error[E0003]:", col)),
+            "{}: {}",
+            input,
+            err
+        );
+    }
+}
+
+#[test]
+fn equals_is_not_an_operator() {
+    assert_eq!(
+        lexemes("a = b == c => d"),
+        vec![
+            Lexeme::Identifier("a"),
+            Lexeme::Equals,
+            Lexeme::Identifier("b"),
+            Lexeme::Operator("=="),
+            Lexeme::Identifier("c"),
+            Lexeme::Operator("=>"),
+            Lexeme::Identifier("d"),
+        ]
+    );
+}
+
+fn trivia(token: &Token<'static>) -> (Vec<&'static str>, Vec<&'static str>) {
+    let texts = |trivia: &[Trivia<'static>]| trivia.iter().map(|trivia| trivia.text).collect();
+    (texts(&token.leading_trivia), texts(&token.trailing_trivia))
+}
+
+#[test]
+fn comments_are_skipped() {
+    assert_eq!(
+        lexemes("a # note\nb"),
+        vec![
+            Lexeme::Identifier("a"),
+            Lexeme::Semicolon,
+            Lexeme::Identifier("b"),
+        ]
+    );
+}
+
+#[test]
+fn attributes_are_not_comments() {
+    assert_eq!(
+        lexemes("#[inline] # note\n#[a\n1]\nf"),
+        vec![
+            Lexeme::HashSquare,
+            Lexeme::Identifier("inline"),
+            Lexeme::RSquare,
+            Lexeme::Semicolon,
+            Lexeme::HashSquare,
+            Lexeme::Identifier("a"),
+            Lexeme::Signed(1),
+            Lexeme::RSquare,
+            Lexeme::Semicolon,
+            Lexeme::Identifier("f"),
+        ]
+    );
+}
+
+#[test]
+fn lossless_tokens_keep_trivia() {
+    let tokens = tokenize_lossless("test.mv", "a  b\t\n\n  c\nd  ").unwrap();
+    let trivia: Vec<_> = tokens.iter().map(trivia).collect();
+    assert_eq!(
+        trivia,
+        vec![
+            (vec![], vec!["  "]),
+            (vec![], vec!["\t"]),
+            (vec!["\n", "\n  "], vec![]),
+            (vec![], vec![]),
+            (vec![], vec!["  "]),
+        ]
+    );
+    let indentation = &tokens[2].leading_trivia[1];
+    assert_eq!(indentation.kind, TriviaKind::Whitespace);
+    assert_eq!(
+        (indentation.location.line, indentation.location.col),
+        (2, 1)
+    );
+    // Lexing normally drops the trivia.
+    assert!(tokenize("test.mv", "a  b\n c")
+        .unwrap()
+        .iter()
+        .all(|token| token.leading_trivia.is_empty() && token.trailing_trivia.is_empty()));
+}
+
+#[test]
+fn lossless_tokens_keep_comments() {
+    let tokens = tokenize_lossless("test.mv", "a # x\nb # y\n\n  # z\nc\n# end").unwrap();
+    assert_eq!(
+        tokens.iter().map(trivia).collect::<Vec<_>>(),
+        vec![
+            (vec![], vec![" ", "# x"]),
+            (vec![], vec![]),
+            (vec![], vec![" ", "# y"]),
+            (vec![], vec![]),
+            (vec!["\n", "  ", "# z", "\n"], vec!["\n", "# end"]),
+        ]
+    );
+    let comment = &tokens[4].leading_trivia[2];
+    assert_eq!(comment.kind, TriviaKind::Comment);
+    assert_eq!((comment.location.line, comment.location.col), (4, 3));
+    // A comment between two tokens on one line is attached as trailing trivia of the first.
+    let tokens = tokenize_lossless("test.mv", "f = 1 # one\n").unwrap();
+    assert_eq!(trivia(&tokens[2]), (vec![], vec![" ", "# one", "\n"]));
+    assert_eq!(tokens[2].trailing_trivia[1].kind, TriviaKind::Comment);
+    assert!(tokenize("test.mv", "a # x\nb")
+        .unwrap()
+        .iter()
+        .all(|token| token.leading_trivia.is_empty() && token.trailing_trivia.is_empty()));
+}
+
+#[test]
+fn lossless_tokens_reproduce_the_input() {
+    let inputs = [
+        "f x = g x\n",
+        "  f (x, [y]) =\n    let a = \"s {x}\" in\t'c' : a\n\n\ng = { h; k }  \n",
+        "#[inline]\nf = \\x -> x ?y .. 1.5 `div` -2\r\n",
+        "# head\nf x = # why\n  g x # note\n\n# tail",
+    ];
+    for input in inputs {
+        let tokens = tokenize_lossless("test.mv", input).unwrap();
+        let mut output = String::new();
+        for token in &tokens {
+            let trivia =
+                |trivia: &[Trivia]| trivia.iter().map(|trivia| trivia.text).collect::<String>();
+            output.push_str(&trivia(&token.leading_trivia));
+            output.push_str(token.text);
+            output.push_str(&trivia(&token.trailing_trivia));
+        }
+        assert_eq!(output, input);
+    }
+}